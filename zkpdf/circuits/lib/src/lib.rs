@@ -3,5 +3,7 @@ sol! {
     /// The public values encoded as a struct that can be easily deserialized inside Solidity.
     struct PublicValuesStruct {
         bool result;
+        /// `MatchMode` flags the substring was compared under.
+        uint8 matchMode;
     }
 }
@@ -1,7 +1,7 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use core::verify_text;
+use core::{verify_text_with_mode, MatchMode};
 
 use alloy_sol_types::SolType;
 use fibonacci_lib::PublicValuesStruct;
@@ -10,10 +10,16 @@ pub fn main() {
     let pdf_bytes = sp1_zkvm::io::read::<Vec<u8>>();
     let page_number = sp1_zkvm::io::read::<u8>();
     let sub_string = sp1_zkvm::io::read::<String>();
+    let match_mode = sp1_zkvm::io::read::<u8>();
 
-    let is_valid = verify_text(pdf_bytes, page_number, &sub_string).is_ok();
+    let is_valid = MatchMode::from_bits(match_mode)
+        .and_then(|mode| verify_text_with_mode(pdf_bytes, page_number, &sub_string, mode))
+        .unwrap_or(false);
 
-    let bytes = PublicValuesStruct::abi_encode(&PublicValuesStruct { result: is_valid });
+    let bytes = PublicValuesStruct::abi_encode(&PublicValuesStruct {
+        result: is_valid,
+        matchMode: match_mode,
+    });
 
     // Commit to the public values of the program. The final proof will have a commitment to all the
     // bytes that were committed to.
@@ -48,6 +48,7 @@ enum ProofSystem {
 #[serde(rename_all = "camelCase")]
 struct SP1FibonacciProofFixture {
     result: bool,
+    match_mode: u8,
     vkey: String,
     public_values: String,
     proof: String,
@@ -73,10 +74,13 @@ fn main() {
     // Setup the inputs.
     let name = "Sample Signed PDF Document";
     let page_number: u8 = 0;
+    // Exact matching (`core::MatchMode::EXACT`).
+    let match_mode: u8 = 0;
     let mut stdin = SP1Stdin::new();
     stdin.write(&pdf_bytes);
     stdin.write(&page_number);
     stdin.write(&name.to_string());
+    stdin.write(&match_mode);
 
     println!("pdf_path: {}", args.pdf_path);
     println!("Proof System: {:?}", args.system);
@@ -99,11 +103,12 @@ fn create_proof_fixture(
 ) {
     // Deserialize the public values.
     let bytes = proof.public_values.as_slice();
-    let PublicValuesStruct { result } = PublicValuesStruct::abi_decode(bytes, false).unwrap();
+    let public_values = PublicValuesStruct::abi_decode(bytes, false).unwrap();
 
     // Create the testing fixture so we can test things end-to-end.
     let fixture = SP1FibonacciProofFixture {
-        result,
+        result: public_values.result,
+        match_mode: public_values.matchMode,
         vkey: vk.bytes32().to_string(),
         public_values: format!("0x{}", hex::encode(bytes)),
         proof: format!("0x{}", hex::encode(proof.bytes())),
@@ -57,12 +57,15 @@ fn main() {
 
     let name = "Sample Signed PDF Document";
     let page_number: u8 = 0;
+    // Exact matching (`core::MatchMode::EXACT`).
+    let match_mode: u8 = 0;
 
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
     stdin.write(&pdf_bytes);
     stdin.write(&page_number);
     stdin.write(&name.to_string());
+    stdin.write(&match_mode);
 
     println!("pdf_path: {}", args.pdf_path);
 
@@ -73,8 +76,8 @@ fn main() {
 
         // Read the output.
         let decoded = PublicValuesStruct::abi_decode(output.as_slice(), true).unwrap();
-        let PublicValuesStruct { result } = decoded;
-        println!("Result: {}", result);
+        println!("Result: {}", decoded.result);
+        println!("Match mode: {:#04x}", decoded.matchMode);
         println!("Number of cycles: {}", report.total_instruction_count());
     } else {
         // Setup the program for proving.
@@ -12,6 +12,9 @@ struct ProofRequest {
     pdf_bytes: Vec<u8>,
     page_number: u8,
     sub_string: String,
+    /// `MatchMode` flags; exact matching when omitted.
+    #[serde(default)]
+    match_mode: u8,
 }
 
 #[derive(Serialize)]
@@ -28,6 +31,7 @@ async fn prove(Json(body): Json<ProofRequest>) -> Json<SP1ProofWithPublicValues>
     stdin.write(&body.pdf_bytes);
     stdin.write(&body.page_number);
     stdin.write(&body.sub_string);
+    stdin.write(&body.match_mode);
 
     let proof = client
         .prove(&pk, &stdin)
@@ -1,11 +1,28 @@
-use extractor::extract_text;
+use extractor::extract_text_with_options;
+use extractor::types::ExtractOptions;
 use signature_validator::verify_pdf_signature;
 
+mod matching;
+
+pub use matching::MatchMode;
+
 /// Verifies PDF signature and checks that `sub_string` appears at a specific `position`
 /// in the given `page_number`. Returns a detailed error if any step fails.
 /// Verifies PDF signature and checks that `sub_string` appears anywhere
 /// in the given `page_number`. Returns a detailed error if any step fails.
 pub fn verify_text(pdf_bytes: Vec<u8>, page_number: u8, sub_string: &str) -> Result<bool, String> {
+    verify_text_with_mode(pdf_bytes, page_number, sub_string, MatchMode::EXACT)
+}
+
+/// Like [`verify_text`], but compares the page text and `sub_string` under the
+/// equivalences of `mode`. Callers that publish the result should commit to
+/// `mode.to_bits()` alongside it.
+pub fn verify_text_with_mode(
+    pdf_bytes: Vec<u8>,
+    page_number: u8,
+    sub_string: &str,
+    mode: MatchMode,
+) -> Result<bool, String> {
     // Step 1: verify signature
     let is_valid = verify_pdf_signature(&pdf_bytes)
         .map_err(|e| format!("signature verification error: {}", e))?;
@@ -14,7 +31,12 @@ pub fn verify_text(pdf_bytes: Vec<u8>, page_number: u8, sub_string: &str) -> Res
     }
 
     // Step 2: extract text
-    let pages = extract_text(pdf_bytes).map_err(|e| format!("text extraction error: {:?}", e))?;
+    let options = ExtractOptions {
+        visible_only: mode.visible_only,
+        ..ExtractOptions::default()
+    };
+    let pages = extract_text_with_options(pdf_bytes, &options)
+        .map_err(|e| format!("text extraction error: {:?}", e))?;
     let index = page_number as usize;
     if index >= pages.len() {
         return Err(format!(
@@ -26,7 +48,7 @@ pub fn verify_text(pdf_bytes: Vec<u8>, page_number: u8, sub_string: &str) -> Res
 
     // Step 3: check if substring is contained anywhere in the page
     let page_text = &pages[index];
-    Ok(page_text.contains(sub_string))
+    Ok(mode.contains(page_text, sub_string))
 }
 
 #[cfg(test)]
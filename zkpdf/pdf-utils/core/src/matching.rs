@@ -0,0 +1,180 @@
+use extractor::{is_unspaced_script, normalize_text};
+
+/// Equivalences applied to both the page text and the claimed substring
+/// before checking containment. The default is an exact, byte-for-byte match.
+///
+/// A mode is committed as a single byte (see [`MatchMode::to_bits`]) so that a
+/// verifier knows exactly which equivalences a `true` result relied on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchMode {
+    /// Drop all spaces, tabs and other non-line-break whitespace.
+    pub ignore_whitespace: bool,
+    /// Treat a line break, with the whitespace around it, as a single space
+    /// between Latin words and as nothing next to CJK text.
+    pub ignore_line_breaks: bool,
+    /// Compare after [`extractor::normalize_text`]: full-width and half-width
+    /// forms, CJK radical and compatibility variants, and NFC composition.
+    pub ignore_width: bool,
+    /// Lowercase Latin-script letters; other scripts are left untouched.
+    pub ignore_latin_case: bool,
+    /// Match against visible text only, leaving out glyphs the extractor
+    /// marks as hidden (invisible render mode, same colour as the background,
+    /// clipped or outside the crop box, too small to read).
+    pub visible_only: bool,
+}
+
+const WHITESPACE_BIT: u8 = 1 << 0;
+const LINE_BREAKS_BIT: u8 = 1 << 1;
+const WIDTH_BIT: u8 = 1 << 2;
+const LATIN_CASE_BIT: u8 = 1 << 3;
+const VISIBLE_ONLY_BIT: u8 = 1 << 4;
+const ALL_BITS: u8 =
+    WHITESPACE_BIT | LINE_BREAKS_BIT | WIDTH_BIT | LATIN_CASE_BIT | VISIBLE_ONLY_BIT;
+
+impl MatchMode {
+    /// Plain `str::contains`, the behaviour of [`crate::verify_text`].
+    pub const EXACT: MatchMode = MatchMode {
+        ignore_whitespace: false,
+        ignore_line_breaks: false,
+        ignore_width: false,
+        ignore_latin_case: false,
+        visible_only: false,
+    };
+
+    /// Encodes the mode as bit flags: bit 0 whitespace, bit 1 line breaks,
+    /// bit 2 width, bit 3 Latin case, bit 4 visible text only.
+    pub fn to_bits(self) -> u8 {
+        let mut bits = 0;
+        for (set, bit) in [
+            (self.ignore_whitespace, WHITESPACE_BIT),
+            (self.ignore_line_breaks, LINE_BREAKS_BIT),
+            (self.ignore_width, WIDTH_BIT),
+            (self.ignore_latin_case, LATIN_CASE_BIT),
+            (self.visible_only, VISIBLE_ONLY_BIT),
+        ] {
+            if set {
+                bits |= bit;
+            }
+        }
+        bits
+    }
+
+    /// Decodes [`MatchMode::to_bits`]. Unknown bits are rejected rather than
+    /// ignored, so a committed mode always has one meaning.
+    pub fn from_bits(bits: u8) -> Result<MatchMode, String> {
+        if bits & !ALL_BITS != 0 {
+            return Err(format!("unknown match mode bits: {:#04x}", bits));
+        }
+        Ok(MatchMode {
+            ignore_whitespace: bits & WHITESPACE_BIT != 0,
+            ignore_line_breaks: bits & LINE_BREAKS_BIT != 0,
+            ignore_width: bits & WIDTH_BIT != 0,
+            ignore_latin_case: bits & LATIN_CASE_BIT != 0,
+            visible_only: bits & VISIBLE_ONLY_BIT != 0,
+        })
+    }
+
+    /// Applies the mode's equivalences to `text`. `visible_only` is applied
+    /// during extraction instead, so it has no effect here.
+    pub fn canonicalize(self, text: &str) -> String {
+        let mut text = if self.ignore_width {
+            normalize_text(text)
+        } else {
+            text.to_string()
+        };
+        if self.ignore_latin_case {
+            let mut folded = String::with_capacity(text.len());
+            for c in text.chars() {
+                if is_latin(c) {
+                    folded.extend(c.to_lowercase());
+                } else {
+                    folded.push(c);
+                }
+            }
+            text = folded;
+        }
+        if self.ignore_line_breaks {
+            text = join_lines(&text);
+        }
+        if self.ignore_whitespace {
+            text.retain(|c| is_line_break(c) || !c.is_whitespace());
+        }
+        text
+    }
+
+    /// Whether `needle` occurs in `haystack` under this mode. A needle that
+    /// is empty once canonicalized, such as whitespace under
+    /// `ignore_whitespace`, matches nothing.
+    pub fn contains(self, haystack: &str, needle: &str) -> bool {
+        let needle = self.canonicalize(needle);
+        !needle.is_empty() && self.canonicalize(haystack).contains(&needle)
+    }
+}
+
+fn is_line_break(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+fn is_latin(c: char) -> bool {
+    matches!(c as u32, 0x41..=0x5A | 0xC0..=0x24F | 0x1E00..=0x1EFF)
+}
+
+fn join_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_line_break(c) {
+            out.push(c);
+            continue;
+        }
+        // Swallow the rest of the break, including blank lines and indentation.
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let trimmed = out.trim_end().len();
+        out.truncate(trimmed);
+        let before = out.chars().next_back();
+        let after = chars.peek().copied();
+        // Scripts written without spaces join the two halves directly.
+        match (before, after) {
+            (Some(b), Some(a)) if !is_unspaced_script(b) && !is_unspaced_script(a) => out.push(' '),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equivalences_are_opt_in_and_round_trip_through_bits() {
+        let page = "Account holder: JOHN\nSMITH\n人民\n币 金额：１２３";
+        assert!(!MatchMode::EXACT.contains(page, "John Smith"));
+
+        let lines = MatchMode {
+            ignore_line_breaks: true,
+            ..MatchMode::default()
+        };
+        assert!(lines.contains(page, "JOHN SMITH人民币"));
+        assert!(!lines.contains(page, "JOHNSMITH"));
+
+        let all = MatchMode::from_bits(0x0F).unwrap();
+        assert_eq!(all.to_bits(), 0x0F);
+        assert!(all.contains(page, "john smith"));
+        assert!(all.contains(page, "人 民 币 金额:123"));
+        assert!(MatchMode::from_bits(0x10).unwrap().visible_only);
+        assert!(MatchMode::from_bits(0x20).is_err());
+    }
+
+    #[test]
+    fn needles_empty_after_canonicalizing_match_nothing() {
+        let all = MatchMode::from_bits(0x0F).unwrap();
+        assert!(!all.contains("any page", " \n\u{3000}"));
+        assert!(!MatchMode::EXACT.contains("any page", ""));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::encoding::decode_text_string;
+use crate::handle_stream_filters;
+use crate::metadata::parse_date;
+use crate::navigation::collect_name_tree;
+use crate::resolve;
+use crate::types::{EmbeddedFile, PdfDocument, PdfObj, PdfStream};
+
+/// Files embedded in the document: those in the `/Names /EmbeddedFiles` tree
+/// first, then those of FileAttachment annotations, page by page. A file
+/// stream reached both ways is listed once.
+pub fn embedded_files(document: &PdfDocument) -> Vec<EmbeddedFile> {
+    let objects = &document.objects;
+    let mut specs: Vec<(&PdfObj, Option<usize>)> = Vec::new();
+    let names = document
+        .catalog()
+        .and_then(|catalog| catalog.get("Names"))
+        .map(|names| resolve(names, objects));
+    if let Some(PdfObj::Dictionary(names)) = names {
+        if let Some(root) = names.get("EmbeddedFiles") {
+            let mut entries = Vec::new();
+            collect_name_tree(root, objects, &mut HashSet::new(), 0, &mut entries);
+            specs.extend(entries.into_iter().map(|(_, spec)| (spec, None)));
+        }
+    }
+    for (index, page) in document.pages.iter().enumerate() {
+        for annotation in &page.annotations {
+            let PdfObj::Dictionary(annotation) = resolve(annotation, objects) else {
+                continue;
+            };
+            let attachment =
+                matches!(annotation.get("Subtype"), Some(PdfObj::Name(s)) if s == "FileAttachment");
+            if let (true, Some(spec)) = (attachment, annotation.get("FS")) {
+                specs.push((spec, Some(index)));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for (spec, page) in specs {
+        let PdfObj::Dictionary(spec) = resolve(spec, objects) else {
+            continue;
+        };
+        let Some((id, stream)) = file_stream(spec, objects) else {
+            continue;
+        };
+        if id.is_some_and(|id| !seen.insert(id)) {
+            continue;
+        }
+        files.push(embedded_file(spec, stream, page, objects));
+    }
+    files
+}
+
+// The embedded file stream of a file specification, `/EF /UF` or `/EF /F`,
+// with its object id when it is indirect.
+fn file_stream<'a>(
+    spec: &'a HashMap<String, PdfObj>,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+) -> Option<(Option<(u32, u16)>, &'a PdfStream)> {
+    let PdfObj::Dictionary(streams) = resolve(spec.get("EF")?, objects) else {
+        return None;
+    };
+    let entry = streams.get("UF").or_else(|| streams.get("F"))?;
+    let id = match entry {
+        PdfObj::Reference(id) => Some(*id),
+        _ => None,
+    };
+    match resolve(entry, objects) {
+        PdfObj::Stream(stream) => Some((id, stream)),
+        _ => None,
+    }
+}
+
+fn embedded_file(
+    spec: &HashMap<String, PdfObj>,
+    stream: &PdfStream,
+    page: Option<usize>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> EmbeddedFile {
+    let text = |dict: &HashMap<String, PdfObj>, key: &str| match dict
+        .get(key)
+        .map(|v| resolve(v, objects))
+    {
+        Some(PdfObj::String(bytes)) => Some(decode_text_string(bytes)),
+        _ => None,
+    };
+    let params = match stream.dict.get("Params").map(|p| resolve(p, objects)) {
+        Some(PdfObj::Dictionary(params)) => params.clone(),
+        _ => HashMap::new(),
+    };
+    let mut decoded = Vec::new();
+    let data = match stream.dict.get("Filter") {
+        Some(filter) => handle_stream_filters(filter, &stream.data, true, &mut decoded)
+            .ok()
+            .and_then(|()| decoded.pop()),
+        None => Some(stream.data.clone()),
+    };
+    EmbeddedFile {
+        name: text(spec, "UF")
+            .or_else(|| text(spec, "F"))
+            .unwrap_or_default(),
+        description: text(spec, "Desc"),
+        mime_type: match stream.dict.get("Subtype") {
+            Some(PdfObj::Name(subtype)) => Some(subtype.clone()),
+            _ => None,
+        },
+        relationship: match spec.get("AFRelationship") {
+            Some(PdfObj::Name(relationship)) => Some(relationship.clone()),
+            _ => None,
+        },
+        size: match params.get("Size").map(|s| resolve(s, objects)) {
+            Some(PdfObj::Number(size)) if *size >= 0.0 => Some(*size as usize),
+            _ => None,
+        },
+        checksum: match params.get("CheckSum").map(|c| resolve(c, objects)) {
+            Some(PdfObj::String(checksum)) => Some(checksum.clone()),
+            _ => None,
+        },
+        creation_date: text(&params, "CreationDate")
+            .as_deref()
+            .and_then(parse_date),
+        mod_date: text(&params, "ModDate").as_deref().and_then(parse_date),
+        page,
+        data,
+    }
+}
@@ -0,0 +1,1228 @@
+use std::collections::HashMap;
+
+use crate::encoding::standard_encoding_glyph;
+use crate::graphics::{multiply, PathSegment};
+
+/// The charset and encoding of an embedded CFF (`FontFile3`) program.
+#[derive(Debug, Default)]
+pub struct CffFont {
+    pub num_glyphs: usize,
+    /// Registry, ordering and supplement of a CID-keyed font.
+    pub ros: Option<(String, String, i32)>,
+    /// Glyph names indexed by glyph ID; empty for CID-keyed fonts.
+    pub glyph_names: Vec<Option<String>>,
+    /// CIDs indexed by glyph ID; empty for name-keyed fonts.
+    pub cids: Vec<u16>,
+    /// Built-in encoding of a name-keyed font, from code to glyph ID.
+    pub encoding: HashMap<u8, u16>,
+}
+
+struct Index<'a> {
+    items: Vec<&'a [u8]>,
+    end: usize,
+}
+
+fn read_index(data: &[u8], pos: usize) -> Option<Index<'_>> {
+    let count = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+    if count == 0 {
+        return Some(Index {
+            items: Vec::new(),
+            end: pos + 2,
+        });
+    }
+    let off_size = *data.get(pos + 2)? as usize;
+    if !(1..=4).contains(&off_size) {
+        return None;
+    }
+    let offsets_start = pos + 3;
+    let read_offset = |i: usize| -> Option<usize> {
+        let bytes = data.get(offsets_start + i * off_size..offsets_start + (i + 1) * off_size)?;
+        Some(bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize))
+    };
+    let data_start = offsets_start + (count + 1) * off_size - 1;
+    let mut items = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = data_start + read_offset(i)?;
+        let end = data_start + read_offset(i + 1)?;
+        items.push(data.get(start..end)?);
+    }
+    Some(Index {
+        items,
+        end: data_start + read_offset(count)?,
+    })
+}
+
+// Decodes a DICT into operator -> operands; two-byte operators are keyed as 1200 + b1.
+fn read_dict(data: &[u8]) -> HashMap<u16, Vec<f64>> {
+    let mut dict = HashMap::new();
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let b0 = data[i];
+        match b0 {
+            0..=21 => {
+                let op = if b0 == 12 {
+                    i += 1;
+                    1200 + *data.get(i).unwrap_or(&0) as u16
+                } else {
+                    b0 as u16
+                };
+                dict.insert(op, std::mem::take(&mut operands));
+                i += 1;
+            }
+            28 => {
+                let v = data
+                    .get(i + 1..i + 3)
+                    .map(|b| i16::from_be_bytes([b[0], b[1]]));
+                operands.push(v.unwrap_or(0) as f64);
+                i += 3;
+            }
+            29 => {
+                let v = data
+                    .get(i + 1..i + 5)
+                    .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+                operands.push(v.unwrap_or(0) as f64);
+                i += 5;
+            }
+            30 => {
+                let (value, next) = read_real(data, i + 1);
+                operands.push(value);
+                i = next;
+            }
+            32..=246 => {
+                operands.push(b0 as f64 - 139.0);
+                i += 1;
+            }
+            247..=250 => {
+                let b1 = *data.get(i + 1).unwrap_or(&0) as f64;
+                operands.push((b0 as f64 - 247.0) * 256.0 + b1 + 108.0);
+                i += 2;
+            }
+            251..=254 => {
+                let b1 = *data.get(i + 1).unwrap_or(&0) as f64;
+                operands.push(-(b0 as f64 - 251.0) * 256.0 - b1 - 108.0);
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    dict
+}
+
+fn read_real(data: &[u8], mut i: usize) -> (f64, usize) {
+    let mut text = String::new();
+    'outer: while let Some(&byte) = data.get(i) {
+        i += 1;
+        for nibble in [byte >> 4, byte & 0x0F] {
+            match nibble {
+                0..=9 => text.push((b'0' + nibble) as char),
+                0xA => text.push('.'),
+                0xB => text.push('E'),
+                0xC => text.push_str("E-"),
+                0xE => text.push('-'),
+                0xF => break 'outer,
+                _ => {}
+            }
+        }
+    }
+    (text.parse().unwrap_or(0.0), i)
+}
+
+/// Parses a bare CFF font program (`Type1C` or `CIDFontType0C`).
+pub fn parse_cff(data: &[u8]) -> Result<CffFont, &'static str> {
+    let header_size = *data.get(2).ok_or("truncated CFF header")? as usize;
+    let names = read_index(data, header_size).ok_or("bad Name INDEX")?;
+    let top_dicts = read_index(data, names.end).ok_or("bad Top DICT INDEX")?;
+    let strings = read_index(data, top_dicts.end).ok_or("bad String INDEX")?;
+    let top = read_dict(top_dicts.items.first().ok_or("empty Top DICT INDEX")?);
+
+    let sid_string = |sid: usize| -> Option<String> {
+        if sid < STANDARD_STRINGS.len() {
+            Some(STANDARD_STRINGS[sid].to_string())
+        } else {
+            strings
+                .items
+                .get(sid - STANDARD_STRINGS.len())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+        }
+    };
+    let offset = |op: u16, default: usize| -> usize {
+        top.get(&op)
+            .and_then(|v| v.first())
+            .map(|&v| v as usize)
+            .unwrap_or(default)
+    };
+
+    let char_strings = read_index(data, offset(17, 0)).ok_or("bad CharStrings INDEX")?;
+    let mut font = CffFont {
+        num_glyphs: char_strings.items.len(),
+        ..CffFont::default()
+    };
+    if let Some(ros) = top.get(&1230).filter(|v| v.len() >= 3) {
+        font.ros = Some((
+            sid_string(ros[0] as usize).unwrap_or_default(),
+            sid_string(ros[1] as usize).unwrap_or_default(),
+            ros[2] as i32,
+        ));
+    }
+
+    let charset = read_charset(data, offset(15, 0), font.num_glyphs);
+    if font.ros.is_some() {
+        font.cids = charset;
+        return Ok(font);
+    }
+    font.glyph_names = charset
+        .iter()
+        .map(|&sid| sid_string(sid as usize))
+        .collect();
+
+    match offset(16, 0) {
+        0 => {
+            for code in 0..=255u8 {
+                if let Some(name) = standard_encoding_glyph(code) {
+                    if let Some(gid) = font
+                        .glyph_names
+                        .iter()
+                        .position(|n| n.as_deref() == Some(name))
+                    {
+                        font.encoding.insert(code, gid as u16);
+                    }
+                }
+            }
+        }
+        1 => {}
+        pos => read_encoding(data, pos, &charset, &mut font.encoding),
+    }
+    Ok(font)
+}
+
+// Returns the SID (or CID) of every glyph, indexed by glyph ID.
+fn read_charset(data: &[u8], pos: usize, num_glyphs: usize) -> Vec<u16> {
+    let mut ids = vec![0u16];
+    match pos {
+        // Predefined ISOAdobe charset: glyph i has SID i.
+        0 => ids.extend(1..num_glyphs.min(229) as u16),
+        1 | 2 => {}
+        _ => {
+            let word = |at: usize| -> Option<u16> {
+                Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+            };
+            let format = data.get(pos).copied().unwrap_or(u8::MAX);
+            let mut at = pos + 1;
+            while ids.len() < num_glyphs {
+                match format {
+                    0 => match word(at) {
+                        Some(id) => {
+                            ids.push(id);
+                            at += 2;
+                        }
+                        None => break,
+                    },
+                    1 | 2 => {
+                        let (Some(first), Some(left)) = (
+                            word(at),
+                            if format == 1 {
+                                data.get(at + 2).map(|&b| b as u16)
+                            } else {
+                                word(at + 2)
+                            },
+                        ) else {
+                            break;
+                        };
+                        at += if format == 1 { 3 } else { 4 };
+                        for id in first..=first.saturating_add(left) {
+                            ids.push(id);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    ids.truncate(num_glyphs);
+    ids
+}
+
+fn read_encoding(data: &[u8], pos: usize, charset: &[u16], encoding: &mut HashMap<u8, u16>) {
+    let Some(&format) = data.get(pos) else {
+        return;
+    };
+    let mut at = pos + 1;
+    let mut gid = 1u16;
+    match format & 0x7F {
+        0 => {
+            let count = *data.get(at).unwrap_or(&0) as usize;
+            for &code in data.get(at + 1..at + 1 + count).unwrap_or(&[]) {
+                encoding.insert(code, gid);
+                gid += 1;
+            }
+            at += 1 + count;
+        }
+        1 => {
+            let ranges = *data.get(at).unwrap_or(&0) as usize;
+            for r in 0..ranges {
+                let (Some(&first), Some(&left)) =
+                    (data.get(at + 1 + r * 2), data.get(at + 2 + r * 2))
+                else {
+                    break;
+                };
+                for code in first..=first.saturating_add(left) {
+                    encoding.insert(code, gid);
+                    gid += 1;
+                }
+            }
+            at += 1 + ranges * 2;
+        }
+        _ => return,
+    }
+    if format & 0x80 != 0 {
+        // Supplements map extra codes to glyphs by SID.
+        let count = *data.get(at).unwrap_or(&0) as usize;
+        for s in 0..count {
+            let entry = at + 1 + s * 3;
+            let (Some(&code), Some(&hi), Some(&lo)) =
+                (data.get(entry), data.get(entry + 1), data.get(entry + 2))
+            else {
+                break;
+            };
+            let sid = u16::from_be_bytes([hi, lo]);
+            if let Some(gid) = charset.iter().position(|&s| s == sid) {
+                encoding.insert(code, gid as u16);
+            }
+        }
+    }
+}
+
+// Subroutine calls nest at most this deep.
+const MAX_SUBR_DEPTH: usize = 10;
+
+// Most operators and operands run for one glyph, across all of its
+// subroutine calls.
+const MAX_CHARSTRING_OPS: usize = 1 << 16;
+
+/// The outline of glyph `gid` from its Type 2 charstring, in ems: charstring
+/// units mapped through the font matrix.
+pub fn glyph_outline(data: &[u8], gid: u16) -> Option<Vec<PathSegment>> {
+    let header_size = *data.get(2)? as usize;
+    let names = IndexView::open(data, header_size)?;
+    let top_dicts = IndexView::open(data, names.end()?)?;
+    let strings = IndexView::open(data, top_dicts.end()?)?;
+    let global_subrs = IndexView::open(data, strings.end()?)?;
+    let top = read_dict(top_dicts.get(0)?);
+    let char_strings = IndexView::open(data, *top.get(&17)?.first()? as usize)?;
+    let char_string = char_strings.get(usize::from(gid))?;
+
+    let mut matrix = font_matrix(&top).unwrap_or([0.001, 0.0, 0.0, 0.001, 0.0, 0.0]);
+    // CID-keyed fonts keep a private dictionary per font dict, chosen by glyph.
+    let private_owner = match (top.get(&1236), top.get(&1237)) {
+        (Some(fd_array), Some(fd_select)) => {
+            let fd_array = IndexView::open(data, *fd_array.first()? as usize)?;
+            let fd = fd_index(data, *fd_select.first()? as usize, gid)?;
+            let font_dict = read_dict(fd_array.get(fd)?);
+            if let Some(fd_matrix) = font_matrix(&font_dict) {
+                matrix = multiply(&fd_matrix, &matrix);
+            }
+            font_dict
+        }
+        _ => top,
+    };
+    let local_subrs = private_owner
+        .get(&18)
+        .filter(|v| v.len() >= 2)
+        .and_then(|private| {
+            let (size, offset) = (private[0] as usize, private[1] as usize);
+            let subrs = read_dict(data.get(offset..offset + size)?)
+                .get(&19)?
+                .first()?
+                .to_owned();
+            IndexView::open(data, offset + subrs as usize)
+        });
+
+    let mut interpreter = CharString {
+        global_subrs,
+        local_subrs,
+        stack: Vec::new(),
+        point: (0.0, 0.0),
+        hints: 0,
+        width_seen: false,
+        open: false,
+        ended: false,
+        ops: MAX_CHARSTRING_OPS,
+        outline: Vec::new(),
+    };
+    if interpreter.run(char_string, 0).is_none() && !interpreter.ended {
+        return None;
+    }
+    interpreter.close();
+    Some(
+        interpreter
+            .outline
+            .iter()
+            .map(|segment| segment.transformed(&matrix))
+            .collect(),
+    )
+}
+
+fn font_matrix(dict: &HashMap<u16, Vec<f64>>) -> Option<[f64; 6]> {
+    let values = dict.get(&1207).filter(|v| v.len() == 6)?;
+    Some([
+        values[0], values[1], values[2], values[3], values[4], values[5],
+    ])
+}
+
+// The FDArray index of glyph `gid` from an FDSelect structure.
+fn fd_index(data: &[u8], pos: usize, gid: u16) -> Option<usize> {
+    let u16_at = |at: usize| Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]));
+    match *data.get(pos)? {
+        0 => data
+            .get(pos + 1 + usize::from(gid))
+            .map(|&fd| usize::from(fd)),
+        3 => {
+            let ranges = usize::from(u16_at(pos + 1)?);
+            (0..ranges).find_map(|r| {
+                let range = pos + 3 + r * 3;
+                let (first, next) = (u16_at(range)?, u16_at(range + 3)?);
+                (first..next)
+                    .contains(&gid)
+                    .then(|| data.get(range + 2).map(|&fd| usize::from(fd)))?
+            })
+        }
+        _ => None,
+    }
+}
+
+// The bias added to a subroutine number, from the size of its INDEX.
+fn subr_bias(count: usize) -> f64 {
+    match count {
+        0..=1239 => 107.0,
+        1240..=33899 => 1131.0,
+        _ => 32768.0,
+    }
+}
+
+// An INDEX read one item at a time, for the large CharStrings and Subrs
+// INDEXes of which a glyph needs only a few items.
+#[derive(Clone, Copy)]
+struct IndexView<'a> {
+    data: &'a [u8],
+    count: usize,
+    off_size: usize,
+    offsets_start: usize,
+}
+
+impl<'a> IndexView<'a> {
+    fn open(data: &'a [u8], pos: usize) -> Option<Self> {
+        let count = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        let off_size = if count == 0 {
+            1
+        } else {
+            *data.get(pos + 2)? as usize
+        };
+        if !(1..=4).contains(&off_size) {
+            return None;
+        }
+        Some(IndexView {
+            data,
+            count,
+            off_size,
+            offsets_start: pos + 3,
+        })
+    }
+
+    fn offset(&self, i: usize) -> Option<usize> {
+        let start = self.offsets_start + i * self.off_size;
+        let bytes = self.data.get(start..start + self.off_size)?;
+        let offset = bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        Some(self.offsets_start + (self.count + 1) * self.off_size - 1 + offset)
+    }
+
+    fn get(&self, i: usize) -> Option<&'a [u8]> {
+        if i >= self.count {
+            return None;
+        }
+        self.data.get(self.offset(i)?..self.offset(i + 1)?)
+    }
+
+    // Where the data following the INDEX starts.
+    fn end(&self) -> Option<usize> {
+        if self.count == 0 {
+            return Some(self.offsets_start - 1);
+        }
+        self.offset(self.count)
+    }
+}
+
+struct CharString<'a> {
+    global_subrs: IndexView<'a>,
+    local_subrs: Option<IndexView<'a>>,
+    stack: Vec<f64>,
+    point: (f64, f64),
+    hints: usize,
+    width_seen: bool,
+    open: bool,
+    ended: bool,
+    // Operators and operands left to run.
+    ops: usize,
+    outline: Vec<PathSegment>,
+}
+
+impl CharString<'_> {
+    // Runs a charstring or subroutine; `None` stops the glyph at `endchar`
+    // or on malformed data, `Some` returns to the caller.
+    fn run(&mut self, code: &[u8], depth: usize) -> Option<()> {
+        let mut i = 0;
+        while i < code.len() {
+            self.ops = self.ops.checked_sub(1)?;
+            let b0 = code[i];
+            i += 1;
+            match b0 {
+                28 => {
+                    let bytes = code.get(i..i + 2)?;
+                    self.stack
+                        .push(f64::from(i16::from_be_bytes([bytes[0], bytes[1]])));
+                    i += 2;
+                }
+                32..=246 => self.stack.push(f64::from(b0) - 139.0),
+                247..=250 => {
+                    let b1 = f64::from(*code.get(i)?);
+                    self.stack
+                        .push((f64::from(b0) - 247.0) * 256.0 + b1 + 108.0);
+                    i += 1;
+                }
+                251..=254 => {
+                    let b1 = f64::from(*code.get(i)?);
+                    self.stack
+                        .push(-(f64::from(b0) - 251.0) * 256.0 - b1 - 108.0);
+                    i += 1;
+                }
+                255 => {
+                    let bytes = code.get(i..i + 4)?;
+                    let fixed = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    self.stack.push(f64::from(fixed) / 65536.0);
+                    i += 4;
+                }
+                // hstem, vstem, hstemhm, vstemhm
+                1 | 3 | 18 | 23 => {
+                    self.take_width(self.stack.len() % 2 == 1);
+                    self.hints += self.stack.len() / 2;
+                    self.stack.clear();
+                }
+                // hintmask, cntrmask: operands left on the stack are vstems.
+                19 | 20 => {
+                    self.take_width(self.stack.len() % 2 == 1);
+                    self.hints += self.stack.len() / 2;
+                    self.stack.clear();
+                    i += self.hints.div_ceil(8);
+                }
+                10 | 29 => {
+                    let subrs = if b0 == 10 {
+                        self.local_subrs?
+                    } else {
+                        self.global_subrs
+                    };
+                    let index = self.stack.pop()? + subr_bias(subrs.count);
+                    let subr = subrs.get(usize::try_from(index as i64).ok()?)?;
+                    if depth >= MAX_SUBR_DEPTH {
+                        return None;
+                    }
+                    self.run(subr, depth + 1)?;
+                }
+                11 => return Some(()),
+                14 => {
+                    self.take_width(self.stack.len() == 1 || self.stack.len() == 5);
+                    self.ended = true;
+                    return None;
+                }
+                12 => {
+                    let b1 = *code.get(i)?;
+                    i += 1;
+                    self.flex(b1);
+                    self.stack.clear();
+                }
+                _ => {
+                    self.path_operator(b0);
+                    self.stack.clear();
+                }
+            }
+        }
+        Some(())
+    }
+
+    // The first stack-clearing operator may carry the advance width first.
+    fn take_width(&mut self, present: bool) {
+        if !self.width_seen && present && !self.stack.is_empty() {
+            self.stack.remove(0);
+        }
+        self.width_seen = true;
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) {
+        self.close();
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.outline.push(PathSegment::MoveTo(self.point));
+        self.open = true;
+    }
+
+    fn line_by(&mut self, dx: f64, dy: f64) {
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.outline.push(PathSegment::LineTo(self.point));
+    }
+
+    fn curve_by(&mut self, d1: (f64, f64), d2: (f64, f64), d3: (f64, f64)) {
+        let c1 = (self.point.0 + d1.0, self.point.1 + d1.1);
+        let c2 = (c1.0 + d2.0, c1.1 + d2.1);
+        self.point = (c2.0 + d3.0, c2.1 + d3.1);
+        self.outline.push(PathSegment::CubicTo(c1, c2, self.point));
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            self.outline.push(PathSegment::Close);
+            self.open = false;
+        }
+    }
+
+    fn path_operator(&mut self, op: u8) {
+        let args = std::mem::take(&mut self.stack);
+        let n = args.len();
+        match op {
+            // rmoveto, hmoveto, vmoveto
+            21 | 22 | 4 => {
+                let expected = if op == 21 { 2 } else { 1 };
+                self.stack = args;
+                self.take_width(n > expected);
+                let args = std::mem::take(&mut self.stack);
+                match (op, args.as_slice()) {
+                    (21, [dx, dy, ..]) => self.move_by(*dx, *dy),
+                    (22, [dx, ..]) => self.move_by(*dx, 0.0),
+                    (4, [dy, ..]) => self.move_by(0.0, *dy),
+                    _ => {}
+                }
+            }
+            // rlineto
+            5 => args.chunks_exact(2).for_each(|d| self.line_by(d[0], d[1])),
+            // hlineto, vlineto
+            6 | 7 => {
+                for (k, &d) in args.iter().enumerate() {
+                    if (k % 2 == 0) == (op == 6) {
+                        self.line_by(d, 0.0);
+                    } else {
+                        self.line_by(0.0, d);
+                    }
+                }
+            }
+            // rrcurveto
+            8 => args.chunks_exact(6).for_each(|d| {
+                self.curve_by((d[0], d[1]), (d[2], d[3]), (d[4], d[5]));
+            }),
+            // rcurveline
+            24 if n >= 8 => {
+                let curves = (n - 2) / 6;
+                args.chunks_exact(6).take(curves).for_each(|d| {
+                    self.curve_by((d[0], d[1]), (d[2], d[3]), (d[4], d[5]));
+                });
+                self.line_by(args[n - 2], args[n - 1]);
+            }
+            // rlinecurve
+            25 if n >= 8 => {
+                let lines = (n - 6) / 2;
+                args.chunks_exact(2)
+                    .take(lines)
+                    .for_each(|d| self.line_by(d[0], d[1]));
+                let d = &args[lines * 2..];
+                self.curve_by((d[0], d[1]), (d[2], d[3]), (d[4], d[5]));
+            }
+            // vvcurveto, hhcurveto
+            26 | 27 => {
+                let (mut first, rest) = if n % 2 == 1 {
+                    (args[0], &args[1..])
+                } else {
+                    (0.0, &args[..])
+                };
+                for d in rest.chunks_exact(4) {
+                    if op == 26 {
+                        self.curve_by((first, d[0]), (d[1], d[2]), (0.0, d[3]));
+                    } else {
+                        self.curve_by((d[0], first), (d[1], d[2]), (d[3], 0.0));
+                    }
+                    first = 0.0;
+                }
+            }
+            // vhcurveto, hvcurveto
+            30 | 31 => {
+                let mut vertical = op == 30;
+                let mut k = 0;
+                while n - k >= 4 {
+                    let d = &args[k..k + 4];
+                    let last = if n - k == 5 { args[k + 4] } else { 0.0 };
+                    if vertical {
+                        self.curve_by((0.0, d[0]), (d[1], d[2]), (d[3], last));
+                    } else {
+                        self.curve_by((d[0], 0.0), (d[1], d[2]), (last, d[3]));
+                    }
+                    k += 4;
+                    vertical = !vertical;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The two-byte flex operators, each drawing two curves.
+    fn flex(&mut self, op: u8) {
+        let a = &self.stack;
+        let curves = match (op, a.len()) {
+            // flex
+            (35, 13) => [
+                [(a[0], a[1]), (a[2], a[3]), (a[4], a[5])],
+                [(a[6], a[7]), (a[8], a[9]), (a[10], a[11])],
+            ],
+            // hflex
+            (34, 7) => [
+                [(a[0], 0.0), (a[1], a[2]), (a[3], 0.0)],
+                [(a[4], 0.0), (a[5], -a[2]), (a[6], 0.0)],
+            ],
+            // hflex1
+            (36, 9) => [
+                [(a[0], a[1]), (a[2], a[3]), (a[4], 0.0)],
+                [(a[5], 0.0), (a[6], a[7]), (a[8], -(a[1] + a[3] + a[7]))],
+            ],
+            // flex1
+            (37, 11) => {
+                let dx: f64 = a[..10].iter().step_by(2).sum();
+                let dy: f64 = a[1..10].iter().step_by(2).sum();
+                let last = if dx.abs() > dy.abs() {
+                    (a[10], -dy)
+                } else {
+                    (-dx, a[10])
+                };
+                [
+                    [(a[0], a[1]), (a[2], a[3]), (a[4], a[5])],
+                    [(a[6], a[7]), (a[8], a[9]), last],
+                ]
+            }
+            _ => return,
+        };
+        for [d1, d2, d3] in curves {
+            self.curve_by(d1, d2, d3);
+        }
+    }
+}
+
+// The 391 predefined CFF strings; SIDs below 391 index into this table.
+const STANDARD_STRINGS: [&str; 391] = [
+    ".notdef",
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quoteright",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "quoteleft",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "fraction",
+    "yen",
+    "florin",
+    "section",
+    "currency",
+    "quotesingle",
+    "quotedblleft",
+    "guillemotleft",
+    "guilsinglleft",
+    "guilsinglright",
+    "fi",
+    "fl",
+    "endash",
+    "dagger",
+    "daggerdbl",
+    "periodcentered",
+    "paragraph",
+    "bullet",
+    "quotesinglbase",
+    "quotedblbase",
+    "quotedblright",
+    "guillemotright",
+    "ellipsis",
+    "perthousand",
+    "questiondown",
+    "grave",
+    "acute",
+    "circumflex",
+    "tilde",
+    "macron",
+    "breve",
+    "dotaccent",
+    "dieresis",
+    "ring",
+    "cedilla",
+    "hungarumlaut",
+    "ogonek",
+    "caron",
+    "emdash",
+    "AE",
+    "ordfeminine",
+    "Lslash",
+    "Oslash",
+    "OE",
+    "ordmasculine",
+    "ae",
+    "dotlessi",
+    "lslash",
+    "oslash",
+    "oe",
+    "germandbls",
+    "onesuperior",
+    "logicalnot",
+    "mu",
+    "trademark",
+    "Eth",
+    "onehalf",
+    "plusminus",
+    "Thorn",
+    "onequarter",
+    "divide",
+    "brokenbar",
+    "degree",
+    "thorn",
+    "threequarters",
+    "twosuperior",
+    "registered",
+    "minus",
+    "eth",
+    "multiply",
+    "threesuperior",
+    "copyright",
+    "Aacute",
+    "Acircumflex",
+    "Adieresis",
+    "Agrave",
+    "Aring",
+    "Atilde",
+    "Ccedilla",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Egrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Igrave",
+    "Ntilde",
+    "Oacute",
+    "Ocircumflex",
+    "Odieresis",
+    "Ograve",
+    "Otilde",
+    "Scaron",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Ugrave",
+    "Yacute",
+    "Ydieresis",
+    "Zcaron",
+    "aacute",
+    "acircumflex",
+    "adieresis",
+    "agrave",
+    "aring",
+    "atilde",
+    "ccedilla",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "egrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "igrave",
+    "ntilde",
+    "oacute",
+    "ocircumflex",
+    "odieresis",
+    "ograve",
+    "otilde",
+    "scaron",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "ugrave",
+    "yacute",
+    "ydieresis",
+    "zcaron",
+    "exclamsmall",
+    "Hungarumlautsmall",
+    "dollaroldstyle",
+    "dollarsuperior",
+    "ampersandsmall",
+    "Acutesmall",
+    "parenleftsuperior",
+    "parenrightsuperior",
+    "twodotenleader",
+    "onedotenleader",
+    "zerooldstyle",
+    "oneoldstyle",
+    "twooldstyle",
+    "threeoldstyle",
+    "fouroldstyle",
+    "fiveoldstyle",
+    "sixoldstyle",
+    "sevenoldstyle",
+    "eightoldstyle",
+    "nineoldstyle",
+    "commasuperior",
+    "threequartersemdash",
+    "periodsuperior",
+    "questionsmall",
+    "asuperior",
+    "bsuperior",
+    "centsuperior",
+    "dsuperior",
+    "esuperior",
+    "isuperior",
+    "lsuperior",
+    "msuperior",
+    "nsuperior",
+    "osuperior",
+    "rsuperior",
+    "ssuperior",
+    "tsuperior",
+    "ff",
+    "ffi",
+    "ffl",
+    "parenleftinferior",
+    "parenrightinferior",
+    "Circumflexsmall",
+    "hyphensuperior",
+    "Gravesmall",
+    "Asmall",
+    "Bsmall",
+    "Csmall",
+    "Dsmall",
+    "Esmall",
+    "Fsmall",
+    "Gsmall",
+    "Hsmall",
+    "Ismall",
+    "Jsmall",
+    "Ksmall",
+    "Lsmall",
+    "Msmall",
+    "Nsmall",
+    "Osmall",
+    "Psmall",
+    "Qsmall",
+    "Rsmall",
+    "Ssmall",
+    "Tsmall",
+    "Usmall",
+    "Vsmall",
+    "Wsmall",
+    "Xsmall",
+    "Ysmall",
+    "Zsmall",
+    "colonmonetary",
+    "onefitted",
+    "rupiah",
+    "Tildesmall",
+    "exclamdownsmall",
+    "centoldstyle",
+    "Lslashsmall",
+    "Scaronsmall",
+    "Zcaronsmall",
+    "Dieresissmall",
+    "Brevesmall",
+    "Caronsmall",
+    "Dotaccentsmall",
+    "Macronsmall",
+    "figuredash",
+    "hypheninferior",
+    "Ogoneksmall",
+    "Ringsmall",
+    "Cedillasmall",
+    "questiondownsmall",
+    "oneeighth",
+    "threeeighths",
+    "fiveeighths",
+    "seveneighths",
+    "onethird",
+    "twothirds",
+    "zerosuperior",
+    "foursuperior",
+    "fivesuperior",
+    "sixsuperior",
+    "sevensuperior",
+    "eightsuperior",
+    "ninesuperior",
+    "zeroinferior",
+    "oneinferior",
+    "twoinferior",
+    "threeinferior",
+    "fourinferior",
+    "fiveinferior",
+    "sixinferior",
+    "seveninferior",
+    "eightinferior",
+    "nineinferior",
+    "centinferior",
+    "dollarinferior",
+    "periodinferior",
+    "commainferior",
+    "Agravesmall",
+    "Aacutesmall",
+    "Acircumflexsmall",
+    "Atildesmall",
+    "Adieresissmall",
+    "Aringsmall",
+    "AEsmall",
+    "Ccedillasmall",
+    "Egravesmall",
+    "Eacutesmall",
+    "Ecircumflexsmall",
+    "Edieresissmall",
+    "Igravesmall",
+    "Iacutesmall",
+    "Icircumflexsmall",
+    "Idieresissmall",
+    "Ethsmall",
+    "Ntildesmall",
+    "Ogravesmall",
+    "Oacutesmall",
+    "Ocircumflexsmall",
+    "Otildesmall",
+    "Odieresissmall",
+    "OEsmall",
+    "Oslashsmall",
+    "Ugravesmall",
+    "Uacutesmall",
+    "Ucircumflexsmall",
+    "Udieresissmall",
+    "Yacutesmall",
+    "Thornsmall",
+    "Ydieresissmall",
+    "001.000",
+    "001.001",
+    "001.002",
+    "001.003",
+    "Black",
+    "Bold",
+    "Book",
+    "Light",
+    "Medium",
+    "Regular",
+    "Roman",
+    "Semibold",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(items: &[&[u8]]) -> Vec<u8> {
+        let mut out = (items.len() as u16).to_be_bytes().to_vec();
+        if items.is_empty() {
+            return out;
+        }
+        let total: usize = items.iter().map(|item| item.len()).sum();
+        let off_size = if total < 255 { 1 } else { 2 };
+        out.push(off_size as u8);
+        let mut offset = 1u16;
+        out.extend_from_slice(&offset.to_be_bytes()[2 - off_size..]);
+        for item in items {
+            offset += item.len() as u16;
+            out.extend_from_slice(&offset.to_be_bytes()[2 - off_size..]);
+        }
+        items.iter().for_each(|item| out.extend_from_slice(item));
+        out
+    }
+
+    fn int_operand(value: usize) -> Vec<u8> {
+        let mut out = vec![29];
+        out.extend_from_slice(&(value as i32).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn reads_charset_names_and_custom_encoding() {
+        let top_dict_len = 3 * 6;
+        let names = index(&[b"F"]);
+        let strings = index(&[b"uni03B1"]);
+        let char_strings = index(&[&[14], &[14], &[14]]);
+        let header_and_indexes =
+            4 + names.len() + index(&[&vec![0; top_dict_len]]).len() + strings.len() + 2;
+        let char_strings_at = header_and_indexes;
+        let charset_at = char_strings_at + char_strings.len();
+        let encoding_at = charset_at + 5;
+
+        let mut top_dict = Vec::new();
+        for (value, op) in [(charset_at, 15), (char_strings_at, 17), (encoding_at, 16)] {
+            top_dict.extend(int_operand(value));
+            top_dict.push(op);
+        }
+        let mut data = vec![1, 0, 4, 1];
+        data.extend(names);
+        data.extend(index(&[&top_dict]));
+        data.extend(strings);
+        data.extend([0, 0]);
+        data.extend(char_strings);
+        // Format 0 charset: glyph 1 is "A" (SID 34), glyph 2 is the custom string.
+        data.extend([0, 0, 34, 0x01, 0x87]);
+        // Format 0 encoding: code 0x41 -> glyph 1, code 0x61 -> glyph 2.
+        data.extend([0, 2, 0x41, 0x61]);
+
+        let font = parse_cff(&data).unwrap();
+        assert_eq!(font.num_glyphs, 3);
+        assert!(font.ros.is_none());
+        assert_eq!(font.glyph_names[1].as_deref(), Some("A"));
+        assert_eq!(font.glyph_names[2].as_deref(), Some("uni03B1"));
+        assert_eq!(font.encoding.get(&0x41), Some(&1));
+        assert_eq!(font.encoding.get(&0x61), Some(&2));
+    }
+
+    #[test]
+    fn charstrings_draw_through_subroutines() {
+        // Width 50, 10 20 rmoveto, 100 hlineto, a global subroutine drawing
+        // 0 100 rlineto, then 0 0 -100 0 0 -100 rrcurveto and endchar.
+        let glyph: &[u8] = &[
+            189, 149, 159, 21, 239, 6, 32, 29, 139, 139, 39, 139, 139, 39, 8, 14,
+        ];
+        let subr: &[u8] = &[139, 239, 5, 11];
+        let data = subr_font(&[subr], &[&[14], glyph]);
+
+        assert_eq!(glyph_outline(&data, 0), Some(Vec::new()));
+        assert_eq!(
+            glyph_outline(&data, 1).unwrap(),
+            [
+                PathSegment::MoveTo((10.0, 20.0)),
+                PathSegment::LineTo((110.0, 20.0)),
+                PathSegment::LineTo((110.0, 120.0)),
+                PathSegment::CubicTo((110.0, 120.0), (10.0, 120.0), (10.0, 20.0)),
+                PathSegment::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn subroutines_fanning_out_stop_at_the_op_budget() {
+        // Global subroutines 0 to 8 each call the next one 100 times.
+        let mut subrs: Vec<Vec<u8>> = (0..9)
+            .map(|k| {
+                let mut subr = [33 + k, 29].repeat(100);
+                subr.push(11);
+                subr
+            })
+            .collect();
+        subrs.push(vec![11]);
+        let subrs: Vec<&[u8]> = subrs.iter().map(Vec::as_slice).collect();
+        let data = subr_font(&subrs, &[&[32, 29, 14]]);
+        assert_eq!(glyph_outline(&data, 0), None);
+    }
+
+    // A font with global subroutines and an identity FontMatrix, which keeps
+    // outlines in charstring units.
+    fn subr_font(subrs: &[&[u8]], char_strings: &[&[u8]]) -> Vec<u8> {
+        let names = index(&[b"F"]);
+        let global_subrs = index(subrs);
+        let char_strings_at = 4 + names.len() + index(&[&[0; 14]]).len() + 2 + global_subrs.len();
+        let mut top_dict = int_operand(char_strings_at);
+        top_dict.push(17);
+        top_dict.extend([140, 139, 139, 140, 139, 139, 12, 7]);
+        let mut data = vec![1, 0, 4, 1];
+        data.extend(names);
+        data.extend(index(&[&top_dict]));
+        data.extend([0, 0]);
+        data.extend(global_subrs);
+        data.extend(index(char_strings));
+        data
+    }
+}
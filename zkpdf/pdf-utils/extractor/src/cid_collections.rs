@@ -0,0 +1,19 @@
+// Unicode values for the parts of the Adobe character collections that can be
+// derived without the published mapping tables: the proportional Latin range
+// at the start of every collection, and half-width katakana in Japan1.
+// Ideographs, Hangul syllables and full-width kana are not covered, since
+// they need the published tables; CID-keyed CFF fonts whose text is mostly
+// Chinese, Japanese or Korean therefore still need a ToUnicode CMap.
+
+/// Maps a CID in the given Adobe ordering (`GB1`, `CNS1`, `Japan1`, `Korea1`)
+/// to Unicode, when the CID falls in a range covered here. Returns `None` for
+/// the ideograph and Hangul parts of each collection.
+pub fn cid_to_unicode(ordering: &str, cid: u32) -> Option<char> {
+    match (ordering, cid) {
+        ("Japan1", 61) => Some('\u{A5}'),
+        ("Japan1", 95) => Some('\u{203E}'),
+        ("Japan1", 327..=389) => char::from_u32(0xFF61 + cid - 327),
+        ("GB1" | "CNS1" | "Japan1" | "Korea1", 1..=95) => char::from_u32(0x20 + cid - 1),
+        _ => None,
+    }
+}
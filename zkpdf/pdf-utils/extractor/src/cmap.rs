@@ -1,316 +1,806 @@
-use std::collections::{BTreeMap, HashMap};
-use crate::types::PdfFont;
-
-// Enhanced CMap parsing with better error handling
-pub fn parse_cmap(cmap_data: &[u8]) -> HashMap<u32, String> {
-    let mut map = BTreeMap::new();
-    let text = String::from_utf8_lossy(cmap_data).into_owned();
-
-    let lines: Vec<&str> = text.lines().collect();
-    let mut i = 0;
-    while i < lines.len() {
-        let line = lines[i].trim();
-
-        if line.contains("beginbfchar") {
-            i += 1;
-            while i < lines.len() && !lines[i].trim_end().ends_with("endbfchar") {
-                let l = lines[i].trim();
-                if l.starts_with('<') {
-                    let parts: Vec<&str> = l.split_ascii_whitespace().collect();
-                    if parts.len() >= 2 {
-                        let src = parts[0].trim_matches(|c| c == '<' || c == '>');
-                        let dst = parts[1].trim_matches(|c| c == '<' || c == '>');
-                        if let Ok(src_code) = u32::from_str_radix(src, 16) {
-                            if let Some(dst_str) = parse_cmap_hex_to_string(dst) {
-                                map.insert(src_code, dst_str);
-                            }
-                        }
-                    }
-                }
-                i += 1;
+use std::collections::HashMap;
+
+use crate::cid_collections::cid_to_unicode;
+
+use crate::encoding::{
+    glyph_to_unicode, mac_expert_to_unicode, mac_roman_to_unicode, standard_to_unicode,
+    winansi_to_unicode,
+};
+use crate::postscript::{PsToken, PsTokenizer};
+use crate::types::{CMap, CharSource, CidRange, CidSystemInfo, CodespaceRange, PdfFont};
+
+// Largest range expanded from a single `bfrange` entry.
+const MAX_RANGE_LEN: u32 = 0x10000;
+
+// Operand values kept on the stack while interpreting a CMap program.
+#[derive(Debug, Clone)]
+enum CMapValue {
+    Integer(i64),
+    Real(f64),
+    String(Vec<u8>),
+    Name(String),
+    Array(Vec<CMapValue>),
+    Dict(HashMap<String, CMapValue>),
+    Mark,
+    DictMark,
+    Proc,
+}
+
+/// Parses a CMap program (ToUnicode or CID encoding) with a PostScript tokenizer.
+///
+/// Entries may be spread over lines arbitrarily. Entries that cannot be
+/// interpreted are skipped and recorded in `CMap::warnings`.
+pub fn parse_cmap(cmap_data: &[u8]) -> CMap {
+    let mut cmap = CMap::default();
+    let mut tokens = PsTokenizer::new(cmap_data);
+    let mut stack: Vec<CMapValue> = Vec::new();
+    let mut dict_stack: Vec<HashMap<String, CMapValue>> = Vec::new();
+    let mut pending: Option<PsToken> = None;
+
+    while let Some(token) = pending.take().or_else(|| tokens.next()) {
+        match token {
+            PsToken::Integer(n) => stack.push(CMapValue::Integer(n)),
+            PsToken::Real(n) => stack.push(CMapValue::Real(n)),
+            PsToken::String(s) => stack.push(CMapValue::String(s)),
+            PsToken::Name(n) => stack.push(CMapValue::Name(n)),
+            PsToken::ArrayStart => stack.push(CMapValue::Mark),
+            PsToken::DictStart => stack.push(CMapValue::DictMark),
+            PsToken::ArrayEnd => {
+                let items = pop_to_mark(&mut stack, |v| matches!(v, CMapValue::Mark));
+                stack.push(CMapValue::Array(items));
             }
-        } else if line.contains("beginbfrange") {
-            i += 1;
-            while i < lines.len() && !lines[i].trim_end().ends_with("endbfrange") {
-                let l = lines[i].trim();
-                if l.starts_with('<') {
-                    // Enhanced splitting that handles bfrange format properly
-                    let mut parts = Vec::new();
-                    let mut current_part = String::new();
-                    let mut in_brackets = false;
-
-                    for ch in l.chars() {
-                        if ch == '<' {
-                            if !current_part.is_empty() {
-                                parts.push(current_part.clone());
-                                current_part.clear();
-                            }
-                            in_brackets = true;
-                            current_part.push(ch);
-                        } else if ch == '>' {
-                            current_part.push(ch);
-                            in_brackets = false;
-                            parts.push(current_part.clone());
-                            current_part.clear();
-                        } else if in_brackets {
-                            current_part.push(ch);
-                        } else if !ch.is_whitespace() {
-                            current_part.push(ch);
-                        } else if !current_part.is_empty() {
-                            parts.push(current_part.clone());
-                            current_part.clear();
+            PsToken::DictEnd => {
+                let items = pop_to_mark(&mut stack, |v| matches!(v, CMapValue::DictMark));
+                stack.push(CMapValue::Dict(pairs_to_dict(items)));
+            }
+            PsToken::ProcStart => {
+                skip_procedure(&mut tokens);
+                stack.push(CMapValue::Proc);
+            }
+            PsToken::ProcEnd => {}
+            PsToken::Operator(op) => match op.as_str() {
+                "def" => {
+                    let value = stack.pop();
+                    let key = stack.pop();
+                    if let (Some(CMapValue::Name(key)), Some(value)) = (key, value) {
+                        apply_definition(&mut cmap, &key, &value);
+                        if let Some(dict) = dict_stack.last_mut() {
+                            dict.insert(key, value);
                         }
                     }
-
-                    if !current_part.is_empty() {
-                        parts.push(current_part);
+                }
+                "dict" => {
+                    stack.pop();
+                    stack.push(CMapValue::Dict(HashMap::new()));
+                }
+                "dup" => {
+                    if let Some(top) = stack.last().cloned() {
+                        stack.push(top);
                     }
-
-                    if parts.len() >= 3 {
-                        let start_hex = parts[0].trim_matches(|c| c == '<' || c == '>');
-                        let end_hex = parts[1].trim_matches(|c| c == '<' || c == '>');
-                        if let (Ok(start_code), Ok(end_code)) = (
-                            u32::from_str_radix(start_hex, 16),
-                            u32::from_str_radix(end_hex, 16),
-                        ) {
-                            if parts[2].starts_with('[') {
-                                // Array-based range mapping
-                                let mut j = 2;
-                                let mut cur_code = start_code;
-                                let mut array_str = String::new();
-                                while j < parts.len() {
-                                    array_str.push_str(&parts[j]);
-                                    array_str.push(' ');
-                                    if parts[j].ends_with(']') {
-                                        break;
-                                    }
-                                    j += 1;
-                                }
-                                array_str = array_str
-                                    .trim()
-                                    .trim_start_matches('[')
-                                    .trim_end_matches(']')
-                                    .trim()
-                                    .to_string();
-                                let dests: Vec<&str> = array_str.split_ascii_whitespace().collect();
-                                for dest_hex in dests {
-                                    let dst = dest_hex.trim_matches(|c| c == '<' || c == '>');
-                                    if let Some(dst_str) = parse_cmap_hex_to_string(dst) {
-                                        map.insert(cur_code, dst_str);
-                                    }
-                                    cur_code += 1;
-                                }
-                            } else {
-                                // Range mapping with start offset
-                                let dest_start_hex = parts[2].trim_matches(|c| c == '<' || c == '>');
-                                if let Some(dest_start_str) = parse_cmap_hex_to_string(dest_start_hex) {
-                                    let mut dest_start_codes: Vec<u32> =
-                                        dest_start_str.chars().map(|ch| ch as u32).collect();
-                                    for code in start_code..=end_code {
-                                        let dest_string: String = dest_start_codes
-                                            .iter()
-                                            .map(|&u| char::from_u32(u).unwrap_or('�'))
-                                            .collect();
-                                        map.insert(code, dest_string);
-                                        if let Some(last) = dest_start_codes.last_mut() {
-                                            *last += 1;
-                                        }
-                                    }
-                                }
+                }
+                "pop" => {
+                    stack.pop();
+                }
+                "begin" => {
+                    let dict = match stack.pop() {
+                        Some(CMapValue::Dict(d)) => d,
+                        _ => HashMap::new(),
+                    };
+                    dict_stack.push(dict);
+                }
+                "end" => {
+                    // `n dict dup begin ... end def` leaves a copy of the dictionary on the
+                    // operand stack; give it the entries defined while it was current.
+                    if let Some(finished) = dict_stack.pop() {
+                        if let Some(CMapValue::Dict(copy)) = stack.last_mut() {
+                            if copy.is_empty() {
+                                *copy = finished;
                             }
                         }
                     }
                 }
-                i += 1;
-            }
-        } else {
-            i += 1;
+                "findresource" => {
+                    stack.pop();
+                    stack.pop();
+                    stack.push(CMapValue::Dict(HashMap::new()));
+                }
+                "defineresource" => {
+                    stack.pop();
+                    stack.pop();
+                    stack.pop();
+                    stack.push(CMapValue::Dict(HashMap::new()));
+                }
+                "usecmap" => match stack.pop() {
+                    Some(CMapValue::Name(parent)) => cmap.use_cmap = Some(parent),
+                    _ => cmap
+                        .warnings
+                        .push("usecmap without a CMap name".to_string()),
+                },
+                "begincodespacerange"
+                | "beginbfchar"
+                | "beginbfrange"
+                | "begincidchar"
+                | "begincidrange"
+                | "beginnotdefchar"
+                | "beginnotdefrange" => {
+                    let declared = match stack.last() {
+                        Some(CMapValue::Integer(n)) => {
+                            let n = *n;
+                            stack.pop();
+                            Some(n)
+                        }
+                        _ => None,
+                    };
+                    let section = &op["begin".len()..];
+                    pending = parse_section(section, declared, &mut tokens, &mut cmap);
+                }
+                "endcodespacerange" | "endbfchar" | "endbfrange" | "endcidchar" | "endcidrange"
+                | "endnotdefchar" | "endnotdefrange" => {
+                    cmap.warnings
+                        .push(format!("'{}' without a matching begin", op));
+                }
+                _ => {}
+            },
         }
     }
 
-    map.into_iter().collect()
+    cmap
 }
 
-// Enhanced hex to string conversion with better error handling
-fn parse_cmap_hex_to_string(hex: &str) -> Option<String> {
-    if hex.is_empty() {
-        return Some(String::new());
+fn pop_to_mark(stack: &mut Vec<CMapValue>, is_mark: fn(&CMapValue) -> bool) -> Vec<CMapValue> {
+    let mut items = Vec::new();
+    while let Some(value) = stack.pop() {
+        if is_mark(&value) {
+            break;
+        }
+        items.push(value);
     }
+    items.reverse();
+    items
+}
 
-    // Handle different hex formats
-    let clean_hex = hex.trim_matches(|c| c == '<' || c == '>');
-
-    if clean_hex.len() % 4 != 0 {
-        return None;
+fn pairs_to_dict(items: Vec<CMapValue>) -> HashMap<String, CMapValue> {
+    let mut dict = HashMap::new();
+    let mut iter = items.into_iter();
+    while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+        if let CMapValue::Name(key) = key {
+            dict.insert(key, value);
+        }
     }
+    dict
+}
 
-    let chunks: Vec<&[u8]> = clean_hex.as_bytes().chunks(4).collect();
-    let mut out = String::new();
-    let mut i = 0;
+fn skip_procedure(tokens: &mut PsTokenizer) {
+    let mut depth = 1;
+    for token in tokens.by_ref() {
+        match token {
+            PsToken::ProcStart => depth += 1,
+            PsToken::ProcEnd => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+}
 
-    while i < chunks.len() {
-        let chunk = chunks[i];
-        if chunk.len() < 4 {
-            break;
+fn apply_definition(cmap: &mut CMap, key: &str, value: &CMapValue) {
+    match (key, value) {
+        ("CMapName", CMapValue::Name(name)) => cmap.name = Some(name.clone()),
+        ("WMode", CMapValue::Integer(mode)) => cmap.wmode = u8::from(*mode == 1),
+        ("CIDSystemInfo", CMapValue::Dict(info)) => {
+            cmap.cid_system_info = cid_system_info(info);
         }
-        let part = match core::str::from_utf8(chunk) {
-            Ok(s) => s,
-            Err(_) => return None,
-        };
+        ("CIDSystemInfo", CMapValue::Array(infos)) => {
+            cmap.cid_system_info = infos.iter().find_map(|v| match v {
+                CMapValue::Dict(info) => cid_system_info(info),
+                _ => None,
+            });
+        }
+        _ => {}
+    }
+}
 
-        let code = match u16::from_str_radix(part, 16) {
-            Ok(c) => c,
-            Err(_) => return None,
+fn cid_system_info(info: &HashMap<String, CMapValue>) -> Option<CidSystemInfo> {
+    let text = |key: &str| match info.get(key) {
+        Some(CMapValue::String(s)) => Some(String::from_utf8_lossy(s).into_owned()),
+        Some(CMapValue::Name(n)) => Some(n.clone()),
+        _ => None,
+    };
+    let supplement = match info.get("Supplement") {
+        Some(CMapValue::Integer(n)) => *n as i32,
+        Some(CMapValue::Real(n)) => *n as i32,
+        _ => 0,
+    };
+    Some(CidSystemInfo {
+        registry: text("Registry")?,
+        ordering: text("Ordering")?,
+        supplement,
+    })
+}
+
+// Reads the operands of one `begin<section> ... end<section>` block and records
+// its entries. Returns the token that interrupted an unterminated section so the
+// caller can process it.
+fn parse_section(
+    section: &str,
+    declared: Option<i64>,
+    tokens: &mut PsTokenizer,
+    cmap: &mut CMap,
+) -> Option<PsToken> {
+    let end_op = format!("end{}", section);
+    let mut operands: Vec<CMapValue> = Vec::new();
+    let mut array: Option<Vec<CMapValue>> = None;
+    let mut interrupted = None;
+    let mut terminated = false;
+
+    for token in tokens.by_ref() {
+        let value = match token {
+            PsToken::Operator(ref op) if *op == end_op => {
+                terminated = true;
+                break;
+            }
+            PsToken::Operator(ref op) if op.starts_with("begin") || op.starts_with("end") => {
+                interrupted = Some(token);
+                break;
+            }
+            PsToken::ArrayStart => {
+                if array.is_some() {
+                    cmap.warnings
+                        .push(format!("nested array in {} section", section));
+                }
+                array = Some(Vec::new());
+                continue;
+            }
+            PsToken::ArrayEnd => match array.take() {
+                Some(items) => CMapValue::Array(items),
+                None => {
+                    cmap.warnings
+                        .push(format!("unbalanced ']' in {} section", section));
+                    continue;
+                }
+            },
+            PsToken::String(s) => CMapValue::String(s),
+            PsToken::Name(n) => CMapValue::Name(n),
+            PsToken::Integer(n) => CMapValue::Integer(n),
+            PsToken::Real(n) => CMapValue::Real(n),
+            other => {
+                cmap.warnings.push(format!(
+                    "unexpected token {:?} in {} section",
+                    other, section
+                ));
+                continue;
+            }
         };
+        match array.as_mut() {
+            Some(items) => items.push(value),
+            None => operands.push(value),
+        }
+    }
+    if !terminated {
+        cmap.warnings
+            .push(format!("unterminated {} section", section));
+    }
 
-        // Handle UTF-16 surrogate pairs
-        if (0xD800..=0xDBFF).contains(&code) {
-            if i + 1 < chunks.len() {
-                let next_part = match core::str::from_utf8(chunks[i + 1]) {
-                    Ok(s) => s,
-                    Err(_) => continue,
-                };
-                if let Ok(low) = u16::from_str_radix(next_part, 16) {
-                    if (0xDC00..=0xDFFF).contains(&low) {
-                        let combined =
-                            0x10000 + (((code - 0xD800) as u32) << 10) + ((low - 0xDC00) as u32);
-                        if let Some(ch) = char::from_u32(combined) {
-                            out.push(ch);
-                            i += 2;
-                            continue;
+    let arity = match section {
+        "bfrange" | "cidrange" | "notdefrange" => 3,
+        _ => 2,
+    };
+    let chunks = operands.chunks_exact(arity);
+    if !chunks.remainder().is_empty() {
+        cmap.warnings
+            .push(format!("incomplete trailing entry in {} section", section));
+    }
+    let mut count = 0;
+    for (index, entry) in chunks.enumerate() {
+        count += 1;
+        if let Err(reason) = apply_entry(section, entry, cmap) {
+            cmap.warnings
+                .push(format!("{} entry {}: {}", section, index, reason));
+        }
+    }
+    if let Some(declared) = declared {
+        if declared != count {
+            cmap.warnings.push(format!(
+                "{} section declares {} entries but contains {}",
+                section, declared, count
+            ));
+        }
+    }
+    interrupted
+}
+
+fn apply_entry(section: &str, entry: &[CMapValue], cmap: &mut CMap) -> Result<(), String> {
+    match section {
+        "codespacerange" => {
+            let (low, low_len) = code_operand(&entry[0])?;
+            let (high, high_len) = code_operand(&entry[1])?;
+            if low_len != high_len {
+                return Err("bounds have different byte lengths".to_string());
+            }
+            cmap.codespace_ranges.push(CodespaceRange {
+                low,
+                high,
+                bytes: low_len,
+            });
+        }
+        "bfchar" => {
+            let (code, _) = code_operand(&entry[0])?;
+            let text = unicode_operand(&entry[1], cmap)?;
+            cmap.to_unicode.insert(code, text);
+            cmap.range_codes.remove(&code);
+        }
+        "bfrange" => {
+            let (low, high) = range_operands(&entry[0], &entry[1])?;
+            match &entry[2] {
+                CMapValue::Array(items) => {
+                    if items.len() as u32 != high - low + 1 {
+                        cmap.warnings.push(format!(
+                            "bfrange <{:X}> <{:X}> has {} destinations",
+                            low,
+                            high,
+                            items.len()
+                        ));
+                    }
+                    for (code, item) in (low..=high).zip(items) {
+                        // A bad destination only loses its own code.
+                        match unicode_operand(item, cmap) {
+                            Ok(text) => {
+                                cmap.to_unicode.insert(code, text);
+                                cmap.range_codes.insert(code);
+                            }
+                            Err(reason) => cmap
+                                .warnings
+                                .push(format!("bfrange code <{:X}>: {}", code, reason)),
                         }
                     }
                 }
+                CMapValue::String(bytes) => {
+                    let mut units = utf16_units(bytes)?;
+                    if units.is_empty() {
+                        return Err("empty destination string".to_string());
+                    }
+                    let last = units.len() - 1;
+                    for code in low..=high {
+                        let text = units_to_string(&units, cmap);
+                        cmap.to_unicode.insert(code, text);
+                        cmap.range_codes.insert(code);
+                        units[last] = units[last].wrapping_add(1);
+                    }
+                }
+                other => return Err(format!("unsupported destination {:?}", other)),
             }
-            out.push('�');
-            i += 1;
-            continue;
-        } else if (0xDC00..=0xDFFF).contains(&code) {
-            out.push('�');
-        } else if let Some(ch) = char::from_u32(code as u32) {
-            out.push(ch);
-        } else {
-            out.push('�');
         }
-        i += 1;
+        "cidchar" | "notdefchar" => {
+            let (code, _) = code_operand(&entry[0])?;
+            let cid = cid_operand(&entry[1])?;
+            let range = CidRange {
+                low: code,
+                high: code,
+                cid,
+            };
+            push_cid_range(section, range, cmap);
+        }
+        "cidrange" | "notdefrange" => {
+            let (low, high) = range_operands(&entry[0], &entry[1])?;
+            let cid = cid_operand(&entry[2])?;
+            push_cid_range(section, CidRange { low, high, cid }, cmap);
+        }
+        _ => {}
     }
+    Ok(())
+}
 
-    Some(out)
+fn push_cid_range(section: &str, range: CidRange, cmap: &mut CMap) {
+    if section.starts_with("notdef") {
+        cmap.notdef_ranges.push(range);
+    } else {
+        cmap.cid_ranges.push(range);
+    }
 }
 
-// Enhanced byte decoding with better CMap handling
-pub fn cmap_decode_bytes(bytes: &[u8], cmap: &HashMap<u32, String>, is_cid: bool) -> String {
-    let mut result = String::new();
-    if is_cid {
-        // For CID fonts, codes are typically 2-byte sequences.
-        let mut i = 0;
-        while i < bytes.len() {
-            let code = if i + 1 < bytes.len() {
-                ((bytes[i] as u32) << 8) | (bytes[i + 1] as u32)
-            } else {
-                bytes[i] as u32
-            };
-            i += 2;
-            if let Some(txt) = cmap.get(&code) {
-                result.push_str(txt);
-            } else {
-                // Enhanced fallback for unmapped codes
-                if code < 128 {
-                    result.push(code as u8 as char);
-                } else {
-                    result.push('�');
-                }
-            }
+fn code_operand(value: &CMapValue) -> Result<(u32, usize), String> {
+    match value {
+        CMapValue::String(bytes) if (1..=4).contains(&bytes.len()) => Ok((
+            bytes.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32),
+            bytes.len(),
+        )),
+        CMapValue::String(bytes) => Err(format!("code of {} bytes", bytes.len())),
+        other => Err(format!("expected a code string, found {:?}", other)),
+    }
+}
+
+fn range_operands(low: &CMapValue, high: &CMapValue) -> Result<(u32, u32), String> {
+    let (low, low_len) = code_operand(low)?;
+    let (high, high_len) = code_operand(high)?;
+    if low_len != high_len {
+        return Err("range bounds have different byte lengths".to_string());
+    }
+    if low > high {
+        return Err(format!("inverted range <{:X}> <{:X}>", low, high));
+    }
+    if high - low >= MAX_RANGE_LEN {
+        return Err(format!("range <{:X}> <{:X}> is too large", low, high));
+    }
+    Ok((low, high))
+}
+
+fn cid_operand(value: &CMapValue) -> Result<u32, String> {
+    match value {
+        CMapValue::Integer(n) if *n >= 0 => Ok(*n as u32),
+        other => Err(format!("expected a CID, found {:?}", other)),
+    }
+}
+
+fn unicode_operand(value: &CMapValue, cmap: &mut CMap) -> Result<String, String> {
+    match value {
+        CMapValue::String(bytes) => Ok(units_to_string(&utf16_units(bytes)?, cmap)),
+        CMapValue::Name(name) => {
+            glyph_to_unicode(name).ok_or_else(|| format!("unknown glyph name /{}", name))
         }
+        other => Err(format!("expected a Unicode string, found {:?}", other)),
+    }
+}
+
+fn utf16_units(bytes: &[u8]) -> Result<Vec<u16>, String> {
+    match bytes.len() {
+        0 => Ok(Vec::new()),
+        // Some producers write single-byte destinations; read them as Latin-1.
+        1 => Ok(vec![bytes[0] as u16]),
+        n if n % 2 == 1 => Err(format!("destination of odd length {}", n)),
+        _ => Ok(bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()),
+    }
+}
+
+fn units_to_string(units: &[u16], cmap: &mut CMap) -> String {
+    char::decode_utf16(units.iter().copied())
+        .map(|r| {
+            r.unwrap_or_else(|e| {
+                cmap.warnings
+                    .push(format!("unpaired surrogate {:04X}", e.unpaired_surrogate()));
+                '\u{FFFD}'
+            })
+        })
+        .collect()
+}
+
+// Enhanced code decoding with better CMap handling. The code is looked up in
+// the font's ToUnicode map, then in `embedded`; the first hit wins.
+fn cmap_decode_code(
+    code: u32,
+    font: &PdfFont,
+    embedded: Option<&HashMap<u32, String>>,
+) -> (String, CharSource) {
+    if let Some(txt) = font.to_unicode_map.as_ref().and_then(|m| m.get(&code)) {
+        let source = if let Some(&source) = font.to_unicode_repairs.get(&code) {
+            source
+        } else if font.to_unicode_ranges.contains(&code) {
+            CharSource::ToUnicodeRange
+        } else {
+            CharSource::ToUnicodeChar
+        };
+        (txt.clone(), source)
+    } else if let Some(txt) = embedded.and_then(|m| m.get(&code)) {
+        let source = font.embedded_source.unwrap_or(CharSource::EmbeddedCmap);
+        (txt.clone(), source)
+    } else if code < 128 {
+        // Enhanced fallback for unmapped codes
+        ((code as u8 as char).to_string(), CharSource::AsciiFallback)
     } else {
-        for &b in bytes {
-            if let Some(txt) = cmap.get(&(b as u32)) {
-                result.push_str(txt);
+        ('�'.to_string(), CharSource::Replacement)
+    }
+}
+
+/// Splits a shown string into character codes and decodes each one, with how
+/// its text was obtained. Codes that paint nothing or have no known Unicode
+/// value decode to "".
+pub fn decode_glyphs(bytes: &[u8], font: &PdfFont) -> Vec<(u32, String, CharSource)> {
+    let is_cid = font.subtype.as_deref() == Some("Type0");
+    // A simple font's declared encoding outranks the embedded program's own cmap.
+    let embedded = match &font.embedded_unicode {
+        Some(map) if is_cid || (font.encoding.is_none() && font.differences.is_none()) => Some(map),
+        _ => None,
+    };
+    let has_maps = font.to_unicode_map.is_some() || embedded.is_some();
+    split_codes(bytes, is_cid, &font.codespace_ranges)
+        .into_iter()
+        .map(|code| {
+            let (text, source) = if has_maps {
+                cmap_decode_code(code, font, embedded)
             } else {
-                // Enhanced fallback for unmapped codes
-                if b < 128 {
-                    result.push(b as char);
-                } else {
-                    result.push('�');
-                }
-            }
+                base_encode_byte(code as u8, font)
+            };
+            (code, text, source)
+        })
+        .collect()
+}
+
+/// Replaces ToUnicode entries that are likely wrong, as subsetting tools
+/// often write them, with what another source says the code shows. An entry
+/// is suspect when it maps to the Private Use Area or a control character;
+/// the other sources, in order, are the embedded program's own mapping, the
+/// code's glyph name for simple fonts, and for `Identity-H`/`Identity-V` CID
+/// fonts the Adobe collection named by `ordering`. Codes sharing their text
+/// are left alone, since fonts often draw one character with several glyphs,
+/// unless the embedded program tells some of them apart from the rest; those
+/// take the embedded program's text. Each replacement is recorded in the
+/// font's warnings.
+pub fn repair_to_unicode(font: &mut PdfFont, ordering: Option<&str>) {
+    let Some(map) = &font.to_unicode_map else {
+        return;
+    };
+    let mut groups: HashMap<&str, Vec<u32>> = HashMap::new();
+    for (&code, text) in map {
+        groups.entry(text.as_str()).or_default().push(code);
+    }
+    let is_cid = font.subtype.as_deref() == Some("Type0");
+    let identity = matches!(font.encoding.as_deref(), Some("Identity-H" | "Identity-V"));
+    let embedded_source = font.embedded_source.unwrap_or(CharSource::EmbeddedCmap);
+    let plausible = |t: &str, text: &str| {
+        !t.is_empty() && t != text && !t.chars().any(|c| is_private_use(c) || c.is_control())
+    };
+    let embedded = |code: u32, text: &str| {
+        font.embedded_unicode
+            .as_ref()
+            .and_then(|m| m.get(&code))
+            .filter(|t| plausible(t, text))
+            .map(|t| (t.clone(), embedded_source))
+    };
+
+    let mut repairs = Vec::new();
+    for (&code, text) in map {
+        let suspect = text.chars().any(is_private_use) || text.chars().any(char::is_control);
+        let alternative = if suspect {
+            let named = (!is_cid)
+                .then(|| base_encode_byte(code as u8, font))
+                .filter(|(t, source)| *source == CharSource::GlyphName && plausible(t, text));
+            let collection = ordering
+                .filter(|_| is_cid && identity)
+                .and_then(|ordering| cid_to_unicode(ordering, code))
+                .map(|c| (c.to_string(), CharSource::PredefinedCMap))
+                .filter(|(t, _)| plausible(t, text));
+            [embedded(code, text), named, collection]
+                .into_iter()
+                .flatten()
+                .next()
+        } else {
+            let group = &groups[text.as_str()];
+            let told_apart =
+                group.len() > 1 && !group.iter().all(|&other| embedded(other, text).is_some());
+            embedded(code, text).filter(|_| told_apart)
+        };
+        let Some((replacement, source)) = alternative else {
+            continue;
+        };
+        let problem = if text.chars().any(is_private_use) {
+            "a private use character"
+        } else if text.chars().any(char::is_control) {
+            "a control character"
+        } else {
+            "also the text of another code"
+        };
+        repairs.push((code, text.clone(), problem, replacement, source));
+    }
+
+    repairs.sort_by_key(|repair| repair.0);
+    let width = if is_cid { 4 } else { 2 };
+    for (code, text, problem, replacement, source) in repairs {
+        let from = match source {
+            CharSource::EmbeddedCmap => "the embedded font",
+            CharSource::PredefinedCMap => "the character collection",
+            _ => "the glyph name",
+        };
+        font.warnings.push(format!(
+            "ToUnicode: <{:0width$X}> maps to {} ({}); using {} from {}",
+            code,
+            code_points(&text),
+            problem,
+            code_points(&replacement),
+            from,
+            width = width
+        ));
+        font.to_unicode_ranges.remove(&code);
+        font.to_unicode_repairs.insert(code, source);
+        if let Some(map) = &mut font.to_unicode_map {
+            map.insert(code, replacement);
         }
     }
-    result
 }
 
-// Enhanced decode_bytes function with better fallback strategies
-pub fn decode_bytes(bytes: &[u8], font: &PdfFont) -> String {
-    if let Some(cmap) = &font.to_unicode_map {
-        let is_cid = font.subtype.as_deref() == Some("Type0");
-        return cmap_decode_bytes(bytes, cmap, is_cid);
+fn is_private_use(c: char) -> bool {
+    matches!(c as u32, 0xE000..=0xF8FF | 0xF0000..=0x10FFFF)
+}
+
+fn code_points(text: &str) -> String {
+    text.chars()
+        .map(|c| format!("U+{:04X}", c as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Splits a shown string into character codes: by the codespace ranges when
+// there are any, else two bytes at a time for CID fonts and one for simple
+// fonts. Bytes starting no code in the codespace are consumed as a code as
+// long as the shortest range, as the PDF specification asks.
+fn split_codes(bytes: &[u8], is_cid: bool, ranges: &[CodespaceRange]) -> Vec<u32> {
+    let value = |chunk: &[u8]| chunk.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    if ranges.is_empty() {
+        let step = if is_cid { 2 } else { 1 };
+        return bytes.chunks(step).map(value).collect();
+    }
+    let shortest = ranges.iter().map(|r| r.bytes).min().unwrap_or(1);
+    let mut codes = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let len = (1..=4)
+            .find(|&len| {
+                rest.len() >= len
+                    && ranges
+                        .iter()
+                        .any(|r| r.bytes == len && in_codespace(&rest[..len], r))
+            })
+            .unwrap_or(shortest)
+            .min(rest.len());
+        codes.push(value(&rest[..len]));
+        rest = &rest[len..];
     }
+    codes
+}
 
-    base_encode_bytes(bytes, font)
+// Codespace ranges bound each byte of a code separately.
+fn in_codespace(code: &[u8], range: &CodespaceRange) -> bool {
+    code.iter().enumerate().all(|(i, &b)| {
+        let shift = 8 * (code.len() - 1 - i);
+        let low = (range.low >> shift) as u8;
+        let high = (range.high >> shift) as u8;
+        (low..=high).contains(&b)
+    })
 }
 
-// Base encoding for fonts without CMap
-fn base_encode_bytes(bytes: &[u8], font: &PdfFont) -> String {
-    let mut result = String::new();
-    for &b in bytes {
-        let ch = if font.subtype.as_deref() == Some("Type1") {
-            standard_to_unicode(b)
-        } else {
+// Base encoding for fonts without CMap. Differences override the named base
+// encoding; with no name, the font program's built-in encoding is the base.
+fn base_encode_byte(b: u8, font: &PdfFont) -> (String, CharSource) {
+    let code = b as u32;
+    let builtin = match font.encoding {
+        None => font.builtin_encoding.as_ref().and_then(|e| e.get(&code)),
+        Some(_) => None,
+    };
+    let glyph = font
+        .differences
+        .as_ref()
+        .and_then(|d| d.get(&code))
+        .or(builtin);
+    if let Some(name) = glyph {
+        // A Type3 glyph without a procedure paints nothing.
+        if let Some(procs) = &font.char_procs {
+            if !procs.contains(name) {
+                return (String::new(), CharSource::GlyphName);
+            }
+        }
+        let text = match font.subtype.as_deref() {
+            Some("Type3") => glyph_to_unicode(name).or_else(|| numbered_glyph(name)),
+            _ => glyph_to_unicode(name),
+        };
+        if let Some(text) = text {
+            return (text, CharSource::GlyphName);
+        }
+    }
+
+    let mut source = CharSource::GlyphName;
+    let ch = match font.encoding.as_deref() {
+        Some("WinAnsiEncoding") => winansi_to_unicode(b),
+        Some("MacRomanEncoding") => mac_roman_to_unicode(b),
+        Some("MacExpertEncoding") => mac_expert_to_unicode(b),
+        Some("StandardEncoding") => standard_to_unicode(b),
+        // A Type1 font without an encoding falls back to StandardEncoding.
+        _ if font.subtype.as_deref() == Some("Type1") => standard_to_unicode(b),
+        _ => {
             if b < 128 {
+                source = CharSource::AsciiFallback;
                 b as char
             } else {
+                source = CharSource::Replacement;
                 '�'
             }
-        };
-
-        if ch != '\0' && ch != '�' {
-            result.push(ch);
         }
+    };
+
+    if ch != '\0' && ch != '�' {
+        (ch.to_string(), source)
+    } else {
+        (String::new(), source)
     }
-    result
 }
 
-// Standard encoding for Type1 fonts
-fn standard_to_unicode(b: u8) -> char {
-    match b {
-        0x20 => ' ', 0x21 => '!', 0x22 => '"', 0x23 => '#', 0x24 => '$', 0x25 => '%',
-        0x26 => '&', 0x27 => '\'', 0x28 => '(', 0x29 => ')', 0x2A => '*', 0x2B => '+',
-        0x2C => ',', 0x2D => '-', 0x2E => '.', 0x2F => '/', 0x30 => '0', 0x31 => '1',
-        0x32 => '2', 0x33 => '3', 0x34 => '4', 0x35 => '5', 0x36 => '6', 0x37 => '7',
-        0x38 => '8', 0x39 => '9', 0x3A => ':', 0x3B => ';', 0x3C => '<', 0x3D => '=',
-        0x3E => '>', 0x3F => '?', 0x40 => '@', 0x41 => 'A', 0x42 => 'B', 0x43 => 'C',
-        0x44 => 'D', 0x45 => 'E', 0x46 => 'F', 0x47 => 'G', 0x48 => 'H', 0x49 => 'I',
-        0x4A => 'J', 0x4B => 'K', 0x4C => 'L', 0x4D => 'M', 0x4E => 'N', 0x4F => 'O',
-        0x50 => 'P', 0x51 => 'Q', 0x52 => 'R', 0x53 => 'S', 0x54 => 'T', 0x55 => 'U',
-        0x56 => 'V', 0x57 => 'W', 0x58 => 'X', 0x59 => 'Y', 0x5A => 'Z', 0x5B => '[',
-        0x5C => '\\', 0x5D => ']', 0x5E => '^', 0x5F => '_', 0x60 => '`', 0x61 => 'a',
-        0x62 => 'b', 0x63 => 'c', 0x64 => 'd', 0x65 => 'e', 0x66 => 'f', 0x67 => 'g',
-        0x68 => 'h', 0x69 => 'i', 0x6A => 'j', 0x6B => 'k', 0x6C => 'l', 0x6D => 'm',
-        0x6E => 'n', 0x6F => 'o', 0x70 => 'p', 0x71 => 'q', 0x72 => 'r', 0x73 => 's',
-        0x74 => 't', 0x75 => 'u', 0x76 => 'v', 0x77 => 'w', 0x78 => 'x', 0x79 => 'y',
-        0x7A => 'z', 0x7B => '{', 0x7C => '|', 0x7D => '}', 0x7E => '~', 0x80 => '€',
-        0x81 => ' ', 0x82 => '‚', 0x83 => 'ƒ', 0x84 => '„', 0x85 => '…', 0x86 => '†',
-        0x87 => '‡', 0x88 => 'ˆ', 0x89 => '‰', 0x8A => 'Š', 0x8B => '‹', 0x8C => 'Œ',
-        0x8D => ' ', 0x8E => 'Ž', 0x8F => ' ', 0x90 => ' ', 0x91 => '\'', 0x92 => '\'',
-        0x93 => '"', 0x94 => '"', 0x95 => '•', 0x96 => '–', 0x97 => '—', 0x98 => '˜',
-        0x99 => '™', 0x9A => 'š', 0x9B => '›', 0x9C => 'œ', 0x9D => ' ', 0x9E => 'ž',
-        0x9F => 'Ÿ', 0xA0 => ' ', 0xA1 => '¡', 0xA2 => '¢', 0xA3 => '£', 0xA4 => '¤',
-        0xA5 => '¥', 0xA6 => '¦', 0xA7 => '§', 0xA8 => '¨', 0xA9 => '©', 0xAA => 'ª',
-        0xAB => '«', 0xAC => '¬', 0xAD => '­', 0xAE => '®', 0xAF => '¯', 0xB0 => '°',
-        0xB1 => '±', 0xB2 => '²', 0xB3 => '³', 0xB4 => '´', 0xB5 => 'µ', 0xB6 => '¶',
-        0xB7 => '·', 0xB8 => '¸', 0xB9 => '¹', 0xBA => 'º', 0xBB => '»', 0xBC => '¼',
-        0xBD => '½', 0xBE => '¾', 0xBF => '¿', 0xC0 => 'À', 0xC1 => 'Á', 0xC2 => 'Â',
-        0xC3 => 'Ã', 0xC4 => 'Ä', 0xC5 => 'Å', 0xC6 => 'Æ', 0xC7 => 'Ç', 0xC8 => 'È',
-        0xC9 => 'É', 0xCA => 'Ê', 0xCB => 'Ë', 0xCC => 'Ì', 0xCD => 'Í', 0xCE => 'Î',
-        0xCF => 'Ï', 0xD0 => 'Ð', 0xD1 => 'Ñ', 0xD2 => 'Ò', 0xD3 => 'Ó', 0xD4 => 'Ô',
-        0xD5 => 'Õ', 0xD6 => 'Ö', 0xD7 => '×', 0xD8 => 'Ø', 0xD9 => 'Ù', 0xDA => 'Ú',
-        0xDB => 'Û', 0xDC => 'Ü', 0xDD => 'Ý', 0xDE => 'Þ', 0xDF => 'ß', 0xE0 => 'à',
-        0xE1 => 'á', 0xE2 => 'â', 0xE3 => 'ã', 0xE4 => 'ä', 0xE5 => 'å', 0xE6 => 'æ',
-        0xE7 => 'ç', 0xE8 => 'è', 0xE9 => 'é', 0xEA => 'ê', 0xEB => 'ë', 0xEC => 'ì',
-        0xED => 'í', 0xEE => 'î', 0xEF => 'ï', 0xF0 => 'ð', 0xF1 => 'ñ', 0xF2 => 'ò',
-        0xF3 => 'ó', 0xF4 => 'ô', 0xF5 => 'õ', 0xF6 => 'ö', 0xF7 => '÷', 0xF8 => 'ø',
-        0xF9 => 'ù', 0xFA => 'ú', 0xFB => 'û', 0xFC => 'ü', 0xFD => 'ý', 0xFE => 'þ',
-        0xFF => 'ÿ',
-        _ => '�',
+// Type3 generators often name glyphs after their code: `Cdd`/`cdd` with a
+// decimal code, or `Gxx`/`gxx` with a hexadecimal one.
+fn numbered_glyph(name: &str) -> Option<String> {
+    let (radix, digits) = match name.split_at_checked(1)? {
+        ("C" | "c", digits) if (2..=3).contains(&digits.len()) => (10, digits),
+        ("G" | "g", digits) if digits.len() == 2 => (16, digits),
+        _ => return None,
+    };
+    let code = u32::from_str_radix(digits, radix).ok()?;
+    char::from_u32(code)
+        .filter(|c| !c.is_control())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cmap, split_codes};
+
+    #[test]
+    fn parses_minified_and_wrapped_entries() {
+        let data = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap \
+            /CIDSystemInfo 3 dict dup begin /Registry (Adobe) def /Ordering (UCS) def \
+            /Supplement 0 def end def /CMapName /Adobe-Identity-UCS def /WMode 1 def \
+            1 begincodespacerange <0000> <FFFF> endcodespacerange \
+            2 beginbfchar <0003> <0020> <0011> <4EBA> endbfchar 1 beginbfrange <0020>\n\
+            <0022>\n<6C11> endbfrange 1 beginbfrange <0030> <0031> [<5E01> /A] endbfrange \
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let cmap = parse_cmap(data);
+
+        assert_eq!(cmap.name.as_deref(), Some("Adobe-Identity-UCS"));
+        assert_eq!(cmap.wmode, 1);
+        let info = cmap.cid_system_info.expect("CIDSystemInfo");
+        assert_eq!(
+            (info.registry.as_str(), info.ordering.as_str()),
+            ("Adobe", "UCS")
+        );
+        assert_eq!(cmap.codespace_ranges.len(), 1);
+        assert_eq!(cmap.to_unicode[&0x11], "人");
+        assert_eq!(cmap.to_unicode[&0x20], "民");
+        assert_eq!(cmap.to_unicode[&0x22], "\u{6C13}");
+        assert_eq!(cmap.to_unicode[&0x30], "币");
+        assert_eq!(cmap.to_unicode[&0x31], "A");
+        assert!(cmap.warnings.is_empty(), "{:?}", cmap.warnings);
+    }
+
+    #[test]
+    fn reports_malformed_entries() {
+        let data = b"2 beginbfchar <01> <0041> <02> endbfchar \
+            1 beginbfrange <10> <05> <0041> endbfrange 1 begincidrange <00> <FF> 1 endcidrange";
+        let cmap = parse_cmap(data);
+
+        assert_eq!(cmap.to_unicode.get(&1).map(String::as_str), Some("A"));
+        assert_eq!(cmap.cid_ranges.len(), 1);
+        assert_eq!(cmap.warnings.len(), 3, "{:?}", cmap.warnings);
+    }
+
+    #[test]
+    fn malformed_bfrange_array_items_are_skipped_alone() {
+        let data = b"1 beginbfrange <01> <03> [<0041> 7 <0043>] endbfrange";
+        let cmap = parse_cmap(data);
+
+        assert_eq!(cmap.to_unicode.get(&1).map(String::as_str), Some("A"));
+        assert_eq!(cmap.to_unicode.get(&2), None);
+        assert_eq!(cmap.to_unicode.get(&3).map(String::as_str), Some("C"));
+        assert_eq!(cmap.warnings.len(), 1, "{:?}", cmap.warnings);
+    }
+
+    #[test]
+    fn stray_delimiters_are_skipped_without_recursing() {
+        let mut data = b")>".repeat(1_000_000);
+        data.extend_from_slice(b" 1 beginbfchar <01> <0041> endbfchar");
+        let cmap = parse_cmap(&data);
+        assert_eq!(cmap.to_unicode.get(&1).map(String::as_str), Some("A"));
+    }
+
+    #[test]
+    fn codes_split_by_codespace_ranges() {
+        // Shift JIS style: one-byte codes below 0x81, two-byte codes above.
+        let data = b"2 begincodespacerange <00> <80> <8140> <FCFC> endcodespacerange";
+        let ranges = parse_cmap(data).codespace_ranges;
+        assert_eq!(
+            split_codes(b"A\x81\x40B\xFE", true, &ranges),
+            [0x41, 0x8140, 0x42, 0xFE]
+        );
+        assert_eq!(split_codes(b"\x00A\x00B", true, &[]), [0x41, 0x42]);
     }
 }
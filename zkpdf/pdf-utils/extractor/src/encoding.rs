@@ -1,60 +1,427 @@
-/// Map a glyph name to a Unicode character (for Differences).
-pub fn glyph_to_unicode(name: &str) -> Option<char> {
-    // Common glyphs:
-    let mapping = [
-        ("space", ' '),
-        ("parenleft", '('),
-        ("parenright", ')'),
-        ("minus", '-'),
-        ("period", '.'),
-        ("comma", ','),
-        ("colon", ':'),
-        ("semicolon", ';'),
-        ("question", '?'),
-        ("exclam", '!'),
-        ("trademark", '™'),
-        ("Trademark", '™'),
-        ("bullet", '•'),
-        ("Euro", '€'),
-        ("Euroglyph", '€'),
-        ("yen", '¥'),
-        ("florin", 'ƒ'),
-        ("emdash", '—'),
-        ("endash", '–'),
-        ("quotedblleft", '“'),
-        ("quotedblright", '”'),
-        ("quoteleft", '‘'),
-        ("quoteright", '’'),
-        ("AE", 'Æ'),
-        ("ae", 'æ'),
-        ("OE", 'Œ'),
-        ("oe", 'œ'),
-        ("fi", 'ﬁ'),
-        ("fl", 'ﬂ'),
-        ("ffi", 'ﬃ'),
-        ("ffl", 'ﬄ'),
-        ("ff", 'ﬀ'),
-        ("dotlessi", 'ı'),
-        ("dotlessj", 'ȷ'),
-        ("germandbls", 'ß'),
-        ("registered", '®'),
-        ("copyright", '©'),
-        ("trademark", '™'),
-        // ... more can be added as needed
-    ];
-    for (glyph, ch) in mapping.iter() {
-        if glyph.eq_ignore_ascii_case(name) {
-            return Some(*ch);
+/// Map a glyph name to Unicode text (for Differences and embedded fonts).
+///
+/// Follows the Adobe Glyph List conventions: suffixes after `.` are ignored,
+/// `_` joins ligature components, and `uniXXXX` / `uXXXX[XX]` names carry
+/// their code points directly.
+pub fn glyph_to_unicode(name: &str) -> Option<String> {
+    let base = name.split('.').next().unwrap_or("");
+    if base.is_empty() {
+        return None;
+    }
+    let mut text = String::new();
+    for component in base.split('_') {
+        text.push_str(&glyph_component_to_unicode(component)?);
+    }
+    Some(text)
+}
+
+fn glyph_component_to_unicode(name: &str) -> Option<String> {
+    if let Some(&(_, ch)) = GLYPH_NAMES.iter().find(|(glyph, _)| *glyph == name) {
+        return Some(ch.to_string());
+    }
+    if let Some(hex) = name.strip_prefix("uni") {
+        if !hex.is_empty() && hex.len() % 4 == 0 {
+            let mut text = String::new();
+            for i in (0..hex.len()).step_by(4) {
+                let unit = u32::from_str_radix(hex.get(i..i + 4)?, 16).ok()?;
+                text.push(char::from_u32(unit)?);
+            }
+            return Some(text);
+        }
+    }
+    if let Some(hex) = name.strip_prefix('u') {
+        if (4..=6).contains(&hex.len()) && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            let code = u32::from_str_radix(hex, 16).ok()?;
+            return char::from_u32(code).map(String::from);
         }
     }
-    // If not found, attempt basic single-letter names (like A, B, a, b, etc.)
-    if name.len() == 1 {
-        let c = name.chars().next().unwrap();
-        return Some(c);
+    // Single letters and digits name themselves.
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Some(c.to_string()),
+        _ => None,
     }
-    None
 }
 
+// Subset of the Adobe Glyph List covering the Standard, WinAnsi, MacRoman and
+// TrueType `post` glyph names.
+const GLYPH_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("quotesingle", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("hyphen", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("zero", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("exclamdown", '¡'),
+    ("cent", '¢'),
+    ("sterling", '£'),
+    ("currency", '¤'),
+    ("yen", '¥'),
+    ("brokenbar", '¦'),
+    ("section", '§'),
+    ("dieresis", '¨'),
+    ("copyright", '©'),
+    ("ordfeminine", 'ª'),
+    ("guillemotleft", '«'),
+    ("logicalnot", '¬'),
+    ("sfthyphen", '\u{AD}'),
+    ("registered", '®'),
+    ("macron", '¯'),
+    ("degree", '°'),
+    ("plusminus", '±'),
+    ("twosuperior", '²'),
+    ("threesuperior", '³'),
+    ("acute", '´'),
+    ("mu", 'µ'),
+    ("paragraph", '¶'),
+    ("periodcentered", '·'),
+    ("cedilla", '¸'),
+    ("onesuperior", '¹'),
+    ("ordmasculine", 'º'),
+    ("guillemotright", '»'),
+    ("onequarter", '¼'),
+    ("onehalf", '½'),
+    ("threequarters", '¾'),
+    ("questiondown", '¿'),
+    ("Agrave", 'À'),
+    ("Aacute", 'Á'),
+    ("Acircumflex", 'Â'),
+    ("Atilde", 'Ã'),
+    ("Adieresis", 'Ä'),
+    ("Aring", 'Å'),
+    ("AE", 'Æ'),
+    ("Ccedilla", 'Ç'),
+    ("Egrave", 'È'),
+    ("Eacute", 'É'),
+    ("Ecircumflex", 'Ê'),
+    ("Edieresis", 'Ë'),
+    ("Igrave", 'Ì'),
+    ("Iacute", 'Í'),
+    ("Icircumflex", 'Î'),
+    ("Idieresis", 'Ï'),
+    ("Eth", 'Ð'),
+    ("Ntilde", 'Ñ'),
+    ("Ograve", 'Ò'),
+    ("Oacute", 'Ó'),
+    ("Ocircumflex", 'Ô'),
+    ("Otilde", 'Õ'),
+    ("Odieresis", 'Ö'),
+    ("multiply", '×'),
+    ("Oslash", 'Ø'),
+    ("Ugrave", 'Ù'),
+    ("Uacute", 'Ú'),
+    ("Ucircumflex", 'Û'),
+    ("Udieresis", 'Ü'),
+    ("Yacute", 'Ý'),
+    ("Thorn", 'Þ'),
+    ("germandbls", 'ß'),
+    ("agrave", 'à'),
+    ("aacute", 'á'),
+    ("acircumflex", 'â'),
+    ("atilde", 'ã'),
+    ("adieresis", 'ä'),
+    ("aring", 'å'),
+    ("ae", 'æ'),
+    ("ccedilla", 'ç'),
+    ("egrave", 'è'),
+    ("eacute", 'é'),
+    ("ecircumflex", 'ê'),
+    ("edieresis", 'ë'),
+    ("igrave", 'ì'),
+    ("iacute", 'í'),
+    ("icircumflex", 'î'),
+    ("idieresis", 'ï'),
+    ("eth", 'ð'),
+    ("ntilde", 'ñ'),
+    ("ograve", 'ò'),
+    ("oacute", 'ó'),
+    ("ocircumflex", 'ô'),
+    ("otilde", 'õ'),
+    ("odieresis", 'ö'),
+    ("divide", '÷'),
+    ("oslash", 'ø'),
+    ("ugrave", 'ù'),
+    ("uacute", 'ú'),
+    ("ucircumflex", 'û'),
+    ("udieresis", 'ü'),
+    ("yacute", 'ý'),
+    ("thorn", 'þ'),
+    ("ydieresis", 'ÿ'),
+    ("Ydieresis", 'Ÿ'),
+    ("nonbreakingspace", '\u{A0}'),
+    ("Gbreve", 'Ğ'),
+    ("gbreve", 'ğ'),
+    ("Idotaccent", 'İ'),
+    ("dotlessi", 'ı'),
+    ("dotlessj", 'ȷ'),
+    ("Scedilla", 'Ş'),
+    ("scedilla", 'ş'),
+    ("Cacute", 'Ć'),
+    ("cacute", 'ć'),
+    ("Ccaron", 'Č'),
+    ("ccaron", 'č'),
+    ("dcroat", 'đ'),
+    ("Lslash", 'Ł'),
+    ("lslash", 'ł'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+    ("Scaron", 'Š'),
+    ("scaron", 'š'),
+    ("Zcaron", 'Ž'),
+    ("zcaron", 'ž'),
+    ("florin", 'ƒ'),
+    ("circumflex", 'ˆ'),
+    ("caron", 'ˇ'),
+    ("breve", '˘'),
+    ("dotaccent", '˙'),
+    ("ring", '˚'),
+    ("ogonek", '˛'),
+    ("tilde", '˜'),
+    ("hungarumlaut", '˝'),
+    ("Delta", '∆'),
+    ("Omega", 'Ω'),
+    ("pi", 'π'),
+    ("endash", '–'),
+    ("emdash", '—'),
+    ("quoteleft", '‘'),
+    ("quoteright", '’'),
+    ("quotesinglbase", '‚'),
+    ("quotedblleft", '“'),
+    ("quotedblright", '”'),
+    ("quotedblbase", '„'),
+    ("dagger", '†'),
+    ("daggerdbl", '‡'),
+    ("bullet", '•'),
+    ("ellipsis", '…'),
+    ("perthousand", '‰'),
+    ("guilsinglleft", '‹'),
+    ("guilsinglright", '›'),
+    ("fraction", '⁄'),
+    ("franc", '₣'),
+    ("won", '₩'),
+    ("Euro", '€'),
+    ("Euroglyph", '€'),
+    ("trademark", '™'),
+    ("Trademark", '™'),
+    ("partialdiff", '∂'),
+    ("product", '∏'),
+    ("summation", '∑'),
+    ("minus", '−'),
+    ("radical", '√'),
+    ("infinity", '∞'),
+    ("integral", '∫'),
+    ("approxequal", '≈'),
+    ("notequal", '≠'),
+    ("lessequal", '≤'),
+    ("greaterequal", '≥'),
+    ("lozenge", '◊'),
+    ("ff", 'ﬀ'),
+    ("fi", 'ﬁ'),
+    ("fl", 'ﬂ'),
+    ("ffi", 'ﬃ'),
+    ("ffl", 'ﬄ'),
+];
+
+/// Glyph name for a code in Adobe StandardEncoding, the default built-in
+/// encoding of Type1 and CFF fonts.
+pub fn standard_encoding_glyph(code: u8) -> Option<&'static str> {
+    let name = match code {
+        0x20..=0x7E => ASCII_GLYPH_NAMES[(code - 0x20) as usize],
+        0xA1 => "exclamdown",
+        0xA2 => "cent",
+        0xA3 => "sterling",
+        0xA4 => "fraction",
+        0xA5 => "yen",
+        0xA6 => "florin",
+        0xA7 => "section",
+        0xA8 => "currency",
+        0xA9 => "quotesingle",
+        0xAA => "quotedblleft",
+        0xAB => "guillemotleft",
+        0xAC => "guilsinglleft",
+        0xAD => "guilsinglright",
+        0xAE => "fi",
+        0xAF => "fl",
+        0xB1 => "endash",
+        0xB2 => "dagger",
+        0xB3 => "daggerdbl",
+        0xB4 => "periodcentered",
+        0xB6 => "paragraph",
+        0xB7 => "bullet",
+        0xB8 => "quotesinglbase",
+        0xB9 => "quotedblbase",
+        0xBA => "quotedblright",
+        0xBB => "guillemotright",
+        0xBC => "ellipsis",
+        0xBD => "perthousand",
+        0xBF => "questiondown",
+        0xC1 => "grave",
+        0xC2 => "acute",
+        0xC3 => "circumflex",
+        0xC4 => "tilde",
+        0xC5 => "macron",
+        0xC6 => "breve",
+        0xC7 => "dotaccent",
+        0xC8 => "dieresis",
+        0xCA => "ring",
+        0xCB => "cedilla",
+        0xCD => "hungarumlaut",
+        0xCE => "ogonek",
+        0xCF => "caron",
+        0xD0 => "emdash",
+        0xE1 => "AE",
+        0xE3 => "ordfeminine",
+        0xE8 => "Lslash",
+        0xE9 => "Oslash",
+        0xEA => "OE",
+        0xEB => "ordmasculine",
+        0xF1 => "ae",
+        0xF5 => "dotlessi",
+        0xF8 => "lslash",
+        0xF9 => "oslash",
+        0xFA => "oe",
+        0xFB => "germandbls",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// StandardEncoding names for 0x20..=0x7E; ASCII except for the two quotes.
+const ASCII_GLYPH_NAMES: [&str; 95] = [
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quoteright",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "quoteleft",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+];
+
 /// WinAnsi (CP1252) encoding mapping
 pub fn winansi_to_unicode(byte: u8) -> char {
     // Map 0x00-0x7F directly (includes standard ASCII control and printable).
@@ -337,37 +704,13 @@ pub fn mac_expert_to_unicode(byte: u8) -> char {
     }
 }
 
-/// StandardEncoding mapping (Adobe Standard Latin). We map common ligatures and symbols; rest is ASCII.
+/// StandardEncoding mapping, through the glyph name each code stands for.
+/// Returns `'\0'` for codes StandardEncoding leaves unassigned.
 pub fn standard_to_unicode(byte: u8) -> char {
-    // 0x00-0x7F: ASCII
-    if byte < 0x80 {
-        return byte as char;
-    }
-    match byte {
-        0xA1 => '¡', 0xA2 => '¢', 0xA3 => '£', 0xA4 => '¤', 0xA5 => '¥', 
-        0xA7 => '§', 0xA8 => '¨', 0xA9 => '©', 0xAA => 'ª', 0xAB => '«',
-        0xAC => '¬', 0xAE => '®', 0xAF => '¯',
-        0xB0 => '°', 0xB1 => '±', 0xB2 => '²', 0xB3 => '³', 0xB4 => '´',
-        0xB5 => 'µ', 0xB6 => '¶', 0xB7 => '·', 0xB8 => '¸', 0xB9 => '¹',
-        0xBA => 'º', 0xBB => '»', 0xBC => '¼', 0xBD => '½', 0xBE => '¾',
-        0xC0 => 'À', 0xC1 => 'Á', 0xC2 => 'Â', 0xC3 => 'Ã', 0xC4 => 'Ä', 0xC5 => 'Å',
-        0xC6 => 'Æ', 0xC7 => 'Ç', 0xC8 => 'È', 0xC9 => 'É', 0xCA => 'Ê', 0xCB => 'Ë',
-        0xCC => 'Ì', 0xCD => 'Í', 0xCE => 'Î', 0xCF => 'Ï',
-        0xD0 => 'Ð', 0xD1 => 'Ñ', 0xD2 => 'Ò', 0xD3 => 'Ó', 0xD4 => 'Ô', 0xD5 => 'Õ',
-        0xD6 => 'Ö', 0xD7 => '×', 0xD8 => 'Ø', 0xD9 => 'Ù', 0xDA => 'Ú', 0xDB => 'Û',
-        0xDC => 'Ü', 0xDD => 'Ý', 0xDE => 'Þ', 0xDF => 'ß',
-        0xE0 => 'à', 0xE1 => 'á', 0xE2 => 'â', 0xE3 => 'ã', 0xE4 => 'ä', 0xE5 => 'å',
-        0xE6 => 'æ', 0xE7 => 'ç', 0xE8 => 'è', 0xE9 => 'é', 0xEA => 'ê', 0xEB => 'ë',
-        0xEC => 'ì', 0xED => 'í', 0xEE => 'î', 0xEF => 'ï',
-        0xF0 => 'ð', 0xF1 => 'ñ', 0xF2 => 'ò', 0xF3 => 'ó', 0xF4 => 'ô', 0xF5 => 'õ',
-        0xF6 => 'ö', 0xF7 => '÷', 0xF8 => 'ø', 0xF9 => 'ù', 0xFA => 'ú', 0xFB => 'û',
-        0xFC => 'ü', 0xFD => 'ý', 0xFE => 'þ', 0xFF => 'ÿ',
-        // StandardEncoding might not actually include all above, but we map as Latin1 for safety.
-        // Special ligatures present in Type1 standard fonts:
-        0xFF /* hypothetical code for fi ligature in StandardEncoding */ => 'ﬁ', // (In actual StandardEncoding, fi might be at a different code)
-        // If unknown, return placeholder:
-        _ => '\0'
-    }
+    standard_encoding_glyph(byte)
+        .and_then(glyph_to_unicode)
+        .and_then(|text| text.chars().next())
+        .unwrap_or('\0')
 }
 
 /// PDFDocEncoding mapping (partial: mostly same as Latin-1 for 0x20-0x7E and some differences above).
@@ -386,3 +729,49 @@ pub fn pdf_doc_to_unicode(byte: u8) -> char {
         }
     }
 }
+
+/// Decodes a PDF text string (document metadata, annotation contents, form
+/// values): UTF-16BE or UTF-8 when it starts with a byte order mark,
+/// PDFDocEncoding otherwise. Language tags (`ESC lang ESC`) in Unicode
+/// strings are dropped.
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+        let text: String = char::decode_utf16(units)
+            .map(|unit| unit.unwrap_or('\u{FFFD}'))
+            .collect();
+        return strip_language_tags(&text);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return strip_language_tags(&String::from_utf8_lossy(utf8));
+    }
+    bytes.iter().map(|&b| pdf_doc_to_unicode(b)).collect()
+}
+
+fn strip_language_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        if c == '\u{1B}' {
+            in_tag = !in_tag;
+        } else if !in_tag {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::standard_to_unicode;
+
+    #[test]
+    fn standard_encoding_follows_its_glyph_names() {
+        assert_eq!(standard_to_unicode(b'A'), 'A');
+        assert_eq!(standard_to_unicode(0x27), '\u{2019}');
+        assert_eq!(standard_to_unicode(0xAE), '\u{FB01}');
+        assert_eq!(standard_to_unicode(0x80), '\0');
+    }
+}
@@ -0,0 +1,202 @@
+//! Recognising glyphs by their outlines, for fonts whose codes have no
+//! Unicode mapping: CID fonts with `Identity-H`, no ToUnicode and a program
+//! stripped of its `cmap` and `post` tables.
+
+use std::collections::HashMap;
+
+use crate::font::{collect_fonts, glyph_outline};
+use crate::graphics::PathSegment;
+use crate::types::{FingerprintDatabase, PdfError, PdfFont, PdfObj};
+
+// Outlines are quantised to this many steps across their larger dimension,
+// coarse enough that rounding in different units per em rarely matters.
+const GRID: f64 = 64.0;
+
+/// A fingerprint of a glyph outline that is the same at any size and
+/// position in the em square, and for quadratic and cubic curves drawing
+/// the same shape. `None` for glyphs that draw nothing.
+pub fn outline_fingerprint(outline: &[PathSegment]) -> Option<u64> {
+    // Quadratic curves are raised to cubic ones with the same shape.
+    let mut current = (0.0, 0.0);
+    let mut start = (0.0, 0.0);
+    let mut segments = Vec::with_capacity(outline.len());
+    for segment in outline {
+        let cubic = match *segment {
+            PathSegment::QuadTo(c, p) => {
+                let toward = |from: (f64, f64)| {
+                    (
+                        from.0 + (c.0 - from.0) * 2.0 / 3.0,
+                        from.1 + (c.1 - from.1) * 2.0 / 3.0,
+                    )
+                };
+                PathSegment::CubicTo(toward(current), toward(p), p)
+            }
+            other => other,
+        };
+        current = match cubic {
+            PathSegment::MoveTo(p) => {
+                start = p;
+                p
+            }
+            PathSegment::LineTo(p) | PathSegment::CubicTo(_, _, p) => p,
+            PathSegment::QuadTo(_, p) => p,
+            PathSegment::Close => start,
+        };
+        segments.push(cubic);
+    }
+
+    let points: Vec<(f64, f64)> = segments.iter().flat_map(segment_points).collect();
+    let min_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let max_x = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let extent = (max_x - min_x).max(max_y - min_y);
+    if !extent.is_finite() || extent <= 0.0 {
+        return None;
+    }
+
+    // FNV-1a over each segment's kind and its quantised points.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for segment in &segments {
+        feed(&[match segment {
+            PathSegment::MoveTo(_) => b'M',
+            PathSegment::LineTo(_) => b'L',
+            PathSegment::QuadTo(..) | PathSegment::CubicTo(..) => b'C',
+            PathSegment::Close => b'Z',
+        }]);
+        for (x, y) in segment_points(segment) {
+            let x = ((x - min_x) / extent * GRID).round() as i16;
+            let y = ((y - min_y) / extent * GRID).round() as i16;
+            feed(&x.to_be_bytes());
+            feed(&y.to_be_bytes());
+        }
+    }
+    Some(hash)
+}
+
+fn segment_points(segment: &PathSegment) -> Vec<(f64, f64)> {
+    match *segment {
+        PathSegment::MoveTo(p) | PathSegment::LineTo(p) => vec![p],
+        PathSegment::QuadTo(c, p) => vec![c, p],
+        PathSegment::CubicTo(c1, c2, p) => vec![c1, c2, p],
+        PathSegment::Close => Vec::new(),
+    }
+}
+
+/// The text of the glyph `code` shows, looked up by its outline.
+pub fn recognise(font: &PdfFont, code: u32, database: &FingerprintDatabase) -> Option<String> {
+    let program = font.program.as_ref()?;
+    let outline = glyph_outline(program, *program.glyph_ids.get(&code)?)?;
+    database
+        .lookup(outline_fingerprint(&outline)?)
+        .map(str::to_string)
+}
+
+/// Adds the outline of every glyph whose Unicode value is known, from the
+/// ToUnicode map or the program's own cmap, of every embedded TrueType and
+/// CFF font in a document. Returns the number of fingerprints added.
+pub fn learn_document(
+    database: &mut FingerprintDatabase,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Result<usize, PdfError> {
+    // Every font dictionary in the file, including those only forms use.
+    let fonts: HashMap<String, PdfObj> = objects
+        .iter()
+        .filter(|(_, obj)| {
+            let PdfObj::Dictionary(dict) = obj else {
+                return false;
+            };
+            let name = |key: &str| match dict.get(key) {
+                Some(PdfObj::Name(name)) => name.as_str(),
+                _ => "",
+            };
+            // Descendant CID fonts are read through their Type0 parent.
+            name("Type") == "Font" && !name("Subtype").starts_with("CIDFontType")
+        })
+        .map(|(id, _)| (format!("{} {}", id.0, id.1), PdfObj::Reference(*id)))
+        .collect();
+    let resources = HashMap::from([("Font".to_string(), PdfObj::Dictionary(fonts))]);
+
+    let mut added = 0;
+    let mut fonts: Vec<PdfFont> = collect_fonts(&resources, objects, true)?
+        .into_values()
+        .collect();
+    // Learn in a stable order, so the first of two fonts drawing the same
+    // outline differently always wins.
+    fonts.sort_by(|a, b| a.base_name.cmp(&b.base_name));
+    for font in &fonts {
+        let Some(program) = &font.program else {
+            continue;
+        };
+        let mut codes: Vec<(&u32, &u16)> = program.glyph_ids.iter().collect();
+        codes.sort();
+        for (code, &gid) in codes {
+            let text = font
+                .to_unicode_map
+                .as_ref()
+                .and_then(|map| map.get(code))
+                .or_else(|| font.embedded_unicode.as_ref()?.get(code));
+            let Some(text) = text.filter(|t| !t.trim().is_empty() && *t != "\u{FFFD}") else {
+                continue;
+            };
+            let fingerprint = glyph_outline(program, gid).and_then(|o| outline_fingerprint(&o));
+            if let Some(fingerprint) = fingerprint {
+                if database.lookup(fingerprint).is_none() {
+                    database.insert(fingerprint, text.clone());
+                    added += 1;
+                }
+            }
+        }
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(scale: f64, dx: f64, quadratic: bool) -> Vec<PathSegment> {
+        let p = |x: f64, y: f64| (x * scale + dx, y * scale);
+        let mut outline = vec![
+            PathSegment::MoveTo(p(0.0, 0.0)),
+            PathSegment::LineTo(p(0.0, 10.0)),
+        ];
+        outline.push(if quadratic {
+            PathSegment::QuadTo(p(5.0, 13.0), p(10.0, 10.0))
+        } else {
+            PathSegment::CubicTo(p(10.0 / 3.0, 12.0), p(20.0 / 3.0, 12.0), p(10.0, 10.0))
+        });
+        outline.push(PathSegment::LineTo(p(10.0, 0.0)));
+        outline.push(PathSegment::Close);
+        outline
+    }
+
+    #[test]
+    fn fingerprints_ignore_scale_position_and_curve_degree() {
+        let base = outline_fingerprint(&square(1.0, 0.0, false)).unwrap();
+        assert_eq!(
+            outline_fingerprint(&square(2.048, 300.0, false)),
+            Some(base)
+        );
+        assert_eq!(outline_fingerprint(&square(1.0, 0.0, true)), Some(base));
+        let mut other = square(1.0, 0.0, false);
+        other[1] = PathSegment::LineTo((0.0, 8.0));
+        assert_ne!(outline_fingerprint(&other), Some(base));
+        assert_eq!(outline_fingerprint(&[]), None);
+
+        let mut database = FingerprintDatabase::default();
+        database.insert(base, "人".to_string());
+        database.insert(7, "fi".to_string());
+        let saved = database.to_text();
+        let loaded = FingerprintDatabase::from_text(&saved);
+        assert_eq!(loaded.lookup(base), Some("人"));
+        assert_eq!(loaded.lookup(7), Some("fi"));
+        assert_eq!(loaded.len(), 2);
+    }
+}
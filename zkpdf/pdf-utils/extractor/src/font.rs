@@ -1,7 +1,13 @@
-use crate::cmap::parse_cmap;
-use crate::types::PdfObj;
-use crate::handle_stream_filters;
-use std::collections::HashMap;
+use crate::cff::{self, parse_cff};
+use crate::cid_collections::cid_to_unicode;
+use crate::cmap::{parse_cmap, repair_to_unicode};
+use crate::encoding::{glyph_to_unicode, mac_roman_to_unicode, winansi_to_unicode};
+use crate::graphics::PathSegment;
+use crate::truetype::{self, parse_truetype};
+use crate::type1::parse_type1_encoding;
+use crate::types::{CharSource, CodespaceRange, FontProgram, PdfObj};
+use crate::{decode_stream, handle_stream_filters, resolve};
+use std::collections::{HashMap, HashSet};
 
 // Use the existing PdfFont from types module
 use crate::types::PdfFont;
@@ -31,7 +37,7 @@ pub fn collect_fonts(
                     let mut differences_map: Option<HashMap<u32, String>> = None;
 
                     if let Some(encoding_obj) = font_dic.get("Encoding") {
-                        match encoding_obj {
+                        match resolve(encoding_obj, objects) {
                             PdfObj::Name(enc_name) => {
                                 encoding_name = Some(enc_name.clone());
                             }
@@ -65,6 +71,9 @@ pub fn collect_fonts(
 
                     // Enhanced ToUnicode CMap extraction with better error handling
                     let mut to_uni_map: Option<HashMap<u32, String>> = None;
+                    let mut to_unicode_ranges = HashSet::new();
+                    let mut to_unicode_codespace = Vec::new();
+                    let mut warnings = Vec::new();
                     if let Some(PdfObj::Reference(tu_ref)) = font_dic.get("ToUnicode") {
                         if let Some(PdfObj::Stream(tu_stream)) = objects.get(tu_ref) {
                             let cmap_bytes = if let Some(filter) = tu_stream.dict.get("Filter") {
@@ -88,18 +97,74 @@ pub fn collect_fonts(
                                 tu_stream.data.clone()
                             };
 
-                            to_uni_map = Some(parse_cmap(&cmap_bytes));
+                            let cmap = parse_cmap(&cmap_bytes);
+                            warnings
+                                .extend(cmap.warnings.iter().map(|w| format!("ToUnicode: {}", w)));
+                            to_uni_map = Some(cmap.to_unicode);
+                            to_unicode_ranges = cmap.range_codes;
+                            to_unicode_codespace = cmap.codespace_ranges;
                         }
                     }
 
-                    let pdf_font = PdfFont {
+                    let (embedded_unicode, embedded_source) =
+                        embedded_unicode_map(font_dic, subtype.as_deref(), objects, &mut warnings)
+                            .unzip();
+                    let builtin_encoding = type1_builtin_encoding(font_dic, objects, &mut warnings);
+                    let (widths, default_width) =
+                        font_widths(font_dic, subtype.as_deref(), objects);
+                    let program = font_program(
+                        font_dic,
+                        subtype.as_deref(),
+                        encoding_name.as_deref(),
+                        differences_map.as_ref(),
+                        objects,
+                    );
+                    let codespace_ranges = match subtype.as_deref() {
+                        Some("Type0") => type0_codespace(font_dic, objects, to_unicode_codespace),
+                        _ => Vec::new(),
+                    };
+                    let is_type3 = subtype.as_deref() == Some("Type3");
+                    let font_matrix = match font_dic.get("FontMatrix").map(|m| resolve(m, objects))
+                    {
+                        Some(PdfObj::Array(values)) if is_type3 && values.len() == 6 => {
+                            let mut matrix = [0.0; 6];
+                            for (slot, value) in matrix.iter_mut().zip(values) {
+                                *slot = number(value, objects).unwrap_or(0.0);
+                            }
+                            matrix
+                        }
+                        _ => [0.001, 0.0, 0.0, 0.001, 0.0, 0.0],
+                    };
+                    let char_procs = match font_dic.get("CharProcs").map(|c| resolve(c, objects)) {
+                        Some(PdfObj::Dictionary(procs)) if is_type3 => {
+                            Some(procs.keys().cloned().collect())
+                        }
+                        _ => None,
+                    };
+
+                    let mut pdf_font = PdfFont {
                         base_name,
                         subtype,
                         encoding: encoding_name,
                         to_unicode_map: to_uni_map.map(|m| m.into_iter().collect()),
+                        to_unicode_ranges,
+                        to_unicode_repairs: HashMap::new(),
                         differences: differences_map,
+                        embedded_unicode,
+                        embedded_source,
+                        builtin_encoding,
+                        widths,
+                        codespace_ranges,
+                        default_width,
+                        font_matrix,
+                        char_procs,
+                        program,
+                        warnings,
                     };
 
+                    let ordering = cid_ordering(font_dic, objects);
+                    repair_to_unicode(&mut pdf_font, ordering.as_deref());
+
                     fonts.insert(name.clone(), pdf_font);
                 }
             }
@@ -108,3 +173,504 @@ pub fn collect_fonts(
 
     Ok(fonts)
 }
+
+/// The outline of glyph `gid` of an embedded font program, in glyph space
+/// units of 1/1000 em.
+pub fn glyph_outline(program: &FontProgram, gid: u16) -> Option<Vec<PathSegment>> {
+    let outline = if program.cff {
+        cff::glyph_outline(&program.data, gid)?
+    } else {
+        truetype::glyph_outline(&program.data, gid)?
+    };
+    let to_glyph_space = [1000.0, 0.0, 0.0, 1000.0, 0.0, 0.0];
+    Some(
+        outline
+            .iter()
+            .map(|s| s.transformed(&to_glyph_space))
+            .collect(),
+    )
+}
+
+// Loads the TrueType or CFF program embedded in a font, with the glyph ID of
+// every code it shows, so that its glyphs can be drawn. Unlike Unicode
+// recovery this needs no `cmap` or `post` table in CID-keyed fonts.
+fn font_program(
+    font_dic: &HashMap<String, PdfObj>,
+    subtype: Option<&str>,
+    encoding: Option<&str>,
+    differences: Option<&HashMap<u32, String>>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Option<FontProgram> {
+    let cid_font = descendant_font(font_dic, subtype, objects);
+    let descriptor = font_descriptor(cid_font.unwrap_or(font_dic), objects)?;
+    let (key, stream) = ["FontFile2", "FontFile3"].iter().find_map(|key| {
+        match descriptor.get(*key).map(|f| resolve(f, objects)) {
+            Some(PdfObj::Stream(s)) => Some((*key, s)),
+            _ => None,
+        }
+    })?;
+    let mut data = decode_stream(stream);
+    let mut cff = key == "FontFile3";
+    if matches!(stream.dict.get("Subtype"), Some(PdfObj::Name(n)) if n == "OpenType") {
+        match truetype::sfnt_table(&data, b"CFF ") {
+            Some(table) => data = table.to_vec(),
+            None => cff = false,
+        }
+    }
+    let differences = differences.into_iter().flatten();
+
+    let mut simple_ids = HashMap::new();
+    let (num_glyphs, program_cid_to_gid) = if cff {
+        let font = parse_cff(&data).ok()?;
+        let cid_to_gid = font.ros.as_ref().map(|_| {
+            let mut table = vec![0u16; font.cids.iter().max().map_or(0, |&c| c as usize + 1)];
+            for (gid, &cid) in font.cids.iter().enumerate() {
+                table[cid as usize] = gid as u16;
+            }
+            table
+        });
+        simple_ids.extend(font.encoding.iter().map(|(&code, &gid)| (code as u32, gid)));
+        for (&code, name) in differences {
+            if let Some(gid) = font
+                .glyph_names
+                .iter()
+                .position(|n| n.as_deref() == Some(name))
+            {
+                simple_ids.insert(code, gid as u16);
+            }
+        }
+        (font.num_glyphs, cid_to_gid)
+    } else {
+        if let Ok(font) = parse_truetype(&data) {
+            let unicode_cmap = font.cmaps.get(&(3, 1));
+            // Non-symbolic fonts are looked up by the Unicode value of each code.
+            let by_unicode = |text: Option<String>| {
+                let c = text?.chars().next()?;
+                unicode_cmap?.get(&(c as u32)).copied()
+            };
+            for code in 0..=255u8 {
+                let unicode = match encoding {
+                    Some("MacRomanEncoding") => mac_roman_to_unicode(code),
+                    _ => winansi_to_unicode(code),
+                };
+                if let Some(gid) = font
+                    .simple_code_to_gid(code)
+                    .or_else(|| by_unicode(Some(unicode.to_string())))
+                {
+                    simple_ids.insert(code as u32, gid);
+                }
+            }
+            for (&code, name) in differences {
+                let by_name = font
+                    .glyph_names
+                    .iter()
+                    .position(|n| n.as_deref() == Some(name));
+                if let Some(gid) = by_name
+                    .map(|gid| gid as u16)
+                    .or_else(|| by_unicode(glyph_to_unicode(name)))
+                {
+                    simple_ids.insert(code, gid);
+                }
+            }
+        }
+        (usize::from(truetype::glyph_count(&data)?), None)
+    };
+
+    let glyph_ids = match cid_font {
+        Some(cid_font) => {
+            cid_glyph_ids(font_dic, cid_font, objects, program_cid_to_gid, num_glyphs)
+                .ok()?
+                .into_iter()
+                .collect()
+        }
+        None => simple_ids,
+    };
+    Some(FontProgram {
+        data,
+        cff,
+        glyph_ids,
+    })
+}
+
+// Recovers a character code to Unicode map from the font program embedded in
+// the font descriptor, for use when ToUnicode is missing or incomplete.
+fn embedded_unicode_map(
+    font_dic: &HashMap<String, PdfObj>,
+    subtype: Option<&str>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    warnings: &mut Vec<String>,
+) -> Option<(HashMap<u32, String>, CharSource)> {
+    let cid_font = descendant_font(font_dic, subtype, objects);
+    let descriptor = font_descriptor(cid_font.unwrap_or(font_dic), objects)?;
+    let ordering = cid_ordering(font_dic, objects);
+    let glyphs = embedded_glyphs(descriptor, objects, ordering, warnings)?;
+    let gid_unicode = glyphs.gid_unicode;
+
+    let mut map = HashMap::new();
+    if let Some(cid_font) = cid_font {
+        let code_to_gid = match cid_glyph_ids(
+            font_dic,
+            cid_font,
+            objects,
+            glyphs.cid_to_gid,
+            glyphs.num_glyphs,
+        ) {
+            Ok(code_to_gid) => code_to_gid,
+            Err(Some(cmap)) => {
+                warnings.push(format!(
+                    "{}: predefined CMap {} is not available",
+                    glyphs.source, cmap
+                ));
+                return None;
+            }
+            Err(None) => return None,
+        };
+        for (code, gid) in code_to_gid {
+            if let Some(text) = gid_unicode.get(&gid) {
+                map.insert(code, text.clone());
+            }
+        }
+    } else {
+        for code in 0..=255u8 {
+            if let Some(text) = glyphs
+                .simple_codes
+                .get(&code)
+                .and_then(|gid| gid_unicode.get(gid))
+            {
+                map.insert(code as u32, text.clone());
+            }
+        }
+    }
+
+    if map.is_empty() {
+        None
+    } else {
+        Some((map, glyphs.unicode_source))
+    }
+}
+
+// The CIDFont of a Type0 font.
+fn descendant_font<'a>(
+    font_dic: &'a HashMap<String, PdfObj>,
+    subtype: Option<&str>,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+) -> Option<&'a HashMap<String, PdfObj>> {
+    if subtype != Some("Type0") {
+        return None;
+    }
+    match font_dic.get("DescendantFonts").map(|d| resolve(d, objects)) {
+        Some(PdfObj::Array(fonts)) => match fonts.first().map(|f| resolve(f, objects)) {
+            Some(PdfObj::Dictionary(d)) => Some(d),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// How a Type0 font's strings split into codes: the codespace of its embedded
+// encoding CMap, none for the two-byte Identity encodings, and the ToUnicode
+// map's for predefined CMaps, which are not available.
+fn type0_codespace(
+    font_dic: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    to_unicode_codespace: Vec<CodespaceRange>,
+) -> Vec<CodespaceRange> {
+    match font_dic.get("Encoding").map(|e| resolve(e, objects)) {
+        Some(PdfObj::Stream(s)) => parse_cmap(&decode_stream(s)).codespace_ranges,
+        Some(PdfObj::Name(n)) if n == "Identity-H" || n == "Identity-V" => Vec::new(),
+        _ => to_unicode_codespace,
+    }
+}
+
+// The `/Ordering` of a Type0 font's CIDFont, such as `Japan1`.
+fn cid_ordering(
+    font_dic: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Option<String> {
+    let subtype = match font_dic.get("Subtype") {
+        Some(PdfObj::Name(n)) => Some(n.as_str()),
+        _ => None,
+    };
+    let info = descendant_font(font_dic, subtype, objects)?.get("CIDSystemInfo")?;
+    match resolve(info, objects) {
+        PdfObj::Dictionary(d) => match d.get("Ordering").map(|o| resolve(o, objects)) {
+            Some(PdfObj::String(s)) => Some(String::from_utf8_lossy(s).into_owned()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn font_descriptor<'a>(
+    font_dic: &'a HashMap<String, PdfObj>,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+) -> Option<&'a HashMap<String, PdfObj>> {
+    match font_dic.get("FontDescriptor").map(|d| resolve(d, objects)) {
+        Some(PdfObj::Dictionary(d)) => Some(d),
+        _ => None,
+    }
+}
+
+// Maps the character codes of a Type0 font to glyph IDs through its encoding
+// CMap and the CIDFont's `CIDToGIDMap`, or `program_cid_to_gid` from a CFF
+// charset. Fails with the name of a predefined CMap, which is not available.
+fn cid_glyph_ids(
+    font_dic: &HashMap<String, PdfObj>,
+    cid_font: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    program_cid_to_gid: Option<Vec<u16>>,
+    num_glyphs: usize,
+) -> Result<Vec<(u32, u16)>, Option<String>> {
+    let cid_to_gid: Option<Vec<u16>> =
+        match cid_font.get("CIDToGIDMap").map(|m| resolve(m, objects)) {
+            Some(PdfObj::Stream(s)) => Some(
+                decode_stream(s)
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect(),
+            ),
+            _ => program_cid_to_gid,
+        };
+    let cid_count = match &cid_to_gid {
+        Some(table) => table.len() as u32,
+        None => num_glyphs as u32,
+    };
+    let code_to_cid: Vec<(u32, u32)> = match font_dic.get("Encoding").map(|e| resolve(e, objects)) {
+        Some(PdfObj::Name(n)) if n == "Identity-H" || n == "Identity-V" => {
+            (0..cid_count).map(|c| (c, c)).collect()
+        }
+        Some(PdfObj::Stream(s)) => {
+            let encoding = parse_cmap(&decode_stream(s));
+            // Ranges whose CIDs would run past u32::MAX are skipped.
+            encoding
+                .cid_ranges
+                .iter()
+                .filter(|r| r.high < r.low || r.cid.checked_add(r.high - r.low).is_some())
+                .flat_map(|r| (r.low..=r.high).map(move |code| (code, r.cid + (code - r.low))))
+                .collect()
+        }
+        Some(PdfObj::Name(n)) => return Err(Some(n.clone())),
+        _ => return Err(None),
+    };
+    Ok(code_to_cid
+        .into_iter()
+        .filter_map(|(code, cid)| {
+            let gid = match &cid_to_gid {
+                Some(table) => *table.get(cid as usize)?,
+                None => u16::try_from(cid).ok()?,
+            };
+            Some((code, gid))
+        })
+        .collect())
+}
+
+fn number(obj: &PdfObj, objects: &HashMap<(u32, u16), PdfObj>) -> Option<f64> {
+    match resolve(obj, objects) {
+        PdfObj::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+// Glyph-space widths from `/FirstChar` and `/Widths`, or for Type0 fonts from
+// the descendant's `/W` and `/DW`. CID widths are only keyed by character code
+// under the Identity encodings, where the two coincide.
+fn font_widths(
+    font_dic: &HashMap<String, PdfObj>,
+    subtype: Option<&str>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> (HashMap<u32, f64>, Option<f64>) {
+    let mut widths = HashMap::new();
+    if subtype != Some("Type0") {
+        let first_char = font_dic
+            .get("FirstChar")
+            .and_then(|f| number(f, objects))
+            .unwrap_or(0.0) as u32;
+        if let Some(PdfObj::Array(values)) = font_dic.get("Widths").map(|w| resolve(w, objects)) {
+            for (offset, value) in values.iter().enumerate() {
+                if let Some(width) = number(value, objects) {
+                    widths.insert(first_char + offset as u32, width);
+                }
+            }
+        }
+        let missing_width = match font_dic.get("FontDescriptor").map(|d| resolve(d, objects)) {
+            Some(PdfObj::Dictionary(d)) => d.get("MissingWidth").and_then(|w| number(w, objects)),
+            _ => None,
+        };
+        return (widths, missing_width);
+    }
+
+    let identity = matches!(
+        font_dic.get("Encoding").map(|e| resolve(e, objects)),
+        Some(PdfObj::Name(n)) if n == "Identity-H" || n == "Identity-V"
+    );
+    let cid_font = match font_dic.get("DescendantFonts").map(|d| resolve(d, objects)) {
+        Some(PdfObj::Array(fonts)) => match fonts.first().map(|f| resolve(f, objects)) {
+            Some(PdfObj::Dictionary(d)) => d,
+            _ => return (widths, None),
+        },
+        _ => return (widths, None),
+    };
+    if !identity {
+        return (widths, None);
+    }
+    if let Some(PdfObj::Array(entries)) = cid_font.get("W").map(|w| resolve(w, objects)) {
+        let mut i = 0;
+        while i < entries.len() {
+            let first = number(&entries[i], objects).unwrap_or(0.0) as u32;
+            match entries.get(i + 1).map(|e| resolve(e, objects)) {
+                // c [w1 w2 ...]
+                Some(PdfObj::Array(list)) => {
+                    for (offset, value) in list.iter().enumerate() {
+                        if let Some(width) = number(value, objects) {
+                            widths.insert(first + offset as u32, width);
+                        }
+                    }
+                    i += 2;
+                }
+                // c_first c_last w
+                Some(last) => {
+                    let last = match last {
+                        PdfObj::Number(n) => *n as u32,
+                        _ => break,
+                    };
+                    let width = entries.get(i + 2).and_then(|w| number(w, objects));
+                    if let Some(width) = width {
+                        for cid in first..=last.min(first.saturating_add(0xFFFF)) {
+                            widths.insert(cid, width);
+                        }
+                    }
+                    i += 3;
+                }
+                None => break,
+            }
+        }
+    }
+    let default_width = cid_font
+        .get("DW")
+        .and_then(|w| number(w, objects))
+        .unwrap_or(1000.0);
+    (widths, Some(default_width))
+}
+
+// Reads the encoding built into an embedded Type1 program (`FontFile`).
+fn type1_builtin_encoding(
+    font_dic: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    warnings: &mut Vec<String>,
+) -> Option<HashMap<u32, String>> {
+    let descriptor = match font_dic.get("FontDescriptor").map(|d| resolve(d, objects)) {
+        Some(PdfObj::Dictionary(d)) => d,
+        _ => return None,
+    };
+    let stream = match descriptor.get("FontFile").map(|f| resolve(f, objects)) {
+        Some(PdfObj::Stream(s)) => s,
+        _ => return None,
+    };
+    let length1 = match stream.dict.get("Length1").map(|l| resolve(l, objects)) {
+        Some(PdfObj::Number(n)) => Some(*n as usize),
+        _ => None,
+    };
+    match parse_type1_encoding(&decode_stream(stream), length1) {
+        Ok(encoding) => Some(encoding.into_iter().map(|(c, g)| (c as u32, g)).collect()),
+        Err(e) => {
+            warnings.push(format!("FontFile: {}", e));
+            None
+        }
+    }
+}
+
+// Glyph-level Unicode recovered from one embedded font program.
+struct EmbeddedGlyphs {
+    source: &'static str,
+    num_glyphs: usize,
+    gid_unicode: HashMap<u16, String>,
+    // Where the text in `gid_unicode` comes from.
+    unicode_source: CharSource,
+    // Character code to glyph ID, as the font program would map it for a simple font.
+    simple_codes: HashMap<u8, u16>,
+    // CID to glyph ID for CID-keyed CFF programs, inverted from their charset.
+    cid_to_gid: Option<Vec<u16>>,
+}
+
+fn embedded_glyphs(
+    descriptor: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    cid_ordering: Option<String>,
+    warnings: &mut Vec<String>,
+) -> Option<EmbeddedGlyphs> {
+    let (source, stream) = ["FontFile2", "FontFile3"].iter().find_map(|key| {
+        match descriptor.get(*key).map(|f| resolve(f, objects)) {
+            Some(PdfObj::Stream(s)) => Some((*key, s)),
+            _ => None,
+        }
+    })?;
+    let program = decode_stream(stream);
+    let is_cff = source == "FontFile3"
+        && !matches!(stream.dict.get("Subtype"), Some(PdfObj::Name(n)) if n == "OpenType");
+
+    if !is_cff {
+        let font = match parse_truetype(&program) {
+            Ok(font) => font,
+            Err(e) => {
+                warnings.push(format!("{}: {}", source, e));
+                return None;
+            }
+        };
+        return Some(EmbeddedGlyphs {
+            source,
+            num_glyphs: font.num_glyphs as usize,
+            gid_unicode: font.gid_to_unicode(),
+            unicode_source: CharSource::EmbeddedCmap,
+            simple_codes: (0..=255u8)
+                .filter_map(|code| font.simple_code_to_gid(code).map(|gid| (code, gid)))
+                .collect(),
+            cid_to_gid: None,
+        });
+    }
+
+    let font = match parse_cff(&program) {
+        Ok(font) => font,
+        Err(e) => {
+            warnings.push(format!("{}: {}", source, e));
+            return None;
+        }
+    };
+    let mut gid_unicode = HashMap::new();
+    let mut cid_to_gid = None;
+    let unicode_source = if font.ros.is_some() {
+        CharSource::PredefinedCMap
+    } else {
+        CharSource::GlyphName
+    };
+    if let Some((_, ros_ordering, _)) = &font.ros {
+        let ordering = cid_ordering.unwrap_or_else(|| ros_ordering.clone());
+        let mut table = vec![0u16; font.cids.iter().max().map_or(0, |&c| c as usize + 1)];
+        for (gid, &cid) in font.cids.iter().enumerate() {
+            table[cid as usize] = gid as u16;
+            if let Some(c) = cid_to_unicode(&ordering, cid as u32) {
+                gid_unicode.insert(gid as u16, c.to_string());
+            }
+        }
+        if gid_unicode.is_empty() {
+            warnings.push(format!(
+                "{}: no Unicode values known for ordering {}",
+                source, ordering
+            ));
+        }
+        cid_to_gid = Some(table);
+    } else {
+        for (gid, name) in font.glyph_names.iter().enumerate() {
+            if let Some(text) = name.as_deref().and_then(glyph_to_unicode) {
+                gid_unicode.insert(gid as u16, text);
+            }
+        }
+    }
+    Some(EmbeddedGlyphs {
+        source,
+        num_glyphs: font.num_glyphs,
+        gid_unicode,
+        unicode_source,
+        simple_codes: font.encoding,
+        cid_to_gid,
+    })
+}
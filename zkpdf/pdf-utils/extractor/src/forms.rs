@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::encoding::decode_text_string;
+use crate::types::{FieldKind, FieldValue, FormField, PdfDocument, PdfObj};
+use crate::{decode_stream, resolve};
+
+// Field flags (`/Ff`) that tell button kinds apart.
+const RADIO: u32 = 1 << 15;
+const PUSHBUTTON: u32 = 1 << 16;
+
+// Attributes a field inherits from its ancestors.
+#[derive(Clone, Copy, Default)]
+struct Inherited<'a> {
+    field_type: Option<&'a str>,
+    flags: u32,
+    value: Option<&'a PdfObj>,
+}
+
+/// The terminal fields of the document's `/AcroForm`, in tree order.
+pub fn form_fields(document: &PdfDocument) -> Vec<FormField> {
+    let objects = &document.objects;
+    let Some(PdfObj::Dictionary(form)) = document
+        .catalog()
+        .and_then(|catalog| catalog.get("AcroForm"))
+        .map(|form| resolve(form, objects))
+    else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    let mut visited = HashSet::new();
+    if let Some(PdfObj::Array(roots)) = form.get("Fields").map(|f| resolve(f, objects)) {
+        for root in roots {
+            collect_fields(
+                root,
+                "",
+                Inherited::default(),
+                objects,
+                &mut visited,
+                &mut fields,
+            );
+        }
+    }
+    fields
+}
+
+fn collect_fields<'a>(
+    node: &'a PdfObj,
+    parent_name: &str,
+    inherited: Inherited<'a>,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+    visited: &mut HashSet<(u32, u16)>,
+    fields: &mut Vec<FormField>,
+) {
+    if let PdfObj::Reference(id) = node {
+        if !visited.insert(*id) {
+            return;
+        }
+    }
+    let PdfObj::Dictionary(field) = resolve(node, objects) else {
+        return;
+    };
+    let name = match field.get("T").map(|t| resolve(t, objects)) {
+        Some(PdfObj::String(partial)) if parent_name.is_empty() => decode_text_string(partial),
+        Some(PdfObj::String(partial)) => {
+            format!("{}.{}", parent_name, decode_text_string(partial))
+        }
+        _ => parent_name.to_string(),
+    };
+    let inherited = Inherited {
+        field_type: match field.get("FT") {
+            Some(PdfObj::Name(field_type)) => Some(field_type.as_str()),
+            _ => inherited.field_type,
+        },
+        flags: match field.get("Ff").map(|f| resolve(f, objects)) {
+            Some(PdfObj::Number(flags)) => *flags as u32,
+            _ => inherited.flags,
+        },
+        value: field.get("V").or(inherited.value),
+    };
+    // Kids without a partial name are the field's widget annotations.
+    let kids: Vec<&PdfObj> = match field.get("Kids").map(|k| resolve(k, objects)) {
+        Some(PdfObj::Array(kids)) => kids
+            .iter()
+            .filter(|kid| match resolve(kid, objects) {
+                PdfObj::Dictionary(kid) => kid.contains_key("T"),
+                _ => false,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if !kids.is_empty() {
+        for kid in kids {
+            collect_fields(kid, &name, inherited, objects, visited, fields);
+        }
+        return;
+    }
+    let kind = match inherited.field_type {
+        Some("Tx") => FieldKind::Text,
+        Some("Btn") if inherited.flags & PUSHBUTTON != 0 => FieldKind::PushButton,
+        Some("Btn") if inherited.flags & RADIO != 0 => FieldKind::RadioButton,
+        Some("Btn") => FieldKind::Checkbox,
+        Some("Ch") => FieldKind::Choice,
+        Some("Sig") => FieldKind::Signature,
+        _ => FieldKind::Unknown,
+    };
+    let alternate_name = match field.get("TU").map(|t| resolve(t, objects)) {
+        Some(PdfObj::String(label)) => Some(decode_text_string(label)),
+        _ => None,
+    };
+    fields.push(FormField {
+        name,
+        alternate_name,
+        kind,
+        value: inherited
+            .value
+            .map_or(FieldValue::Empty, |v| field_value(kind, v, objects)),
+    });
+}
+
+fn field_value(
+    kind: FieldKind,
+    value: &PdfObj,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> FieldValue {
+    let text = |obj: &PdfObj| match resolve(obj, objects) {
+        PdfObj::String(bytes) => Some(decode_text_string(bytes)),
+        // Long text values may be given as a text stream.
+        PdfObj::Stream(stream) => Some(decode_text_string(&decode_stream(stream))),
+        PdfObj::Name(name) => Some(name.clone()),
+        _ => None,
+    };
+    match (kind, resolve(value, objects)) {
+        (FieldKind::Signature, PdfObj::Dictionary(signature)) => FieldValue::Signature {
+            signer: signature.get("Name").and_then(text),
+        },
+        (FieldKind::Checkbox | FieldKind::RadioButton, PdfObj::Name(state)) => {
+            FieldValue::State(state.clone())
+        }
+        (FieldKind::Choice, PdfObj::Array(selected)) => {
+            FieldValue::Choices(selected.iter().filter_map(text).collect())
+        }
+        (FieldKind::Choice, _) => text(value).map_or(FieldValue::Empty, |choice| {
+            FieldValue::Choices(vec![choice])
+        }),
+        _ => text(value).map_or(FieldValue::Empty, FieldValue::Text),
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::resolve;
+use crate::types::{HiddenReason, PdfObj};
+
+pub const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// Glyphs rendered smaller than this, in default user space units (points),
+// cannot be read.
+const MIN_VISIBLE_SIZE: f64 = 0.5;
+// Largest per-channel difference at which two colours are taken as the same.
+const COLOR_TOLERANCE: f64 = 0.03;
+const WHITE: [f64; 3] = [1.0, 1.0, 1.0];
+// Constant alpha at or below which paint cannot be seen.
+const MIN_VISIBLE_ALPHA: f64 = 0.05;
+
+/// Colour spaces whose values can be compared; everything else (patterns,
+/// separations, indexed spaces) is `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Unknown,
+}
+
+impl ColorSpace {
+    /// Resolves a colour space operand of `cs`/`CS`, looking names up in the
+    /// `/ColorSpace` resources when they aren't device spaces.
+    pub fn from_name(
+        name: &str,
+        resources: &HashMap<String, PdfObj>,
+        objects: &HashMap<(u32, u16), PdfObj>,
+    ) -> ColorSpace {
+        match name {
+            "DeviceGray" | "G" | "CalGray" => return ColorSpace::Gray,
+            "DeviceRGB" | "RGB" | "CalRGB" => return ColorSpace::Rgb,
+            "DeviceCMYK" | "CMYK" => return ColorSpace::Cmyk,
+            _ => {}
+        }
+        let space = match resources.get("ColorSpace").map(|c| resolve(c, objects)) {
+            Some(PdfObj::Dictionary(spaces)) => spaces.get(name).map(|s| resolve(s, objects)),
+            _ => None,
+        };
+        match space {
+            Some(PdfObj::Name(n)) if n != name => ColorSpace::from_name(n, resources, objects),
+            Some(PdfObj::Array(parts)) => match parts.first().map(|p| resolve(p, objects)) {
+                Some(PdfObj::Name(family)) if family == "ICCBased" => {
+                    let components = match parts.get(1).map(|p| resolve(p, objects)) {
+                        Some(PdfObj::Stream(profile)) => {
+                            profile.dict.get("N").map(|n| resolve(n, objects))
+                        }
+                        _ => None,
+                    };
+                    match components {
+                        Some(PdfObj::Number(n)) if *n == 1.0 => ColorSpace::Gray,
+                        Some(PdfObj::Number(n)) if *n == 3.0 => ColorSpace::Rgb,
+                        Some(PdfObj::Number(n)) if *n == 4.0 => ColorSpace::Cmyk,
+                        _ => ColorSpace::Unknown,
+                    }
+                }
+                Some(PdfObj::Name(family)) if family == "CalGray" => ColorSpace::Gray,
+                Some(PdfObj::Name(family)) if family == "CalRGB" => ColorSpace::Rgb,
+                _ => ColorSpace::Unknown,
+            },
+            _ => ColorSpace::Unknown,
+        }
+    }
+
+    /// Converts colour components in this space to RGB.
+    pub fn to_rgb(self, components: &[f64]) -> Option<[f64; 3]> {
+        match (self, components) {
+            (ColorSpace::Gray, [g]) => Some([*g, *g, *g]),
+            (ColorSpace::Rgb, [r, g, b]) => Some([*r, *g, *b]),
+            (ColorSpace::Cmyk, [c, m, y, k]) => Some([
+                (1.0 - c) * (1.0 - k),
+                (1.0 - m) * (1.0 - k),
+                (1.0 - y) * (1.0 - k),
+            ]),
+            _ => None,
+        }
+    }
+
+    /// The colour selected along with the space by `cs`/`CS`: black for the
+    /// device spaces.
+    pub fn initial_color(self) -> Option<[f64; 3]> {
+        match self {
+            ColorSpace::Unknown => None,
+            _ => Some([0.0, 0.0, 0.0]),
+        }
+    }
+}
+
+/// The parts of the graphics state that decide whether painted text shows.
+#[derive(Debug, Clone)]
+pub struct GraphicsState {
+    pub ctm: [f64; 6],
+    pub fill_space: ColorSpace,
+    /// Fill colour as RGB, `None` when its colour space isn't understood.
+    pub fill: Option<[f64; 3]>,
+    pub stroke_space: ColorSpace,
+    pub stroke: Option<[f64; 3]>,
+    /// Text rendering mode set by `Tr`.
+    pub render_mode: u8,
+    /// Line width set by `w`, in user space units.
+    pub line_width: f64,
+    /// Bounding box of the clipping path in default user space.
+    pub clip: Option<[f64; 4]>,
+    /// Constant alpha for filling (`/ca`) and stroking (`/CA`), set by `gs`.
+    pub fill_alpha: f64,
+    pub stroke_alpha: f64,
+    /// Whether an ExtGState set a soft mask other than `/None`.
+    pub soft_mask: bool,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            ctm: IDENTITY,
+            fill_space: ColorSpace::Gray,
+            fill: Some([0.0, 0.0, 0.0]),
+            stroke_space: ColorSpace::Gray,
+            stroke: Some([0.0, 0.0, 0.0]),
+            render_mode: 0,
+            line_width: 1.0,
+            clip: None,
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            soft_mask: false,
+        }
+    }
+}
+
+impl GraphicsState {
+    /// `cm`: prepends `matrix` to the current transformation.
+    pub fn concat(&mut self, matrix: &[f64; 6]) {
+        self.ctm = multiply(matrix, &self.ctm);
+    }
+
+    /// `gs`: applies the transparency entries of the named ExtGState
+    /// resource.
+    pub fn apply_ext_gstate(
+        &mut self,
+        name: &str,
+        resources: &HashMap<String, PdfObj>,
+        objects: &HashMap<(u32, u16), PdfObj>,
+    ) {
+        let state = match resources.get("ExtGState").map(|s| resolve(s, objects)) {
+            Some(PdfObj::Dictionary(states)) => states.get(name).map(|s| resolve(s, objects)),
+            _ => None,
+        };
+        let Some(PdfObj::Dictionary(state)) = state else {
+            return;
+        };
+        let number = |key: &str| match state.get(key).map(|v| resolve(v, objects)) {
+            Some(PdfObj::Number(n)) => Some(*n),
+            _ => None,
+        };
+        if let Some(alpha) = number("ca") {
+            self.fill_alpha = alpha;
+        }
+        if let Some(alpha) = number("CA") {
+            self.stroke_alpha = alpha;
+        }
+        match state.get("SMask").map(|m| resolve(m, objects)) {
+            Some(PdfObj::Name(mask)) if mask == "None" => self.soft_mask = false,
+            Some(_) => self.soft_mask = true,
+            None => {}
+        }
+    }
+
+    /// Narrows the clipping region to `bbox`, given in default user space.
+    pub fn clip_to(&mut self, bbox: [f64; 4]) {
+        self.clip = Some(match self.clip {
+            Some(clip) => intersect(&clip, &bbox),
+            None => bbox,
+        });
+    }
+}
+
+/// Areas painted so far on the page, used to find what lies beneath a glyph.
+/// Only bounding boxes are kept.
+#[derive(Debug, Default)]
+pub struct Canvas {
+    regions: Vec<([f64; 4], Option<[f64; 3]>)>,
+}
+
+impl Canvas {
+    /// Records a fill or image over `bbox`; `color` is `None` when the paint
+    /// isn't a single known colour.
+    pub fn paint(&mut self, bbox: [f64; 4], color: Option<[f64; 3]>) {
+        self.regions.push((bbox, color));
+    }
+
+    /// Colour of the topmost paint at `point`, white paper when nothing was
+    /// painted there, or `None` when it isn't a single known colour.
+    pub fn background_at(&self, point: (f64, f64)) -> Option<[f64; 3]> {
+        self.regions
+            .iter()
+            .rev()
+            .find(|(bbox, _)| contains(bbox, point))
+            .map_or(Some(WHITE), |(_, color)| *color)
+    }
+}
+
+/// One piece of a path or glyph outline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo((f64, f64)),
+    LineTo((f64, f64)),
+    /// A quadratic Bézier curve through a control point.
+    QuadTo((f64, f64), (f64, f64)),
+    /// A cubic Bézier curve through two control points.
+    CubicTo((f64, f64), (f64, f64), (f64, f64)),
+    /// Closes the current subpath.
+    Close,
+}
+
+impl PathSegment {
+    /// The segment with every point transformed by `m`.
+    pub fn transformed(&self, m: &[f64; 6]) -> PathSegment {
+        match *self {
+            PathSegment::MoveTo(p) => PathSegment::MoveTo(transform(m, p)),
+            PathSegment::LineTo(p) => PathSegment::LineTo(transform(m, p)),
+            PathSegment::QuadTo(c, p) => PathSegment::QuadTo(transform(m, c), transform(m, p)),
+            PathSegment::CubicTo(c1, c2, p) => {
+                PathSegment::CubicTo(transform(m, c1), transform(m, c2), transform(m, p))
+            }
+            PathSegment::Close => PathSegment::Close,
+        }
+    }
+}
+
+/// Bounding box of the path under construction, in default user space.
+#[derive(Debug, Default)]
+pub struct PathBounds {
+    bbox: Option<[f64; 4]>,
+    /// Set by `W`/`W*`: the path clips when it is next painted or ended.
+    pub clip_pending: bool,
+}
+
+impl PathBounds {
+    pub fn add_point(&mut self, (x, y): (f64, f64)) {
+        self.bbox = Some(match self.bbox {
+            Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+            None => [x, y, x, y],
+        });
+    }
+
+    /// Ends the path, returning its bounding box.
+    pub fn take(&mut self) -> Option<[f64; 4]> {
+        self.clip_pending = false;
+        self.bbox.take()
+    }
+}
+
+/// Reasons a glyph would not be seen, judged at sample `points` across it
+/// in default user space. A reason holds when it holds at any of the points,
+/// so a glyph that is partly covered or clipped is not taken as seen; paint
+/// whose colour isn't understood is not taken as seen either. `size` is the
+/// glyph's rendered size in the same units.
+pub fn hidden_reasons(
+    graphics: &GraphicsState,
+    canvas: &Canvas,
+    crop_box: Option<[f64; 4]>,
+    points: &[(f64, f64)],
+    size: f64,
+) -> Vec<HiddenReason> {
+    let mut reasons = Vec::new();
+    let mode = graphics.render_mode;
+    let fills = matches!(mode, 0 | 2 | 4 | 6);
+    let strokes = matches!(mode, 1 | 2 | 5 | 6);
+    // The colours of the paints the mode uses that aren't transparent.
+    let paints: Vec<Option<[f64; 3]>> = [
+        (fills, graphics.fill, graphics.fill_alpha),
+        (strokes, graphics.stroke, graphics.stroke_alpha),
+    ]
+    .into_iter()
+    .filter(|&(used, _, alpha)| used && alpha > MIN_VISIBLE_ALPHA)
+    .map(|(_, color, _)| color)
+    .collect();
+    if !fills && !strokes {
+        reasons.push(HiddenReason::InvisibleRenderMode);
+    } else if paints.is_empty() {
+        reasons.push(HiddenReason::Transparent);
+    } else if paints.contains(&None) {
+        reasons.push(HiddenReason::UnknownPaint);
+    } else {
+        let blends = |background: [f64; 3]| {
+            paints.iter().flatten().all(|color| {
+                color
+                    .iter()
+                    .zip(background)
+                    .all(|(a, b)| (a - b).abs() <= COLOR_TOLERANCE)
+            })
+        };
+        let backgrounds: Vec<Option<[f64; 3]>> = points
+            .iter()
+            .map(|&point| canvas.background_at(point))
+            .collect();
+        if backgrounds
+            .iter()
+            .flatten()
+            .any(|&background| blends(background))
+        {
+            reasons.push(HiddenReason::SameColorAsBackground);
+        } else if backgrounds.contains(&None) {
+            reasons.push(HiddenReason::UnknownBackground);
+        }
+    }
+    if graphics.soft_mask {
+        reasons.push(HiddenReason::SoftMasked);
+    }
+    if crop_box.is_some_and(|crop| points.iter().any(|&p| !contains(&crop, p))) {
+        reasons.push(HiddenReason::OutsideCropBox);
+    }
+    if graphics
+        .clip
+        .is_some_and(|clip| points.iter().any(|&p| !contains(&clip, p)))
+    {
+        reasons.push(HiddenReason::Clipped);
+    }
+    if size < MIN_VISIBLE_SIZE {
+        reasons.push(HiddenReason::ZeroScale);
+    }
+    reasons
+}
+
+/// `a` followed by `b`, in the row-vector convention PDF matrices use.
+pub fn multiply(a: &[f64; 6], b: &[f64; 6]) -> [f64; 6] {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+pub fn transform(m: &[f64; 6], (x, y): (f64, f64)) -> (f64, f64) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+/// Maps default user space onto the displayed page: the crop box turned
+/// clockwise by `rotate` degrees, with its lower-left corner at the origin.
+pub fn display_matrix(crop_box: &[f64; 4], rotate: u16) -> [f64; 6] {
+    let [x0, y0, x1, y1] = *crop_box;
+    match rotate {
+        90 => [0.0, -1.0, 1.0, 0.0, -y0, x1],
+        180 => [-1.0, 0.0, 0.0, -1.0, x1, y1],
+        270 => [0.0, 1.0, -1.0, 0.0, y1, -x0],
+        _ => [1.0, 0.0, 0.0, 1.0, -x0, -y0],
+    }
+}
+
+/// Bounding box of a rectangle `[x0 y0 x1 y1]` after transformation by `m`.
+pub fn transform_rect(m: &[f64; 6], rect: &[f64; 4]) -> [f64; 4] {
+    let mut bounds = PathBounds::default();
+    for corner in [
+        (rect[0], rect[1]),
+        (rect[2], rect[1]),
+        (rect[0], rect[3]),
+        (rect[2], rect[3]),
+    ] {
+        bounds.add_point(transform(m, corner));
+    }
+    bounds.take().unwrap_or(*rect)
+}
+
+pub fn intersect(a: &[f64; 4], b: &[f64; 4]) -> [f64; 4] {
+    let x0 = a[0].max(b[0]);
+    let y0 = a[1].max(b[1]);
+    // An empty intersection collapses to a zero-size box that contains nothing.
+    [x0, y0, a[2].min(b[2]).max(x0), a[3].min(b[3]).max(y0)]
+}
+
+fn contains(rect: &[f64; 4], (x, y): (f64, f64)) -> bool {
+    let (x0, x1) = (rect[0].min(rect[2]), rect[0].max(rect[2]));
+    let (y0, y1) = (rect[1].min(rect[3]), rect[1].max(rect[3]));
+    x >= x0 && x <= x1 && y >= y0 && y <= y1
+}
@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+
+use crate::graphics::transform_rect;
+use crate::jpeg;
+use crate::resolve;
+use crate::types::{ImageExport, PageImage, PdfObj, PdfStream, Token};
+
+// Colour spaces given by name inside other colour spaces are followed this
+// deep.
+const MAX_COLOR_SPACE_DEPTH: usize = 8;
+
+/// Describes an image XObject drawn through `matrix`, which maps the unit
+/// square onto the displayed page.
+pub fn xobject_image(
+    name: &str,
+    stream: &PdfStream,
+    matrix: [f64; 6],
+    resources: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> PageImage {
+    describe(
+        Some(name.to_string()),
+        &stream.dict,
+        stream.data.clone(),
+        matrix,
+        resources,
+        objects,
+    )
+}
+
+/// Describes an inline image (`BI ... ID ... EI`) drawn through `matrix`,
+/// expanding the abbreviated keys and names of its parameters.
+pub fn inline_image(
+    params: &[Token],
+    data: &[u8],
+    matrix: [f64; 6],
+    resources: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> PageImage {
+    let mut dict = HashMap::new();
+    for pair in params.chunks_exact(2) {
+        if let Token::Name(key) = &pair[0] {
+            dict.insert(expand_key(key).to_string(), inline_value(&pair[1]));
+        }
+    }
+    describe(None, &dict, data.to_vec(), matrix, resources, objects)
+}
+
+fn expand_key(key: &str) -> &str {
+    match key {
+        "BPC" => "BitsPerComponent",
+        "CS" => "ColorSpace",
+        "D" => "Decode",
+        "DP" => "DecodeParms",
+        "F" => "Filter",
+        "H" => "Height",
+        "IM" => "ImageMask",
+        "I" => "Interpolate",
+        "W" => "Width",
+        other => other,
+    }
+}
+
+fn inline_value(token: &Token) -> PdfObj {
+    match token {
+        Token::Number(n) => PdfObj::Number(f64::from(*n)),
+        Token::String(bytes) => PdfObj::String(bytes.clone()),
+        Token::Name(name) => PdfObj::Name(expand_name(name).to_string()),
+        Token::Array(items) => PdfObj::Array(items.iter().map(inline_value).collect()),
+        Token::Operator(op) if op == "true" => PdfObj::Boolean(true),
+        Token::Operator(op) if op == "false" => PdfObj::Boolean(false),
+        _ => PdfObj::Null,
+    }
+}
+
+// Abbreviated filter and colour space names of inline images.
+fn expand_name(name: &str) -> &str {
+    match name {
+        "AHx" => "ASCIIHexDecode",
+        "A85" => "ASCII85Decode",
+        "LZW" => "LZWDecode",
+        "Fl" => "FlateDecode",
+        "RL" => "RunLengthDecode",
+        "CCF" => "CCITTFaxDecode",
+        "DCT" => "DCTDecode",
+        "G" => "DeviceGray",
+        "RGB" => "DeviceRGB",
+        "CMYK" => "DeviceCMYK",
+        "I" => "Indexed",
+        other => other,
+    }
+}
+
+fn describe(
+    name: Option<String>,
+    dict: &HashMap<String, PdfObj>,
+    data: Vec<u8>,
+    matrix: [f64; 6],
+    resources: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> PageImage {
+    let number = |key: &str| match dict.get(key).map(|v| resolve(v, objects)) {
+        Some(PdfObj::Number(n)) if *n >= 0.0 => Some(*n as u32),
+        _ => None,
+    };
+    let image_mask = matches!(
+        dict.get("ImageMask").map(|m| resolve(m, objects)),
+        Some(PdfObj::Boolean(true))
+    );
+    let (color_space, components, palette) = if image_mask {
+        (None, 1, None)
+    } else {
+        match dict.get("ColorSpace") {
+            Some(space) => color_space(space, resources, objects, 0),
+            None => (None, 1, None),
+        }
+    };
+    let filters: Vec<String> = match dict.get("Filter").map(|f| resolve(f, objects)) {
+        Some(PdfObj::Name(filter)) => vec![filter.clone()],
+        Some(PdfObj::Array(filters)) => filters
+            .iter()
+            .filter_map(|f| match resolve(f, objects) {
+                PdfObj::Name(filter) => Some(filter.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let parms = |obj: &PdfObj| match resolve(obj, objects) {
+        PdfObj::Dictionary(parms) => parms
+            .iter()
+            .map(|(key, value)| (key.clone(), resolve(value, objects).clone()))
+            .collect(),
+        _ => HashMap::new(),
+    };
+    let mut decode_parms: Vec<HashMap<String, PdfObj>> =
+        match dict.get("DecodeParms").map(|p| resolve(p, objects)) {
+            Some(PdfObj::Array(items)) => items.iter().map(parms).collect(),
+            Some(single) => vec![parms(single)],
+            None => Vec::new(),
+        };
+    decode_parms.resize(filters.len(), HashMap::new());
+    PageImage {
+        name,
+        rect: transform_rect(&matrix, &[0.0, 0.0, 1.0, 1.0]),
+        matrix,
+        width: number("Width").unwrap_or(0),
+        height: number("Height").unwrap_or(0),
+        color_space,
+        components,
+        bits_per_component: if image_mask {
+            1
+        } else {
+            number("BitsPerComponent").unwrap_or(8) as u8
+        },
+        image_mask,
+        filters: filters.iter().map(|f| expand_name(f).to_string()).collect(),
+        decode_parms,
+        palette,
+        data,
+    }
+}
+
+// The colour space family, its components per sample and, for Indexed
+// spaces, the lookup table converted to RGB.
+fn color_space(
+    space: &PdfObj,
+    resources: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    depth: usize,
+) -> (Option<String>, u8, Option<Vec<u8>>) {
+    if depth > MAX_COLOR_SPACE_DEPTH {
+        return (None, 1, None);
+    }
+    let (family, operands): (&str, &[PdfObj]) = match resolve(space, objects) {
+        PdfObj::Name(name) => (expand_name(name), &[]),
+        PdfObj::Array(items) => match items.first() {
+            Some(PdfObj::Name(name)) => (expand_name(name), &items[1..]),
+            _ => return (None, 1, None),
+        },
+        _ => return (None, 1, None),
+    };
+    let components = match family {
+        "DeviceGray" | "CalGray" | "Separation" | "Indexed" => 1,
+        "DeviceRGB" | "CalRGB" | "Lab" => 3,
+        "DeviceCMYK" => 4,
+        "ICCBased" => match operands.first().map(|s| resolve(s, objects)) {
+            Some(PdfObj::Stream(profile)) => match profile.dict.get("N") {
+                Some(PdfObj::Number(n)) => *n as u8,
+                _ => 3,
+            },
+            _ => 3,
+        },
+        "DeviceN" => match operands.first().map(|n| resolve(n, objects)) {
+            Some(PdfObj::Array(names)) => names.len() as u8,
+            _ => 1,
+        },
+        _ => {
+            // A colour space named in the resources.
+            let named = match resources.get("ColorSpace").map(|c| resolve(c, objects)) {
+                Some(PdfObj::Dictionary(spaces)) => spaces.get(family),
+                _ => None,
+            };
+            return match named {
+                Some(named) => color_space(named, resources, objects, depth + 1),
+                None => (Some(family.to_string()), 1, None),
+            };
+        }
+    };
+    let palette = if family == "Indexed" {
+        indexed_palette(operands, resources, objects, depth)
+    } else {
+        None
+    };
+    (Some(family.to_string()), components, palette)
+}
+
+// `[/Indexed base hival lookup]`: the lookup table as RGB triples.
+fn indexed_palette(
+    operands: &[PdfObj],
+    resources: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    depth: usize,
+) -> Option<Vec<u8>> {
+    let (_, base_components, _) = color_space(operands.first()?, resources, objects, depth + 1);
+    let entries = match resolve(operands.get(1)?, objects) {
+        PdfObj::Number(hival) => *hival as usize + 1,
+        _ => return None,
+    };
+    let lookup = match resolve(operands.get(2)?, objects) {
+        PdfObj::String(bytes) => bytes.clone(),
+        PdfObj::Stream(stream) => crate::decode_stream(stream),
+        _ => return None,
+    };
+    let step = usize::from(base_components.max(1));
+    Some(
+        lookup
+            .chunks_exact(step)
+            .take(entries)
+            .flat_map(|entry| match entry {
+                [gray] => [*gray, *gray, *gray],
+                [r, g, b] => [*r, *g, *b],
+                [c, m, y, k] => cmyk_to_rgb(*c, *m, *y, *k),
+                _ => [0, 0, 0],
+            })
+            .collect(),
+    )
+}
+
+fn cmyk_to_rgb(c: u8, m: u8, y: u8, k: u8) -> [u8; 3] {
+    let channel = |v: u8| ((255 - u16::from(v)) * (255 - u16::from(k)) / 255) as u8;
+    [channel(c), channel(m), channel(y)]
+}
+
+/// The image's samples with every filter undone, row by row as in the PDF;
+/// `None` when a filter cannot be decoded here or ends in an image codec.
+pub fn image_samples(image: &PageImage) -> Option<Vec<u8>> {
+    match decode_filters(image)? {
+        (samples, None) => Some(samples),
+        (_, Some(_)) => None,
+    }
+}
+
+/// The image in a standalone format: DCT data as JPEG and JPX data as
+/// JPEG 2000, both as stored, and other images decoded and written as PNG.
+/// `None` for codecs that are not supported (CCITT, JBIG2, LZW) and for
+/// sample layouts PNG cannot hold.
+pub fn export_image(image: &PageImage) -> Option<ImageExport> {
+    match decode_filters(image)? {
+        (data, Some("DCTDecode")) => Some(ImageExport::Jpeg(data)),
+        (data, Some("JPXDecode")) => Some(ImageExport::Jpeg2000(data)),
+        (_, Some(_)) => None,
+        (samples, None) => encode_png(image, &samples).map(ImageExport::Png),
+    }
+}
+
+/// One gray level per pixel, row by row, with the image's width and height:
+/// the luminance of [`rgb_pixels`].
+pub fn gray_pixels(image: &PageImage) -> Option<(usize, usize, Vec<u8>)> {
+    let (width, height, rgb) = rgb_pixels(image)?;
+    let gray = rgb
+        .chunks_exact(3)
+        .map(|p| luminance(p[0], p[1], p[2]))
+        .collect();
+    Some((width, height, gray))
+}
+
+/// Three bytes of RGB per pixel, row by row, with the image's width and
+/// height: palette entries looked up, gray and CMYK converted, image masks
+/// black where they paint, and DCT data decoded. `None` for other codecs.
+pub fn rgb_pixels(image: &PageImage) -> Option<(usize, usize, Vec<u8>)> {
+    let samples = match decode_filters(image)? {
+        (data, Some("DCTDecode")) => return jpeg::decode_rgb(&data),
+        (_, Some(_)) => return None,
+        (samples, None) => samples,
+    };
+    let (width, height) = (image.width as usize, image.height as usize);
+    let channels = usize::from(image.components.max(1));
+    let bits = usize::from(image.bits_per_component);
+    if width == 0 || height == 0 || !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return None;
+    }
+    let count = width
+        .checked_mul(height)
+        .filter(|&count| count <= jpeg::MAX_PIXELS)?;
+    let row_len = width.checked_mul(channels)?.checked_mul(bits)?.div_ceil(8);
+    if samples.len() < row_len.checked_mul(height)? {
+        return None;
+    }
+    let max = (1u32 << bits.min(8)) - 1;
+    // Sample `index` of a row, keeping the high byte of 16-bit samples.
+    let sample = |row: &[u8], index: usize| -> u32 {
+        match bits {
+            16 => u32::from(row[index * 2]),
+            8 => u32::from(row[index]),
+            _ => {
+                let offset = index * bits;
+                u32::from(row[offset / 8] >> (8 - bits - offset % 8)) & max
+            }
+        }
+    };
+    let mut pixels = Vec::with_capacity(count * 3);
+    for row in samples.chunks_exact(row_len).take(height) {
+        for x in 0..width {
+            let level = |c: usize| (sample(row, x * channels + c) * 255 / max) as u8;
+            let rgb = if image.image_mask {
+                if sample(row, x) == 0 {
+                    [0; 3]
+                } else {
+                    [255; 3]
+                }
+            } else if let Some(palette) = &image.palette {
+                let entry = sample(row, x) as usize * 3;
+                match palette.get(entry..entry + 3) {
+                    Some(rgb) => [rgb[0], rgb[1], rgb[2]],
+                    None => [0; 3],
+                }
+            } else {
+                match channels {
+                    3 => [level(0), level(1), level(2)],
+                    4 => cmyk_to_rgb(level(0), level(1), level(2), level(3)),
+                    _ => [level(0); 3],
+                }
+            };
+            pixels.extend(rgb);
+        }
+    }
+    Some((width, height, pixels))
+}
+
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b)) / 1000) as u8
+}
+
+// Undoes the general-purpose filters, stopping at an image codec, which is
+// returned along with the data it still has to decode.
+fn decode_filters(image: &PageImage) -> Option<(Vec<u8>, Option<&str>)> {
+    let mut data = image.data.clone();
+    for (index, filter) in image.filters.iter().enumerate() {
+        let parms = image.decode_parms.get(index);
+        data = match filter.as_str() {
+            "FlateDecode" => {
+                let inflated = decompress_to_vec_zlib(&data)
+                    .or_else(|_| decompress_to_vec(&data))
+                    .ok()?;
+                match parms {
+                    Some(parms) => undo_predictor(inflated, parms)?,
+                    None => inflated,
+                }
+            }
+            "ASCIIHexDecode" => ascii_hex_decode(&data),
+            "ASCII85Decode" => ascii85_decode(&data)?,
+            "RunLengthDecode" => run_length_decode(&data),
+            codec if index + 1 == image.filters.len() => return Some((data, Some(codec))),
+            _ => return None,
+        };
+    }
+    Some((data, None))
+}
+
+fn undo_predictor(data: Vec<u8>, parms: &HashMap<String, PdfObj>) -> Option<Vec<u8>> {
+    let number = |key: &str, default: usize| match parms.get(key) {
+        Some(PdfObj::Number(n)) if *n >= 1.0 => *n as usize,
+        _ => default,
+    };
+    let predictor = number("Predictor", 1);
+    if predictor == 1 {
+        return Some(data);
+    }
+    let bits_per_pixel = number("Colors", 1).checked_mul(number("BitsPerComponent", 8))?;
+    let row_len = number("Columns", 1)
+        .checked_mul(bits_per_pixel)?
+        .div_ceil(8);
+    // No real image has a row longer than its whole pixel budget.
+    if row_len > jpeg::MAX_PIXELS {
+        return None;
+    }
+    let bpp = bits_per_pixel.div_ceil(8);
+    if predictor == 2 {
+        // TIFF horizontal differencing, for whole-byte samples only.
+        if bits_per_pixel % 8 != 0 {
+            return None;
+        }
+        let mut out = data;
+        for row in out.chunks_mut(row_len) {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        return Some(out);
+    }
+    // PNG predictors: every row starts with its own filter type.
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_len];
+    for chunk in data.chunks(row_len.checked_add(1)?) {
+        let (&kind, encoded) = chunk.split_first()?;
+        let mut row = encoded.to_vec();
+        row.resize(row_len, 0);
+        for i in 0..row_len {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            row[i] = row[i].wrapping_add(match kind {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return None,
+            });
+        }
+        out.extend_from_slice(&row);
+        previous = row;
+    }
+    Some(out)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |v: u8| (estimate - i16::from(v)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn ascii_hex_decode(data: &[u8]) -> Vec<u8> {
+    let mut digits: Vec<u8> = data
+        .iter()
+        .take_while(|&&b| b != b'>')
+        .filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect()
+}
+
+fn ascii85_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut group = Vec::with_capacity(5);
+    for &b in data {
+        match b {
+            b'~' => break,
+            b'z' if group.is_empty() => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(b - b'!');
+                if group.len() == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    group.clear();
+                }
+            }
+            _ if b.is_ascii_whitespace() => {}
+            _ => return None,
+        }
+    }
+    if !group.is_empty() {
+        // A final partial group is padded with `u` and cut back.
+        let kept = group.len() - 1;
+        group.resize(5, b'u' - b'!');
+        out.extend_from_slice(&ascii85_group(&group)?[..kept]);
+    }
+    Some(out)
+}
+
+fn ascii85_group(digits: &[u8]) -> Option<[u8; 4]> {
+    let value = digits.iter().try_fold(0u32, |acc, &d| {
+        acc.checked_mul(85)?.checked_add(u32::from(d))
+    })?;
+    Some(value.to_be_bytes())
+}
+
+fn run_length_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while let Some(&length) = data.get(i) {
+        match length {
+            128 => break,
+            0..=127 => {
+                let end = (i + 2 + usize::from(length)).min(data.len());
+                out.extend_from_slice(&data[(i + 1).min(end)..end]);
+                i = end;
+            }
+            _ => {
+                if let Some(&byte) = data.get(i + 1) {
+                    out.extend(std::iter::repeat_n(byte, 257 - usize::from(length)));
+                }
+                i += 2;
+            }
+        }
+    }
+    out
+}
+
+// Writes the samples as a PNG: gray, RGB or palette images keep their
+// samples, CMYK is converted to RGB, and image masks become 1-bit gray
+// (black where painted).
+fn encode_png(image: &PageImage, samples: &[u8]) -> Option<Vec<u8>> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let bits = image.bits_per_component;
+    let (color_type, rows, depth) = match (&image.palette, image.components, bits) {
+        (Some(_), _, 1 | 2 | 4 | 8) => (3, rows(samples, width, height, 1, bits)?, bits),
+        (None, 1, 1 | 2 | 4 | 8 | 16) => (0, rows(samples, width, height, 1, bits)?, bits),
+        (None, 3, 8 | 16) => (2, rows(samples, width, height, 3, bits)?, bits),
+        (None, 4, 8) => {
+            let cmyk = rows(samples, width, height, 4, 8)?;
+            let rgb = cmyk
+                .chunks_exact(4)
+                .flat_map(|p| cmyk_to_rgb(p[0], p[1], p[2], p[3]))
+                .collect();
+            (2, rgb, 8)
+        }
+        _ => return None,
+    };
+    let channels = match color_type {
+        2 => 3,
+        _ => 1,
+    };
+    let row_len = width
+        .checked_mul(channels)?
+        .checked_mul(usize::from(depth))?
+        .div_ceil(8);
+    let mut raw = Vec::with_capacity(row_len.checked_add(1)?.checked_mul(height)?);
+    for row in rows.chunks(row_len.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&u32::try_from(width).ok()?.to_be_bytes());
+    header.extend_from_slice(&u32::try_from(height).ok()?.to_be_bytes());
+    header.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    if let Some(palette) = &image.palette {
+        write_chunk(&mut png, b"PLTE", palette);
+    }
+    write_chunk(&mut png, b"IDAT", &compress_to_vec_zlib(&raw, 6));
+    write_chunk(&mut png, b"IEND", &[]);
+    Some(png)
+}
+
+/// Writes 8-bit RGBA pixels, row by row, as a PNG.
+pub fn encode_rgba_png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+    for row in pixels.chunks(width * 4).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &compress_to_vec_zlib(&raw, 6));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// The first `height` rows of `width` samples, or `None` when the data is
+// too short.
+fn rows(samples: &[u8], width: usize, height: usize, channels: usize, bits: u8) -> Option<Vec<u8>> {
+    if width == 0 || height == 0 || width.checked_mul(height)? > jpeg::MAX_PIXELS {
+        return None;
+    }
+    let row_len = width
+        .checked_mul(channels)?
+        .checked_mul(usize::from(bits))?
+        .div_ceil(8);
+    samples
+        .get(..row_len.checked_mul(height)?)
+        .map(<[u8]>::to_vec)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
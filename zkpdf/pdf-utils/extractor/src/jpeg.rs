@@ -0,0 +1,501 @@
+//! Baseline JPEG decoding to RGB, enough to read codes printed in DCT images
+//! and to draw them. Progressive and arithmetic-coded images are not supported.
+
+// Position in the 8x8 block of each coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Images larger than this many pixels are not decoded.
+pub(crate) const MAX_PIXELS: usize = 1 << 26;
+
+#[derive(Default, Clone)]
+struct Huffman {
+    // Canonical decoding tables of JPEG Annex F, by code length 1..=16.
+    max_code: [i32; 17],
+    val_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8; 16], values: Vec<u8>) -> Self {
+        let mut table = Huffman {
+            max_code: [-1; 17],
+            val_offset: [0; 17],
+            values,
+        };
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for length in 1..=16 {
+            let count = i32::from(counts[length - 1]);
+            if count > 0 {
+                table.val_offset[length] = index - code;
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc_table: usize,
+    ac_table: usize,
+    predictor: i32,
+    // Decoded samples, `blocks_wide * 8` wide.
+    plane: Vec<u8>,
+    blocks_wide: usize,
+    blocks_high: usize,
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.count == 0 {
+            let byte = match self.data.get(self.pos) {
+                Some(0xFF) => match self.data.get(self.pos + 1) {
+                    // A stuffed zero byte follows a data 0xFF.
+                    Some(0x00) => {
+                        self.pos += 2;
+                        0xFF
+                    }
+                    // A marker: the data has run out, so feed zeros.
+                    _ => 0,
+                },
+                Some(&byte) => {
+                    self.pos += 1;
+                    byte
+                }
+                None => 0,
+            };
+            self.bits = u32::from(byte);
+            self.count = 8;
+        }
+        self.count -= 1;
+        (self.bits >> self.count) & 1
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        (0..n).fold(0, |acc, _| (acc << 1) | self.bit())
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Option<u8> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit() as i32;
+            if code <= table.max_code[length] {
+                let index = (code + table.val_offset[length]) as usize;
+                return table.values.get(index).copied();
+            }
+        }
+        None
+    }
+
+    // A coefficient of `size` bits, with its sign from the first bit.
+    fn extend(&mut self, size: u8) -> i32 {
+        if size == 0 {
+            return 0;
+        }
+        let value = self.bits(u32::from(size)) as i32;
+        if value < 1 << (size - 1) {
+            value - (1 << size) + 1
+        } else {
+            value
+        }
+    }
+
+    // Skips to the byte after the next restart marker.
+    fn restart(&mut self) {
+        self.count = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// Decodes a baseline JPEG to three bytes of RGB per pixel, returning its
+/// width, height and pixels row by row.
+pub fn decode_rgb(data: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut pos = 2;
+    let mut quant = [[0u16; 64]; 4];
+    let mut dc_tables: [Huffman; 4] = Default::default();
+    let mut ac_tables: [Huffman; 4] = Default::default();
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0, 0);
+    let mut restart_interval = 0;
+    let mut adobe_transform = None;
+    loop {
+        // Markers may be preceded by fill bytes.
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if data.get(pos) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        pos += 2;
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+        let length = usize::from(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]));
+        let segment = data.get(pos + 2..pos + length)?;
+        match marker {
+            0xC0 | 0xC1 => {
+                let header = segment.get(..6)?;
+                if header[0] != 8 {
+                    return None;
+                }
+                height = usize::from(u16::from_be_bytes([header[1], header[2]]));
+                width = usize::from(u16::from_be_bytes([header[3], header[4]]));
+                if width == 0 || height == 0 || width * height > MAX_PIXELS {
+                    return None;
+                }
+                let count = usize::from(header[5]);
+                let specs = segment.get(6..)?;
+                if specs.len() < count * 3 {
+                    return None;
+                }
+                for c in specs[..count * 3].chunks_exact(3) {
+                    components.push(Component {
+                        id: c[0],
+                        h: usize::from(c[1] >> 4).max(1),
+                        v: usize::from(c[1] & 15).max(1),
+                        quant: usize::from(c[2] & 3),
+                        dc_table: 0,
+                        ac_table: 0,
+                        predictor: 0,
+                        plane: Vec::new(),
+                        blocks_wide: 0,
+                        blocks_high: 0,
+                    });
+                }
+            }
+            // Progressive, lossless, hierarchical and arithmetic-coded frames.
+            0xC2..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return None,
+            0xC4 => {
+                let mut rest = segment;
+                while rest.len() >= 17 {
+                    let (class, index) = (rest[0] >> 4, usize::from(rest[0] & 3));
+                    let counts: [u8; 16] = rest[1..17].try_into().ok()?;
+                    let total: usize = counts.iter().map(|&c| usize::from(c)).sum();
+                    let values = rest.get(17..17 + total)?.to_vec();
+                    let table = Huffman::new(&counts, values);
+                    if class == 0 {
+                        dc_tables[index] = table;
+                    } else {
+                        ac_tables[index] = table;
+                    }
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xDB => {
+                let mut rest = segment;
+                while let Some(&info) = rest.first() {
+                    let wide = info >> 4 != 0;
+                    let table = &mut quant[usize::from(info & 3)];
+                    let size = if wide { 128 } else { 64 };
+                    let values = rest.get(1..1 + size)?;
+                    for (k, entry) in table.iter_mut().enumerate() {
+                        *entry = if wide {
+                            u16::from_be_bytes([values[2 * k], values[2 * k + 1]])
+                        } else {
+                            u16::from(values[k])
+                        };
+                    }
+                    rest = &rest[1 + size..];
+                }
+            }
+            0xDD => {
+                let interval = segment.get(..2)?;
+                restart_interval = usize::from(u16::from_be_bytes([interval[0], interval[1]]));
+            }
+            0xEE if segment.starts_with(b"Adobe") => adobe_transform = segment.get(11).copied(),
+            0xDA => {
+                let count = usize::from(*segment.first()?);
+                let mut scan = Vec::new();
+                for c in segment.get(1..1 + count * 2)?.chunks_exact(2) {
+                    let index = components.iter().position(|comp| comp.id == c[0])?;
+                    components[index].dc_table = usize::from(c[1] >> 4) & 3;
+                    components[index].ac_table = usize::from(c[1] & 15) & 3;
+                    scan.push(index);
+                }
+                pos += length;
+                let mut reader = BitReader {
+                    data,
+                    pos,
+                    bits: 0,
+                    count: 0,
+                };
+                decode_scan(
+                    &mut reader,
+                    &mut components,
+                    &scan,
+                    (width, height),
+                    &quant,
+                    (&dc_tables, &ac_tables),
+                    restart_interval,
+                )?;
+                // Find the marker that ends the scan.
+                pos = reader.pos;
+                while pos + 1 < data.len()
+                    && !(data[pos] == 0xFF
+                        && data[pos + 1] != 0
+                        && !(0xD0..=0xD7).contains(&data[pos + 1]))
+                {
+                    pos += 1;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        pos += length;
+    }
+    rgb_image(&components, width, height, adobe_transform)
+}
+
+fn decode_scan(
+    reader: &mut BitReader,
+    components: &mut [Component],
+    scan: &[usize],
+    (width, height): (usize, usize),
+    quant: &[[u16; 64]; 4],
+    (dc_tables, ac_tables): (&[Huffman; 4], &[Huffman; 4]),
+    restart_interval: usize,
+) -> Option<()> {
+    let h_max = components.iter().map(|c| c.h).max()?;
+    let v_max = components.iter().map(|c| c.v).max()?;
+    let mcus_wide = width.div_ceil(8 * h_max);
+    let mcus_high = height.div_ceil(8 * v_max);
+    for component in components.iter_mut() {
+        if component.plane.is_empty() {
+            component.blocks_wide = mcus_wide * component.h;
+            component.blocks_high = mcus_high * component.v;
+            component.plane = vec![0; component.blocks_wide * component.blocks_high * 64];
+        }
+        component.predictor = 0;
+    }
+    // A scan of one component codes its blocks one by one, covering only the
+    // component's own size rather than whole MCUs.
+    let (units_wide, units_high) = if let [only] = scan {
+        let c = &components[*only];
+        (
+            (width * c.h).div_ceil(h_max).div_ceil(8),
+            (height * c.v).div_ceil(v_max).div_ceil(8),
+        )
+    } else {
+        (mcus_wide, mcus_high)
+    };
+    let mut coefficients = [0i32; 64];
+    for unit in 0..units_wide * units_high {
+        if restart_interval > 0 && unit > 0 && unit % restart_interval == 0 {
+            reader.restart();
+            for &index in scan {
+                components[index].predictor = 0;
+            }
+        }
+        let (unit_x, unit_y) = (unit % units_wide, unit / units_wide);
+        for &index in scan {
+            let component = &mut components[index];
+            let blocks: Vec<(usize, usize)> = if scan.len() == 1 {
+                vec![(unit_x, unit_y)]
+            } else {
+                (0..component.v)
+                    .flat_map(|v| (0..component.h).map(move |h| (h, v)))
+                    .map(|(h, v)| (unit_x * component.h + h, unit_y * component.v + v))
+                    .collect()
+            };
+            for (block_x, block_y) in blocks {
+                coefficients.fill(0);
+                let table = &quant[component.quant];
+                let size = reader.decode(&dc_tables[component.dc_table])?;
+                component.predictor += reader.extend(size);
+                coefficients[0] = component.predictor * i32::from(table[0]);
+                let mut k = 1;
+                while k < 64 {
+                    let symbol = reader.decode(&ac_tables[component.ac_table])?;
+                    let (run, size) = (usize::from(symbol >> 4), symbol & 15);
+                    if size == 0 {
+                        if run != 15 {
+                            break;
+                        }
+                        k += 16;
+                        continue;
+                    }
+                    k += run;
+                    if k >= 64 {
+                        break;
+                    }
+                    coefficients[ZIGZAG[k]] = reader.extend(size) * i32::from(table[k]);
+                    k += 1;
+                }
+                let stride = component.blocks_wide * 8;
+                let origin = block_y * 8 * stride + block_x * 8;
+                idct(&coefficients, &mut component.plane[origin..], stride);
+            }
+        }
+    }
+    Some(())
+}
+
+// Inverse DCT of one block into `out`, whose rows are `stride` apart.
+fn idct(coefficients: &[i32; 64], out: &mut [u8], stride: usize) {
+    let mut cosines = [[0f32; 8]; 8];
+    for (x, row) in cosines.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            *value = scale * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    // Rows first, then columns.
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8)
+                .map(|u| cosines[x][u] * coefficients[v * 8 + u] as f32)
+                .sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|v| cosines[y][v] * rows[v * 8 + x]).sum();
+            out[y * stride + x] = (value / 4.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// Upsamples the components and converts them to RGB from gray, YCbCr, RGB,
+// CMYK or YCCK.
+fn rgb_image(
+    components: &[Component],
+    width: usize,
+    height: usize,
+    adobe_transform: Option<u8>,
+) -> Option<(usize, usize, Vec<u8>)> {
+    let h_max = components.iter().map(|c| c.h).max()?;
+    let v_max = components.iter().map(|c| c.v).max()?;
+    if components.iter().any(|c| c.plane.is_empty()) {
+        return None;
+    }
+    let sample = |c: &Component, x: usize, y: usize| -> f32 {
+        let (cx, cy) = (x * c.h / h_max, y * c.v / v_max);
+        f32::from(c.plane[cy * c.blocks_wide * 8 + cx])
+    };
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let rgb = match components {
+                [luma] => {
+                    let luma = sample(luma, x, y);
+                    [luma, luma, luma]
+                }
+                // JFIF images are YCbCr unless an Adobe marker says RGB.
+                [l, cb, cr] if adobe_transform != Some(0) => {
+                    ycc_to_rgb(sample(l, x, y), sample(cb, x, y), sample(cr, x, y))
+                }
+                [r, g, b] => [sample(r, x, y), sample(g, x, y), sample(b, x, y)],
+                [c, m, y_, k] => {
+                    // Adobe writes CMYK inverted; YCCK carries YCbCr in place
+                    // of the inverted cyan, magenta and yellow.
+                    let k = sample(k, x, y) / 255.0;
+                    let (c, m, y_) = (sample(c, x, y), sample(m, x, y), sample(y_, x, y));
+                    let base = if adobe_transform == Some(2) {
+                        ycc_to_rgb(c, m, y_)
+                    } else {
+                        [c, m, y_]
+                    };
+                    base.map(|v| v * k)
+                }
+                _ => return None,
+            };
+            pixels.extend(rgb.map(|v| v.round().clamp(0.0, 255.0) as u8));
+        }
+    }
+    Some((width, height, pixels))
+}
+
+fn ycc_to_rgb(y: f32, cb: f32, cr: f32) -> [f32; 3] {
+    let (cb, cr) = (cb - 128.0, cr - 128.0);
+    [
+        y + 1.402 * cr,
+        y - 0.344_136 * cb - 0.714_136 * cr,
+        y + 1.772 * cb,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xFF, marker];
+        out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn decodes_flat_blocks() {
+        // A 16x8 gray image of a black and a white block, with unit
+        // quantisation and one-code Huffman tables: DC category 11 and EOB.
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend(segment(0xDB, &[[0u8].as_slice(), &[1; 64]].concat()));
+        jpeg.extend(segment(0xC0, &[8, 0, 8, 0, 16, 1, 1, 0x11, 0]));
+        let mut tables = vec![0x00, 1];
+        tables.extend([0; 15]);
+        tables.extend([11, 0x10, 1]);
+        tables.extend([0; 15]);
+        tables.push(0);
+        jpeg.extend(segment(0xC4, &tables));
+        jpeg.extend(segment(0xDA, &[1, 1, 0x00, 0, 63, 0]));
+        // DC differences of -1024 and +2040, each followed by EOB.
+        jpeg.extend([0x3F, 0xF3, 0xFC, 0x3F, 0xFF, 0xD9]);
+
+        let (width, height, pixels) = decode_rgb(&jpeg).unwrap();
+        assert_eq!((width, height), (16, 8));
+        assert!(pixels
+            .chunks(48)
+            .all(|row| row[..24] == [0; 24] && row[24..] == [255; 24]));
+    }
+
+    #[test]
+    fn rejects_truncated_segments() {
+        for (marker, payload) in [
+            (0xC0, &[8, 0, 8][..]),
+            (0xC0, &[8, 0, 8, 0, 16, 2, 1, 0x11, 0]),
+            (0xDD, &[0]),
+        ] {
+            let mut jpeg = vec![0xFF, 0xD8];
+            jpeg.extend(segment(marker, payload));
+            jpeg.extend([0xFF, 0xD9]);
+            assert_eq!(decode_rgb(&jpeg), None);
+        }
+    }
+}
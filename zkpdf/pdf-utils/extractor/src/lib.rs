@@ -1,21 +1,70 @@
 pub mod parser_utils;
 pub mod types;
 
+mod attachments;
+mod cff;
+mod cid_collections;
 mod cmap;
 mod encoding;
+mod fingerprint;
 mod font;
+mod forms;
+mod graphics;
+mod images;
+mod jpeg;
+mod links;
+mod metadata;
+mod navigation;
+mod normalize;
+mod optional_content;
+mod overprint;
 mod parser;
+mod postscript;
+mod qr;
+mod raster;
+mod script;
+mod text_state;
+mod truetype;
+mod type1;
+mod unicode_tables;
+mod xfa;
+mod xml;
 
 extern crate alloc;
 
-use crate::cmap::decode_bytes;
-use crate::font::collect_fonts;
+use crate::attachments::embedded_files;
+use crate::cmap::decode_glyphs;
+use crate::encoding::decode_text_string;
+use crate::fingerprint::{learn_document, recognise};
+use crate::font::{collect_fonts, glyph_outline};
+use crate::forms::form_fields;
+use crate::graphics::{
+    display_matrix, hidden_reasons, intersect, multiply, transform, transform_rect, Canvas,
+    ColorSpace, GraphicsState, PathBounds, PathSegment, IDENTITY,
+};
+use crate::images::{inline_image, xobject_image};
+use crate::links::document_links;
+use crate::metadata::document_metadata;
+use crate::navigation::document_navigation;
+use crate::normalize::normalize_chars;
+use crate::optional_content::{hidden_groups, is_visible};
+use crate::overprint::OverprintIndex;
 use crate::parser::Parser;
 use crate::parser_utils::{
     fold_array_tokens, is_delimiter, parse_hex_string, parse_literal_string, parse_name,
     parse_number,
 };
-use crate::types::{PageContent, PdfError, PdfFont, PdfObj, PdfStream, Token};
+use crate::qr::image_qr_codes;
+use crate::raster::Raster;
+use crate::script::ends_sentence;
+use crate::text_state::TextState;
+use crate::types::{
+    CharSource, DocumentMetadata, DocumentNavigation, EmbeddedFile, ExtractOptions,
+    FingerprintDatabase, FormField, HiddenReason, Link, PageContent, PageDiagnostics, PageImage,
+    PdfDocument, PdfError, PdfFont, PdfObj, PdfStream, QrCode, RenderedPage, TextGlyph, Token,
+    XfaField,
+};
+use crate::xfa::{dynamic_form_text, xfa_fields};
 use alloc::string::String;
 use alloc::vec::Vec;
 use miniz_oxide::inflate::decompress_to_vec_zlib;
@@ -23,12 +72,175 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::str;
 
+pub use crate::images::{export_image, image_samples, rgb_pixels};
+pub use crate::metadata::parse_date;
+pub use crate::normalize::normalize_text;
+pub use crate::script::is_unspaced_script;
+
 /// Extracts text from a PDF and returns per-page strings
+///
+/// The data of a dynamic XFA form, whose pages only show a placeholder, is
+/// appended to the first page's text as `path: value` lines, except when
+/// [`ExtractOptions::visible_only`] is set.
 pub fn extract_text(pdf_bytes: Vec<u8>) -> Result<Vec<String>, PdfError> {
+    extract_text_with_options(pdf_bytes, &ExtractOptions::default())
+}
+
+/// Extracts per-page text like [`extract_text`], post-processed as `options`
+/// asks.
+pub fn extract_text_with_options(
+    pdf_bytes: Vec<u8>,
+    options: &ExtractOptions,
+) -> Result<Vec<String>, PdfError> {
+    let pages = extract_text_with_diagnostics(pdf_bytes, options)?;
+    Ok(pages.into_iter().map(|(text, _)| text).collect())
+}
+
+/// Extracts per-page text like [`extract_text_with_options`], along with notes
+/// on how each page's text was assembled.
+pub fn extract_text_with_diagnostics(
+    pdf_bytes: Vec<u8>,
+    options: &ExtractOptions,
+) -> Result<Vec<(String, PageDiagnostics)>, PdfError> {
+    let document = parse_document(&pdf_bytes)?;
+    // Form data is not drawn on any page, so it is never visible text.
+    let mut form_text = if options.visible_only {
+        None
+    } else {
+        dynamic_form_text(&document)
+    };
+    Ok(document
+        .pages
+        .iter()
+        .map(|page| {
+            let sink = interpret_page(page, &document.objects, options);
+            let (mut text, mut sources) = layout_sourced_text(&sink.output, &output_sources(&sink));
+            if let Some(form_text) = form_text.take() {
+                if !text.is_empty() {
+                    text.push('\n');
+                    sources.push(CharSource::Layout);
+                }
+                text.push_str(&form_text);
+                sources.extend(form_text.chars().map(|_| CharSource::TextString));
+            }
+            if options.normalize_unicode {
+                // A composed character is guessed when any of its parts was.
+                let merge = |base: CharSource, mark: CharSource| {
+                    if mark.is_guessed() {
+                        mark
+                    } else {
+                        base
+                    }
+                };
+                (text, sources) = normalize_chars(text.chars().zip(sources), merge)
+                    .into_iter()
+                    .unzip();
+            }
+            let mut diagnostics = sink.diagnostics;
+            diagnostics.char_sources = sources;
+            (text, diagnostics)
+        })
+        .collect())
+}
+
+/// Extracts the glyphs shown on each page, each marked with the reasons it
+/// would not be seen.
+pub fn extract_glyphs(pdf_bytes: Vec<u8>) -> Result<Vec<Vec<TextGlyph>>, PdfError> {
+    let (page_content, objects) = parse_pdf(&pdf_bytes)?;
+    Ok(page_content
+        .iter()
+        .map(|page| extract_glyphs_from_page(page, &objects))
+        .collect())
+}
+
+/// Lists the image XObjects and inline images each page draws, with where
+/// they are placed. [`export_image`] turns them into JPEG, JPEG 2000 or PNG
+/// files.
+pub fn extract_images(pdf_bytes: Vec<u8>) -> Result<Vec<Vec<PageImage>>, PdfError> {
+    let (page_content, objects) = parse_pdf(&pdf_bytes)?;
+    Ok(page_content
+        .iter()
+        .map(|page| extract_images_from_page(page, &objects))
+        .collect())
+}
+
+/// Decodes the QR codes printed in the images each page draws, in page and
+/// drawing order, with where each code lies on its page.
+pub fn extract_qr_codes(pdf_bytes: Vec<u8>) -> Result<Vec<QrCode>, PdfError> {
+    let (page_content, objects) = parse_pdf(&pdf_bytes)?;
+    Ok(page_content
+        .iter()
+        .enumerate()
+        .flat_map(|(index, page)| {
+            extract_images_from_page(page, &objects)
+                .iter()
+                .flat_map(|image| image_qr_codes(image, index))
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// Draws page `page_index` at `scale` pixels per point: its paths, images
+/// and the outlines of embedded TrueType and CFF glyphs.
+pub fn render_page(
+    pdf_bytes: Vec<u8>,
+    page_index: usize,
+    scale: f64,
+) -> Result<RenderedPage, PdfError> {
     let (page_content, objects) = parse_pdf(&pdf_bytes)?;
-    let text_per_page = extract_text_from_document(&page_content, &objects)
-        .map_err(|_| PdfError::ParseError("text extraction failed"))?;
-    Ok(text_per_page)
+    let page = page_content
+        .get(page_index)
+        .ok_or(PdfError::ParseError("page index out of range"))?;
+    Ok(render_page_content(page, &objects, scale))
+}
+
+/// Adds the glyph outlines of every embedded TrueType and CFF font in a
+/// document whose Unicode text is known, from its ToUnicode map or the
+/// program's `cmap`, to `database`. Set the result as
+/// [`ExtractOptions::glyph_fingerprints`] to read fonts that map to nothing.
+/// Returns the number of glyphs added.
+pub fn learn_glyph_fingerprints(
+    pdf_bytes: Vec<u8>,
+    database: &mut FingerprintDatabase,
+) -> Result<usize, PdfError> {
+    let document = parse_document(&pdf_bytes)?;
+    learn_document(database, &document.objects)
+}
+
+/// Reads the terminal fields of a filled-in interactive form, with fully
+/// qualified names and decoded values.
+pub fn extract_form_fields(pdf_bytes: Vec<u8>) -> Result<Vec<FormField>, PdfError> {
+    Ok(form_fields(&parse_document(&pdf_bytes)?))
+}
+
+/// Reads the document information dictionary and the Dublin Core, `xmp` and
+/// `pdf` properties of the catalog's XMP metadata.
+pub fn extract_metadata(pdf_bytes: Vec<u8>) -> Result<DocumentMetadata, PdfError> {
+    Ok(document_metadata(&parse_document(&pdf_bytes)?))
+}
+
+/// Lists the `/Link` annotations of each page with their actions and the text
+/// under them.
+pub fn extract_links(pdf_bytes: Vec<u8>) -> Result<Vec<Vec<Link>>, PdfError> {
+    Ok(document_links(&parse_document(&pdf_bytes)?))
+}
+
+/// Lists the embedded files, from the `/EmbeddedFiles` name tree and
+/// FileAttachment annotations, with their decoded contents.
+pub fn extract_embedded_files(pdf_bytes: Vec<u8>) -> Result<Vec<EmbeddedFile>, PdfError> {
+    Ok(embedded_files(&parse_document(&pdf_bytes)?))
+}
+
+/// Reads the outline, named destinations and page labels, with destinations
+/// resolved to page indices as used by [`extract_text`].
+pub fn extract_navigation(pdf_bytes: Vec<u8>) -> Result<DocumentNavigation, PdfError> {
+    Ok(document_navigation(&parse_document(&pdf_bytes)?))
+}
+
+/// Reads the data of an XFA form from its `datasets` packet, as paths to
+/// data values.
+pub fn extract_xfa_data(pdf_bytes: Vec<u8>) -> Result<Vec<XfaField>, PdfError> {
+    Ok(xfa_fields(&parse_document(&pdf_bytes)?))
 }
 
 /// Extracts text from all pages of a document.
@@ -43,27 +255,332 @@ pub fn extract_text_from_document(
     Ok(pages_text)
 }
 
-pub fn extract_text_from_page(
+pub fn extract_text_from_page(page: &PageContent, objects: &HashMap<(u32, u16), PdfObj>) -> String {
+    extract_text_from_page_with_options(page, objects, &ExtractOptions::default())
+}
+
+/// Extracts a page's text, leaving out hidden glyphs as `options` asks.
+/// Unicode normalisation is not applied here.
+pub fn extract_text_from_page_with_options(
     page: &PageContent,
-    _objects: &HashMap<(u32, u16), PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    options: &ExtractOptions,
 ) -> String {
-    let mut output = String::new();
+    layout_text(&interpret_page(page, objects, options).output)
+}
+
+// Collapses whitespace within each line of raw extracted text and drops empty
+// lines.
+fn layout_text(output: &str) -> String {
+    let sources = vec![CharSource::Layout; output.chars().count()];
+    layout_sourced_text(output, &sources).0
+}
+
+// `layout_text` for raw text whose characters each have a source. A run of
+// whitespace collapsed to one space keeps the source of its first character.
+fn layout_sourced_text(output: &str, sources: &[CharSource]) -> (String, Vec<CharSource>) {
+    let mut text = String::new();
+    let mut text_sources = Vec::new();
+    let chars: Vec<(char, CharSource)> = output.chars().zip(sources.iter().copied()).collect();
+    for line in chars.split(|&(c, _)| c == '\n') {
+        let mut words: Vec<(char, CharSource)> = Vec::new();
+        let mut space = None;
+        for &(c, source) in line {
+            if c.is_whitespace() {
+                if !words.is_empty() {
+                    space = space.or(Some(source));
+                }
+            } else {
+                if let Some(space) = space.take() {
+                    words.push((' ', space));
+                }
+                words.push((c, source));
+            }
+        }
+        let Some(&(first, _)) = words.first() else {
+            continue;
+        };
+        // A CJK sentence wrapped onto the next line continues without a break.
+        let wrapped = text.chars().next_back().is_some_and(|last| {
+            is_unspaced_script(last) && !ends_sentence(last) && is_unspaced_script(first)
+        });
+        if !text.is_empty() && !wrapped {
+            text.push('\n');
+            text_sources.push(CharSource::Layout);
+        }
+        for (c, source) in words {
+            text.push(c);
+            text_sources.push(source);
+        }
+    }
+    (text, text_sources)
+}
+
+// The source of each character of a page's raw text: the glyphs or text
+// strings it was read from, or the layout that placed it.
+fn output_sources(sink: &PageSink) -> Vec<CharSource> {
+    let mut byte_sources = vec![CharSource::Layout; sink.output.len()];
+    for &(start, end, source) in &sink.text_sources {
+        byte_sources[start..end].fill(source);
+    }
+    sink.output
+        .char_indices()
+        .map(|(at, _)| byte_sources[at])
+        .collect()
+}
+
+/// Lists the images a page draws, in drawing order.
+pub fn extract_images_from_page(
+    page: &PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Vec<PageImage> {
+    interpret_page(page, objects, &ExtractOptions::default()).images
+}
+
+pub fn extract_glyphs_from_page(
+    page: &PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Vec<TextGlyph> {
+    interpret_page(page, objects, &ExtractOptions::default()).glyphs
+}
+
+// Text laid out from the page's content within `region`, in default user space.
+pub(crate) fn text_in_region(
+    page: &PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    region: [f64; 4],
+) -> String {
+    let page = PageContent {
+        crop_box: Some(region),
+        annotations: Vec::new(),
+        ..page.clone()
+    };
+    let options = ExtractOptions {
+        crop_to_page: true,
+        ..ExtractOptions::default()
+    };
+    layout_text(&interpret_page(&page, objects, &options).output)
+}
+
+/// Draws a page at `scale` pixels per point, the crop box filling the image
+/// and `/Rotate` applied. Text in fonts without an embedded TrueType or CFF
+/// program is drawn as faint boxes; shadings, patterns, soft masks and dash
+/// patterns are not drawn.
+pub fn render_page_content(
+    page: &PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    scale: f64,
+) -> RenderedPage {
+    let options = ExtractOptions::default();
+    let mut sink = page_sink(page, &options);
+    let [x0, y0, x1, y1] = page.crop_box.unwrap_or(LETTER);
+    let (width, height) = match page.rotate {
+        90 | 270 => ((y1 - y0).abs(), (x1 - x0).abs()),
+        _ => ((x1 - x0).abs(), (y1 - y0).abs()),
+    };
+    sink.raster = Some(Raster::new(width, height, scale));
+    run_page(page, objects, &mut sink);
+    let raster = sink
+        .raster
+        .unwrap_or_else(|| Raster::new(width, height, scale));
+    RenderedPage {
+        width: raster.width,
+        height: raster.height,
+        pixels: raster.pixels,
+    }
+}
+
+// Pages without a crop or media box are drawn at US Letter size.
+const LETTER: [f64; 4] = [0.0, 0.0, 612.0, 792.0];
+
+// Colour for paint whose colour space isn't understood.
+const UNKNOWN_PAINT: [f64; 3] = [0.5, 0.5, 0.5];
+
+// Runs a page's content streams, collecting its text and glyphs.
+fn interpret_page<'a>(
+    page: &'a PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    options: &'a ExtractOptions,
+) -> PageSink<'a> {
+    let mut sink = page_sink(page, options);
+    run_page(page, objects, &mut sink);
+    sink
+}
+
+fn page_sink<'a>(page: &'a PageContent, options: &'a ExtractOptions) -> PageSink<'a> {
+    PageSink {
+        output: String::new(),
+        glyphs: Vec::new(),
+        images: Vec::new(),
+        diagnostics: PageDiagnostics::default(),
+        overprints: OverprintIndex::default(),
+        visible_only: options.visible_only,
+        include_hidden_layers: options.include_hidden_layers,
+        crop_to_page: options.crop_to_page,
+        crop_box: page.crop_box,
+        display: page
+            .crop_box
+            .map_or(IDENTITY, |crop| display_matrix(&crop, page.rotate)),
+        hidden_layers: &page.hidden_layers,
+        hidden_layer_depth: 0,
+        canvas: Canvas::default(),
+        raster: None,
+        glyph_fingerprints: options.glyph_fingerprints.as_ref(),
+        text_sources: Vec::new(),
+    }
+}
+
+fn run_page(page: &PageContent, objects: &HashMap<(u32, u16), PdfObj>, sink: &mut PageSink) {
     let tokens = parse_content_tokens(&page.content_streams.concat());
     let mut visited = HashSet::new();
     extract_from_tokens(
         &tokens,
         &page.fonts,
         &page.resources,
-        &mut output,
-        _objects,
+        sink,
+        objects,
         &mut visited,
+        GraphicsState::default(),
     );
-    output
-        .lines()
-        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
-        .filter(|l| !l.is_empty())
-        .collect::<Vec<_>>()
-        .join("\n")
+    for annotation in &page.annotations {
+        if let PdfObj::Dictionary(annotation) = resolve(annotation, objects) {
+            extract_annotation(annotation, page, objects, sink, &mut visited);
+        }
+    }
+}
+
+// Annotation flags (`/F`) for annotations that are never displayed.
+const ANNOTATION_HIDDEN: u32 = 1 << 1;
+const ANNOTATION_NO_VIEW: u32 = 1 << 5;
+
+// Extracts the text an annotation shows, on lines of its own: what its normal
+// appearance stream draws, placed by `/Rect` and the form's `/Matrix`. FreeText,
+// Text and Stamp annotations whose appearance draws no text contribute their
+// `/Contents` instead, unless only visible text is wanted.
+fn extract_annotation(
+    annotation: &HashMap<String, PdfObj>,
+    page: &PageContent,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    sink: &mut PageSink,
+    visited: &mut HashSet<(u32, u16)>,
+) {
+    let subtype = match annotation.get("Subtype") {
+        Some(PdfObj::Name(subtype)) => subtype.as_str(),
+        _ => "",
+    };
+    let flags = match annotation.get("F").map(|f| resolve(f, objects)) {
+        Some(PdfObj::Number(flags)) => *flags as u32,
+        _ => 0,
+    };
+    // Widgets are form fields, whose values are read from the AcroForm.
+    let skipped = matches!(subtype, "Popup" | "Widget");
+    if skipped || flags & (ANNOTATION_HIDDEN | ANNOTATION_NO_VIEW) != 0 {
+        return;
+    }
+    let layer_hidden = annotation
+        .get("OC")
+        .is_some_and(|oc| !is_visible(oc, sink.hidden_layers, objects));
+    if layer_hidden {
+        sink.hidden_layer_depth += 1;
+    }
+    sink.output.push('\n');
+    let start = sink.output.len();
+    if let Some(form) = appearance_stream(annotation, objects) {
+        let resources = match form.dict.get("Resources").map(|r| resolve(r, objects)) {
+            Some(PdfObj::Dictionary(resources)) => resources,
+            _ => &page.resources,
+        };
+        let fonts = collect_fonts(resources, objects, true).unwrap_or_default();
+        let tokens = parse_content_tokens(&decode_stream(form));
+        extract_from_tokens(
+            &tokens,
+            &fonts,
+            resources,
+            sink,
+            objects,
+            visited,
+            appearance_graphics(annotation, form, objects),
+        );
+    }
+    let drew_text = !sink.output[start..].trim().is_empty();
+    let shows_contents = matches!(subtype, "FreeText" | "Text" | "Stamp");
+    let contents_hidden = sink.visible_only || (layer_hidden && !sink.include_hidden_layers);
+    if !drew_text && shows_contents && !contents_hidden {
+        if let Some(PdfObj::String(contents)) =
+            annotation.get("Contents").map(|c| resolve(c, objects))
+        {
+            let start = sink.output.len();
+            sink.output.push_str(&decode_text_string(contents));
+            let end = sink.output.len();
+            sink.text_sources.push((start, end, CharSource::TextString));
+        }
+    }
+    sink.output.push('\n');
+    if layer_hidden {
+        sink.hidden_layer_depth -= 1;
+    }
+}
+
+// The normal appearance (`/AP /N`), picking the state named by `/AS` when
+// there are several.
+fn appearance_stream<'a>(
+    annotation: &'a HashMap<String, PdfObj>,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+) -> Option<&'a PdfStream> {
+    let PdfObj::Dictionary(appearances) = resolve(annotation.get("AP")?, objects) else {
+        return None;
+    };
+    match resolve(appearances.get("N")?, objects) {
+        PdfObj::Stream(form) => Some(form),
+        PdfObj::Dictionary(states) => {
+            let PdfObj::Name(state) = annotation.get("AS")? else {
+                return None;
+            };
+            match resolve(states.get(state)?, objects) {
+                PdfObj::Stream(form) => Some(form),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Graphics state an appearance stream starts with: its `/BBox`, transformed by
+// its `/Matrix`, is mapped onto the annotation's `/Rect`, which also clips it.
+fn appearance_graphics(
+    annotation: &HashMap<String, PdfObj>,
+    form: &PdfStream,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> GraphicsState {
+    let numbers = |obj: Option<&PdfObj>| -> Vec<f64> {
+        match obj.map(|v| resolve(v, objects)) {
+            Some(PdfObj::Array(items)) => items
+                .iter()
+                .filter_map(|item| match resolve(item, objects) {
+                    PdfObj::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    let mut graphics = GraphicsState::default();
+    let Ok([rx0, ry0, rx1, ry1]) = <[f64; 4]>::try_from(numbers(annotation.get("Rect"))) else {
+        return graphics;
+    };
+    let rect = [rx0.min(rx1), ry0.min(ry1), rx0.max(rx1), ry0.max(ry1)];
+    let matrix = <[f64; 6]>::try_from(numbers(form.dict.get("Matrix"))).unwrap_or(IDENTITY);
+    let bbox = <[f64; 4]>::try_from(numbers(form.dict.get("BBox"))).unwrap_or(rect);
+    let [x0, y0, x1, y1] = transform_rect(&matrix, &bbox);
+    let scale = |to: f64, from: f64| if from == 0.0 { 1.0 } else { to / from };
+    let (sx, sy) = (
+        scale(rect[2] - rect[0], x1 - x0),
+        scale(rect[3] - rect[1], y1 - y0),
+    );
+    let onto_rect = [sx, 0.0, 0.0, sy, rect[0] - x0 * sx, rect[1] - y0 * sy];
+    graphics.concat(&multiply(&matrix, &onto_rect));
+    graphics.clip_to(rect);
+    graphics
 }
 
 // Use a recursive function to traverse the Pages tree
@@ -71,6 +588,7 @@ fn traverse_pages(
     obj_id: (u32, u16),
     objects: &HashMap<(u32, u16), PdfObj>,
     inherited_resources: Option<&HashMap<String, PdfObj>>,
+    inherited_geometry: PageGeometry,
     result: &mut Vec<PageContent>,
     decompress: &dyn Fn(&[u8]) -> Result<Vec<u8>, PdfError>,
 ) -> Result<(), PdfError> {
@@ -86,6 +604,7 @@ fn traverse_pages(
             let type_name = dict.get("Type");
             if let Some(PdfObj::Name(type_str)) = type_name {
                 if type_str == "Pages" {
+                    let geometry = inherited_geometry.inherit(dict, objects);
                     let new_inherited_res =
                         if let Some(PdfObj::Dictionary(res_dict)) = dict.get("Resources") {
                             Some(res_dict)
@@ -121,6 +640,7 @@ fn traverse_pages(
                                     child_id,
                                     objects,
                                     new_inherited_res.or(inherited_resources),
+                                    geometry,
                                     result,
                                     &decompress,
                                 )?;
@@ -131,6 +651,8 @@ fn traverse_pages(
                                         process_page_dict(
                                             child_dict,
                                             new_inherited_res.or(inherited_resources),
+                                            geometry,
+                                            None,
                                             objects,
                                             result,
                                             &decompress,
@@ -140,6 +662,7 @@ fn traverse_pages(
                                             (0, 0),
                                             objects,
                                             new_inherited_res.or(inherited_resources),
+                                            geometry,
                                             result,
                                             &decompress,
                                         )?;
@@ -150,7 +673,15 @@ fn traverse_pages(
                         }
                     }
                 } else if type_str == "Page" {
-                    process_page_dict(dict, inherited_resources, objects, result, &decompress)?;
+                    process_page_dict(
+                        dict,
+                        inherited_resources,
+                        inherited_geometry,
+                        Some(obj_id),
+                        objects,
+                        result,
+                        &decompress,
+                    )?;
                 } else {
                     return Err(PdfError::ParseError("Unknown object in page tree"));
                 }
@@ -161,7 +692,15 @@ fn traverse_pages(
         PdfObj::Stream(stream) => {
             if let Some(PdfObj::Name(t)) = stream.dict.get("Type") {
                 if t == "Page" {
-                    process_page_stream(stream, inherited_resources, objects, result, &decompress)?;
+                    process_page_stream(
+                        stream,
+                        inherited_resources,
+                        inherited_geometry,
+                        Some(obj_id),
+                        objects,
+                        result,
+                        &decompress,
+                    )?;
                 } else if t == "Pages" {
                     return Err(PdfError::ParseError(
                         "Pages object in stream form is not supported",
@@ -182,6 +721,8 @@ fn traverse_pages(
 fn process_page_dict(
     page_dict: &HashMap<String, PdfObj>,
     inherited_res: Option<&HashMap<String, PdfObj>>,
+    inherited_geometry: PageGeometry,
+    id: Option<(u32, u16)>,
     objects: &HashMap<(u32, u16), PdfObj>,
     result: &mut Vec<PageContent>,
     decompress: &dyn Fn(&[u8]) -> Result<Vec<u8>, PdfError>,
@@ -251,11 +792,21 @@ fn process_page_dict(
         }
     }
 
+    let geometry = inherited_geometry.inherit(page_dict, objects);
     let fonts_map = collect_fonts(resources_dict, objects, true)?;
     result.push(PageContent {
+        id,
         content_streams,
         fonts: fonts_map,
         resources: resources_dict.clone(),
+        media_box: geometry.media_box,
+        crop_box: geometry.crop_box(),
+        rotate: geometry.rotation(),
+        hidden_layers: HashSet::new(),
+        annotations: match page_dict.get("Annots").map(|a| resolve(a, objects)) {
+            Some(PdfObj::Array(annotations)) => annotations.clone(),
+            _ => Vec::new(),
+        },
     });
     Ok(())
 }
@@ -264,6 +815,8 @@ fn process_page_dict(
 fn process_page_stream(
     page_stream: &PdfStream,
     inherited_res: Option<&HashMap<String, PdfObj>>,
+    inherited_geometry: PageGeometry,
+    id: Option<(u32, u16)>,
     objects: &HashMap<(u32, u16), PdfObj>,
     result: &mut Vec<PageContent>,
     decompress: &dyn Fn(&[u8]) -> Result<Vec<u8>, PdfError>,
@@ -291,15 +844,85 @@ fn process_page_stream(
         content_streams.push(page_stream.data.clone());
     }
 
+    let geometry = inherited_geometry.inherit(page_dict, objects);
     let fonts_map = collect_fonts(resources_dict, objects, true)?;
     result.push(PageContent {
+        id,
         content_streams,
         fonts: fonts_map,
         resources: resources_dict.clone(),
+        media_box: geometry.media_box,
+        crop_box: geometry.crop_box(),
+        rotate: geometry.rotation(),
+        hidden_layers: HashSet::new(),
+        annotations: match page_dict.get("Annots").map(|a| resolve(a, objects)) {
+            Some(PdfObj::Array(annotations)) => annotations.clone(),
+            _ => Vec::new(),
+        },
     });
     Ok(())
 }
 
+// Page attributes inherited through the page tree along with `/Resources`.
+#[derive(Debug, Clone, Copy, Default)]
+struct PageGeometry {
+    media_box: Option<[f64; 4]>,
+    crop_box: Option<[f64; 4]>,
+    rotate: Option<f64>,
+}
+
+impl PageGeometry {
+    // The attributes of a page tree node, falling back to its ancestors'.
+    fn inherit(
+        self,
+        dict: &HashMap<String, PdfObj>,
+        objects: &HashMap<(u32, u16), PdfObj>,
+    ) -> PageGeometry {
+        let rect = |key: &str| -> Option<[f64; 4]> {
+            let PdfObj::Array(items) = resolve(dict.get(key)?, objects) else {
+                return None;
+            };
+            let numbers: Vec<f64> = items
+                .iter()
+                .filter_map(|item| match resolve(item, objects) {
+                    PdfObj::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            let [x0, y0, x1, y1] = <[f64; 4]>::try_from(numbers).ok()?;
+            Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+        };
+        let rotate = match dict.get("Rotate").map(|r| resolve(r, objects)) {
+            Some(PdfObj::Number(n)) => Some(*n),
+            _ => None,
+        };
+        PageGeometry {
+            media_box: rect("MediaBox").or(self.media_box),
+            crop_box: rect("CropBox").or(self.crop_box),
+            rotate: rotate.or(self.rotate),
+        }
+    }
+
+    // The crop box, clipped to the media box, which it defaults to.
+    fn crop_box(&self) -> Option<[f64; 4]> {
+        match (self.crop_box, self.media_box) {
+            (Some(crop), Some(media)) => Some(intersect(&crop, &media)),
+            (crop, media) => crop.or(media),
+        }
+    }
+
+    // `/Rotate` as 0, 90, 180 or 270; other values are not multiples of 90
+    // and are ignored.
+    fn rotation(&self) -> u16 {
+        let degrees = self.rotate.unwrap_or(0.0).rem_euclid(360.0);
+        if degrees % 90.0 == 0.0 {
+            degrees as u16
+        } else {
+            0
+        }
+    }
+}
+
 pub fn handle_stream_filters(
     filter_obj: &PdfObj,
     data: &[u8],
@@ -336,8 +959,44 @@ pub fn handle_stream_filters(
     Ok(())
 }
 
+/// Follows indirect references until a direct object is reached.
+pub(crate) fn resolve<'a>(obj: &'a PdfObj, objects: &'a HashMap<(u32, u16), PdfObj>) -> &'a PdfObj {
+    let mut current = obj;
+    let mut hops = 0;
+    while let PdfObj::Reference(id) = current {
+        match objects.get(id) {
+            Some(next) if hops < 32 => {
+                current = next;
+                hops += 1;
+            }
+            _ => return &PdfObj::Null,
+        }
+    }
+    current
+}
+
+/// Returns the decoded data of a stream, or the raw data when its filters are unsupported.
+pub(crate) fn decode_stream(stream: &PdfStream) -> Vec<u8> {
+    if let Some(filter) = stream.dict.get("Filter") {
+        let mut decoded: Vec<Vec<u8>> = Vec::new();
+        if handle_stream_filters(filter, &stream.data, true, &mut decoded).is_ok()
+            && !decoded.is_empty()
+        {
+            return decoded.remove(0);
+        }
+    }
+    stream.data.clone()
+}
+
 // Parse an entire PDF byte slice and produce page content data
 pub fn parse_pdf(data: &[u8]) -> Result<(Vec<PageContent>, HashMap<(u32, u16), PdfObj>), PdfError> {
+    let document = parse_document(data)?;
+    Ok((document.pages, document.objects))
+}
+
+/// Parses a PDF like [`parse_pdf`], keeping the trailer for document-level
+/// structures such as the catalog and `/Info`.
+pub fn parse_document(data: &[u8]) -> Result<PdfDocument, PdfError> {
     let mut parser = Parser::new(data);
     let mut objects: HashMap<(u32, u16), PdfObj> = HashMap::new();
 
@@ -601,6 +1260,10 @@ pub fn parse_pdf(data: &[u8]) -> Result<(Vec<PageContent>, HashMap<(u32, u16), P
         None => None,
     };
     let root_obj = root_obj.ok_or(PdfError::ParseError("Root object not found"))?;
+    let hidden_layers = match &root_obj {
+        PdfObj::Dictionary(catalog) => hidden_groups(catalog, &objects),
+        _ => HashSet::new(),
+    };
     let pages_obj_id = match root_obj {
         PdfObj::Dictionary(ref m) => {
             match m.get("Pages") {
@@ -617,16 +1280,28 @@ pub fn parse_pdf(data: &[u8]) -> Result<(Vec<PageContent>, HashMap<(u32, u16), P
     let mut result = Vec::new();
 
     if pages_obj_id != (0, 0) {
-        traverse_pages(pages_obj_id, &objects, None, &mut result, &|bytes| {
-            decompress_to_vec_zlib(bytes).map_err(|_| PdfError::DecompressionError)
-        })?;
+        traverse_pages(
+            pages_obj_id,
+            &objects,
+            None,
+            PageGeometry::default(),
+            &mut result,
+            &|bytes| decompress_to_vec_zlib(bytes).map_err(|_| PdfError::DecompressionError),
+        )?;
     } else {
         return Err(PdfError::ParseError(
             "Pages object embedded in catalog is not supported",
         ));
     }
+    for page in &mut result {
+        page.hidden_layers = hidden_layers.clone();
+    }
 
-    Ok((result, objects))
+    Ok(PdfDocument {
+        pages: result,
+        objects,
+        trailer: trailer_dict,
+    })
 }
 
 fn parse_obj_stream(
@@ -726,7 +1401,11 @@ fn parse_content_tokens(data: &[u8]) -> Vec<Token> {
                 while i < data.len() && !data[i].is_ascii_whitespace() && !is_delimiter(data[i]) {
                     i += 1;
                 }
-                if let Ok(op) = str::from_utf8(&data[start..i]) {
+                if &data[start..i] == b"BI" {
+                    let (image, end) = parse_inline_image(data, i);
+                    tokens.push(image);
+                    i = end;
+                } else if let Ok(op) = str::from_utf8(&data[start..i]) {
                     tokens.push(Token::Operator(op.to_string()));
                 }
             }
@@ -735,30 +1414,339 @@ fn parse_content_tokens(data: &[u8]) -> Vec<Token> {
     fold_array_tokens(tokens)
 }
 
+// Reads an inline image from just after its `BI`: the parameters up to `ID`,
+// then, after one whitespace byte, the data up to `EI`. The data is binary,
+// so its end is taken from the parameters when the image is unfiltered, and
+// otherwise from the first `EI` standing alone after it.
+fn parse_inline_image(data: &[u8], start: usize) -> (Token, usize) {
+    let is_space = |b: &u8| b.is_ascii_whitespace();
+    let id = (start..data.len().saturating_sub(1)).find(|&i| {
+        &data[i..i + 2] == b"ID"
+            && data.get(i.wrapping_sub(1)).is_some_and(is_space)
+            && data.get(i + 2).is_none_or(is_space)
+    });
+    let Some(id) = id else {
+        return (
+            Token::InlineImage {
+                params: Vec::new(),
+                data: Vec::new(),
+            },
+            data.len(),
+        );
+    };
+    let params = parse_content_tokens(&data[start..id]);
+    let data_start = (id + 3).min(data.len());
+    let search_from = inline_image_length(&params)
+        .and_then(|len| data_start.checked_add(len))
+        .unwrap_or(data_start);
+    let is_end = |i: usize| {
+        let after = data.get(i + 2);
+        &data[i..i + 2] == b"EI"
+            && (i == data_start || is_space(&data[i - 1]))
+            && after.is_none_or(|b| is_space(b) || is_delimiter(*b))
+    };
+    let first = search_from.saturating_sub(1).max(data_start);
+    let ei = (first..data.len().saturating_sub(1)).find(|&i| is_end(i));
+    let (data_end, end) = match ei {
+        // The whitespace before `EI` is not part of the data.
+        Some(ei) => (ei.saturating_sub(1).max(data_start), ei + 2),
+        None => (data.len(), data.len()),
+    };
+    let image = Token::InlineImage {
+        params,
+        data: data[data_start..data_end].to_vec(),
+    };
+    (image, end)
+}
+
+// Size in bytes of an unfiltered inline image with a device colour space.
+fn inline_image_length(params: &[Token]) -> Option<usize> {
+    let mut width = None;
+    let mut height = None;
+    let mut bits = 8;
+    let mut components = 1;
+    for pair in params.chunks_exact(2) {
+        match (&pair[0], &pair[1]) {
+            (Token::Name(key), _) if key == "F" || key == "Filter" => return None,
+            (Token::Name(key), Token::Number(n)) if key == "W" || key == "Width" => {
+                width = Some(*n as usize)
+            }
+            (Token::Name(key), Token::Number(n)) if key == "H" || key == "Height" => {
+                height = Some(*n as usize)
+            }
+            (Token::Name(key), Token::Number(n)) if key == "BPC" || key == "BitsPerComponent" => {
+                bits = *n as usize
+            }
+            (Token::Name(key), Token::Name(space)) if key == "CS" || key == "ColorSpace" => {
+                components = match space.as_str() {
+                    "G" | "DeviceGray" | "I" | "Indexed" => 1,
+                    "RGB" | "DeviceRGB" => 3,
+                    "CMYK" | "DeviceCMYK" => 4,
+                    _ => return None,
+                }
+            }
+            (Token::Name(key), _) if key == "IM" || key == "ImageMask" => bits = 1,
+            _ => {}
+        }
+    }
+    let (width, height) = (width?, height?);
+    if width.checked_mul(height)? > jpeg::MAX_PIXELS {
+        return None;
+    }
+    let row = width.checked_mul(components)?.checked_mul(bits)?;
+    row.div_ceil(8).checked_mul(height)
+}
+
+// Text and glyphs collected while interpreting one page, with what has been
+// painted so far to judge whether later glyphs can be seen.
+struct PageSink<'a> {
+    output: String,
+    glyphs: Vec<TextGlyph>,
+    images: Vec<PageImage>,
+    diagnostics: PageDiagnostics,
+    // Glyphs whose text went into `output`, to recognise overprints of them.
+    overprints: OverprintIndex,
+    // Leave hidden glyphs out of `output`; they are still recorded in `glyphs`.
+    visible_only: bool,
+    include_hidden_layers: bool,
+    crop_to_page: bool,
+    crop_box: Option<[f64; 4]>,
+    // From default user space to the displayed page, for glyph positions.
+    display: [f64; 6],
+    hidden_layers: &'a HashSet<(u32, u16)>,
+    // Number of enclosing optional content sections that are off.
+    hidden_layer_depth: usize,
+    canvas: Canvas,
+    // Where the page is drawn when rendering it.
+    raster: Option<Raster>,
+    // Outlines of known glyphs, for codes that map to no text.
+    glyph_fingerprints: Option<&'a FingerprintDatabase>,
+    // Byte ranges of `output` read from glyphs or text strings, with how
+    // their text was obtained; anything else was placed by layout.
+    text_sources: Vec<(usize, usize, CharSource)>,
+}
+
 fn extract_from_tokens(
     tokens: &[Token],
     fonts: &HashMap<String, PdfFont>,
     resources: &HashMap<String, PdfObj>,
-    output: &mut String,
+    sink: &mut PageSink,
     objects: &HashMap<(u32, u16), PdfObj>,
     visited: &mut HashSet<(u32, u16)>,
+    mut graphics: GraphicsState,
 ) {
+    let hidden_layer_depth = sink.hidden_layer_depth;
     let mut in_text = false;
     let mut current_font: Option<&PdfFont> = None;
+    let mut state = TextState::default();
+    let mut saved_states: Vec<(Option<&PdfFont>, TextState, GraphicsState)> = Vec::new();
+    let mut path = PathBounds::default();
+    // The path under construction on the displayed page, kept when rendering.
+    let mut outline: Vec<PathSegment> = Vec::new();
+    let mut current_point = (0.0, 0.0);
+    // One entry per open marked-content section: whether it hides its content.
+    let mut marked_content: Vec<bool> = Vec::new();
+    // Where the last shown string ended, when its glyph widths were known.
+    let mut last_end: Option<(f64, f64)> = None;
+    // Horizontal gap, in ems, between the last shown glyph and the next one.
+    let mut pending_gap = 0.0;
     let mut i = 0;
 
     while i < tokens.len() {
         if let Token::Operator(op) = &tokens[i] {
             match op.as_str() {
+                "q" => saved_states.push((current_font, state.clone(), graphics.clone())),
+                "Q" => {
+                    if let Some((font, saved, saved_graphics)) = saved_states.pop() {
+                        current_font = font;
+                        state = saved;
+                        graphics = saved_graphics;
+                    }
+                }
+                "cm" => {
+                    if let Some(matrix) = number_operands::<6>(tokens, i) {
+                        graphics.concat(&matrix);
+                    }
+                }
+                "g" | "rg" | "k" | "G" | "RG" | "K" => {
+                    let (space, color) = match op.as_str() {
+                        "g" | "G" => (
+                            ColorSpace::Gray,
+                            number_operands::<1>(tokens, i).map(Vec::from),
+                        ),
+                        "rg" | "RG" => (
+                            ColorSpace::Rgb,
+                            number_operands::<3>(tokens, i).map(Vec::from),
+                        ),
+                        _ => (
+                            ColorSpace::Cmyk,
+                            number_operands::<4>(tokens, i).map(Vec::from),
+                        ),
+                    };
+                    let color = color.and_then(|c| space.to_rgb(&c));
+                    if op.chars().all(|c| c.is_ascii_lowercase()) {
+                        graphics.fill_space = space;
+                        graphics.fill = color;
+                    } else {
+                        graphics.stroke_space = space;
+                        graphics.stroke = color;
+                    }
+                }
+                "cs" | "CS" => {
+                    if let Some(Token::Name(name)) = i.checked_sub(1).map(|p| &tokens[p]) {
+                        let space = ColorSpace::from_name(name, resources, objects);
+                        if op == "cs" {
+                            graphics.fill_space = space;
+                            graphics.fill = space.initial_color();
+                        } else {
+                            graphics.stroke_space = space;
+                            graphics.stroke = space.initial_color();
+                        }
+                    }
+                }
+                "sc" | "scn" | "SC" | "SCN" => {
+                    // A trailing name selects a pattern, whose colour is unknown.
+                    let components: Vec<f64> = tokens[..i]
+                        .iter()
+                        .rev()
+                        .map_while(|token| match token {
+                            Token::Number(n) => Some(*n as f64),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .rev()
+                        .collect();
+                    if op.starts_with('s') {
+                        graphics.fill = graphics.fill_space.to_rgb(&components);
+                    } else {
+                        graphics.stroke = graphics.stroke_space.to_rgb(&components);
+                    }
+                }
+                "re" => {
+                    if let Some([x, y, w, h]) = number_operands::<4>(tokens, i) {
+                        for corner in [(x, y), (x + w, y), (x, y + h), (x + w, y + h)] {
+                            path.add_point(transform(&graphics.ctm, corner));
+                        }
+                        if sink.raster.is_some() {
+                            let to_page = multiply(&graphics.ctm, &sink.display);
+                            let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+                                .map(|corner| transform(&to_page, corner));
+                            outline.push(PathSegment::MoveTo(corners[0]));
+                            outline.extend(corners[1..].iter().map(|&c| PathSegment::LineTo(c)));
+                            outline.push(PathSegment::Close);
+                            current_point = corners[0];
+                        }
+                    }
+                }
+                "m" | "l" | "c" | "v" | "y" => {
+                    let count = match op.as_str() {
+                        "m" | "l" => 2,
+                        "c" => 6,
+                        _ => 4,
+                    };
+                    let points: Vec<f64> = tokens[i.saturating_sub(count)..i]
+                        .iter()
+                        .filter_map(|token| match token {
+                            Token::Number(n) => Some(*n as f64),
+                            _ => None,
+                        })
+                        .collect();
+                    // Control points keep the box conservative for curves.
+                    for point in points.chunks_exact(2) {
+                        path.add_point(transform(&graphics.ctm, (point[0], point[1])));
+                    }
+                    if sink.raster.is_some() && points.len() == count {
+                        let to_page = multiply(&graphics.ctm, &sink.display);
+                        let p: Vec<(f64, f64)> = points
+                            .chunks_exact(2)
+                            .map(|point| transform(&to_page, (point[0], point[1])))
+                            .collect();
+                        let segment = match op.as_str() {
+                            "m" => PathSegment::MoveTo(p[0]),
+                            "l" => PathSegment::LineTo(p[0]),
+                            "c" => PathSegment::CubicTo(p[0], p[1], p[2]),
+                            "v" => PathSegment::CubicTo(current_point, p[0], p[1]),
+                            _ => PathSegment::CubicTo(p[0], p[1], p[1]),
+                        };
+                        outline.push(segment);
+                        current_point = p[p.len() - 1];
+                    }
+                }
+                "h" => outline.push(PathSegment::Close),
+                "W" | "W*" => path.clip_pending = true,
+                "n" | "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => {
+                    if sink.hidden_layer_depth == 0 {
+                        if let Some(raster) = &mut sink.raster {
+                            paint_path(raster, op, &mut outline, &graphics, &sink.display);
+                        }
+                    }
+                    outline.clear();
+                    let clips = path.clip_pending;
+                    if let Some(bbox) = path.take() {
+                        if !matches!(op.as_str(), "n" | "S" | "s") && sink.hidden_layer_depth == 0 {
+                            let painted = match graphics.clip {
+                                Some(clip) => intersect(&clip, &bbox),
+                                None => bbox,
+                            };
+                            sink.canvas.paint(painted, graphics.fill);
+                        }
+                        // The new clip applies only after the path is painted.
+                        if clips {
+                            graphics.clip_to(bbox);
+                        }
+                    }
+                }
+                "sh" if sink.hidden_layer_depth == 0 => {
+                    if let Some(region) = graphics.clip.or(sink.crop_box) {
+                        sink.canvas.paint(region, None);
+                    }
+                }
+                "w" => {
+                    if let Some([width]) = number_operands::<1>(tokens, i) {
+                        graphics.line_width = width;
+                    }
+                }
+                "gs" => {
+                    if let Some(Token::Name(name)) = i.checked_sub(1).map(|p| &tokens[p]) {
+                        graphics.apply_ext_gstate(name, resources, objects);
+                    }
+                }
+                "Tr" => {
+                    if let Some([mode]) = number_operands::<1>(tokens, i) {
+                        graphics.render_mode = mode as u8;
+                    }
+                }
+                "BMC" => marked_content.push(false),
+                "BDC" => {
+                    let operand = |back: usize| i.checked_sub(back).map(|p| &tokens[p]);
+                    let hides = match (operand(2), operand(1)) {
+                        (Some(Token::Name(tag)), Some(Token::Name(name))) if tag == "OC" => {
+                            property_list(resources, name, objects)
+                                .is_some_and(|ocg| !is_visible(ocg, sink.hidden_layers, objects))
+                        }
+                        _ => false,
+                    };
+                    if hides {
+                        sink.hidden_layer_depth += 1;
+                    }
+                    marked_content.push(hides);
+                }
+                "EMC" => {
+                    let closes_hidden = marked_content.pop() == Some(true);
+                    sink.hidden_layer_depth -= usize::from(closes_hidden);
+                }
                 "BT" => {
                     // Begin Text Object
                     in_text = true;
+                    state.begin_text();
                 }
                 "ET" => {
                     // End Text Object
                     in_text = false;
                     current_font = None;
-                    output.push('\n');
+                    last_end = None;
+                    sink.output.push('\n');
                 }
                 "Tf" => {
                     // Set text font+size: /F1 12 Tf
@@ -772,18 +1760,60 @@ fn extract_from_tokens(
                                 // Font not found in resources
                             }
                         }
+                        if let Token::Number(size) = &tokens[i - 1] {
+                            state.font_size = *size as f64;
+                        }
+                    }
+                }
+                "Tc" | "Tw" | "Tz" | "TL" => {
+                    if let Some(Token::Number(n)) = i.checked_sub(1).map(|p| &tokens[p]) {
+                        let n = *n as f64;
+                        match op.as_str() {
+                            "Tc" => state.char_spacing = n,
+                            "Tw" => state.word_spacing = n,
+                            "Tz" => state.horizontal_scale = n / 100.0,
+                            _ => state.leading = n,
+                        }
+                    }
+                }
+                "Tm" if in_text => {
+                    if let Some(matrix) = number_operands::<6>(tokens, i) {
+                        state.set_matrix(matrix);
                     }
                 }
                 "Tj" | "'" | "\"" if in_text => {
+                    if op != "Tj" {
+                        state.next_line();
+                    }
+                    if op == "\"" && i >= 3 {
+                        if let (Token::Number(aw), Token::Number(ac)) =
+                            (&tokens[i - 3], &tokens[i - 2])
+                        {
+                            state.word_spacing = *aw as f64;
+                            state.char_spacing = *ac as f64;
+                        }
+                    }
                     if let Some(font) = current_font {
                         // If `'` or `"` used, start a new line
                         if op != "Tj" {
-                            output.push('\n');
+                            sink.output.push('\n');
                         }
                         // The literal string to draw is immediately before the operator
                         if i >= 1 {
                             if let Token::String(bytes) = &tokens[i - 1] {
-                                output.push_str(&decode_bytes(bytes, font));
+                                if let Some(gap) =
+                                    separate_from_previous(&state, last_end, &mut sink.output)
+                                {
+                                    pending_gap = gap;
+                                }
+                                last_end = show_string(
+                                    &mut state,
+                                    &graphics,
+                                    bytes,
+                                    font,
+                                    sink,
+                                    &mut pending_gap,
+                                );
                             }
                         }
                     }
@@ -793,13 +1823,32 @@ fn extract_from_tokens(
                     if let Some(font) = current_font {
                         if i >= 1 {
                             if let Token::Array(arr) = &tokens[i - 1] {
+                                if let Some(gap) =
+                                    separate_from_previous(&state, last_end, &mut sink.output)
+                                {
+                                    pending_gap = gap;
+                                }
                                 for elem in arr {
                                     match elem {
                                         Token::String(bytes) => {
-                                            output.push_str(&decode_bytes(bytes, font));
+                                            last_end = show_string(
+                                                &mut state,
+                                                &graphics,
+                                                bytes,
+                                                font,
+                                                sink,
+                                                &mut pending_gap,
+                                            );
                                         }
-                                        Token::Number(n) if *n < -200.0 => {
-                                            output.push(' ');
+                                        Token::Number(n) => {
+                                            // Adjustments are in thousandths of an em.
+                                            pending_gap -= *n as f64 / 1000.0;
+                                            state.advance(
+                                                -(*n as f64) / 1000.0
+                                                    * state.font_size
+                                                    * state.horizontal_scale,
+                                            );
+                                            last_end = last_end.map(|_| state.origin());
                                         }
                                         _ => {}
                                     }
@@ -810,7 +1859,8 @@ fn extract_from_tokens(
                 }
                 "T*" if in_text => {
                     // Move to next line
-                    output.push('\n');
+                    state.next_line();
+                    sink.output.push('\n');
                 }
                 "Td" | "TD" if in_text => {
                     // `Td`/`TD` moves the text position. When the vertical
@@ -819,11 +1869,15 @@ fn extract_from_tokens(
                     // for individual glyphs. Only insert a newline when the
                     // second operand (Ty) is not zero.
                     if i >= 2 {
-                        if let (Token::Number(_tx), Token::Number(ty)) =
+                        if let (Token::Number(tx), Token::Number(ty)) =
                             (&tokens[i - 2], &tokens[i - 1])
                         {
+                            if op == "TD" {
+                                state.leading = -*ty as f64;
+                            }
+                            state.move_line(*tx as f64, *ty as f64);
                             if *ty != 0.0 {
-                                output.push('\n');
+                                sink.output.push('\n');
                             }
                         }
                     }
@@ -873,12 +1927,32 @@ fn extract_from_tokens(
                                             };
 
                                         if let Some(xf) = form_stream_data {
+                                            let layer_hidden =
+                                                xf.dict.get("OC").is_some_and(|oc| {
+                                                    !is_visible(oc, sink.hidden_layers, objects)
+                                                });
                                             let subtype =
                                                 xf.dict.get("Subtype").and_then(|v| match v {
                                                     PdfObj::Name(name) => Some(name.as_str()),
                                                     _ => None,
                                                 });
 
+                                            if subtype == Some("Image")
+                                                && !layer_hidden
+                                                && sink.hidden_layer_depth == 0
+                                            {
+                                                let matrix = paint_image(sink, &graphics);
+                                                let image = xobject_image(
+                                                    xobj_name_from_token,
+                                                    xf,
+                                                    matrix,
+                                                    resources,
+                                                    objects,
+                                                );
+                                                draw_image(sink, &image, &graphics);
+                                                sink.images.push(image);
+                                            }
+
                                             if subtype == Some("Form") {
                                                 let form_specific_resources: &HashMap<
                                                     String,
@@ -951,14 +2025,21 @@ fn extract_from_tokens(
                                                             Err(_e) => HashMap::new(),
                                                         };
 
+                                                    if layer_hidden {
+                                                        sink.hidden_layer_depth += 1;
+                                                    }
                                                     extract_from_tokens(
                                                         &nested_tokens,
                                                         &form_fonts,
                                                         form_specific_resources,
-                                                        output,
+                                                        sink,
                                                         objects,
                                                         visited,
+                                                        form_graphics(xf, &graphics, objects),
                                                     );
+                                                    if layer_hidden {
+                                                        sink.hidden_layer_depth -= 1;
+                                                    }
 
                                                     if let Some(id_visited) =
                                                         object_id_for_visited_check
@@ -976,9 +2057,335 @@ fn extract_from_tokens(
                 }
                 _ => {}
             }
+        } else if let Token::InlineImage { params, data } = &tokens[i] {
+            if sink.hidden_layer_depth == 0 {
+                let matrix = paint_image(sink, &graphics);
+                let image = inline_image(params, data, matrix, resources, objects);
+                draw_image(sink, &image, &graphics);
+                sink.images.push(image);
+            }
         }
         i += 1;
     }
+    // Sections left open at the end of the stream close with it.
+    sink.hidden_layer_depth = hidden_layer_depth;
+}
+
+// Images paint the unit square in colours that aren't known. Returns the
+// matrix mapping the unit square onto the displayed page.
+fn paint_image(sink: &mut PageSink, graphics: &GraphicsState) -> [f64; 6] {
+    let bounds = transform_rect(&graphics.ctm, &[0.0, 0.0, 1.0, 1.0]);
+    sink.canvas.paint(bounds, None);
+    multiply(&graphics.ctm, &sink.display)
+}
+
+// Fills and strokes the path under construction as the painting operator
+// `op` asks; `n` only ends it.
+fn paint_path(
+    raster: &mut Raster,
+    op: &str,
+    outline: &mut Vec<PathSegment>,
+    graphics: &GraphicsState,
+    display: &[f64; 6],
+) {
+    if matches!(op, "s" | "b" | "b*") {
+        outline.push(PathSegment::Close);
+    }
+    let clip = graphics.clip.map(|clip| transform_rect(display, &clip));
+    if matches!(op, "f" | "F" | "f*" | "B" | "B*" | "b" | "b*") {
+        let color = graphics.fill.unwrap_or(UNKNOWN_PAINT);
+        raster.fill(outline, op.ends_with('*'), color, 1.0, clip);
+    }
+    if matches!(op, "S" | "s" | "B" | "B*" | "b" | "b*") {
+        let color = graphics.stroke.unwrap_or(UNKNOWN_PAINT);
+        raster.stroke(outline, line_width(graphics), color, clip);
+    }
+}
+
+// The line width on the displayed page, scaled by the CTM.
+fn line_width(graphics: &GraphicsState) -> f64 {
+    let m = &graphics.ctm;
+    graphics.line_width * (m[0] * m[3] - m[1] * m[2]).abs().sqrt()
+}
+
+// Draws an image when rendering; images in codecs that can't be decoded here
+// are drawn as gray boxes.
+fn draw_image(sink: &mut PageSink, image: &PageImage, graphics: &GraphicsState) {
+    let Some(raster) = &mut sink.raster else {
+        return;
+    };
+    let clip = graphics
+        .clip
+        .map(|clip| transform_rect(&sink.display, &clip));
+    match rgb_pixels(image) {
+        Some((width, height, rgb)) => {
+            let mask_color = image
+                .image_mask
+                .then(|| graphics.fill.unwrap_or(UNKNOWN_PAINT));
+            raster.draw_image(&image.matrix, width, height, &rgb, mask_color, clip);
+        }
+        None => {
+            let square = rect_path(&image.matrix, 1.0, 1.0);
+            raster.fill(&square, false, UNKNOWN_PAINT, 1.0, clip);
+        }
+    }
+}
+
+// The rectangle from the origin to `(width, height)`, transformed by `m`.
+fn rect_path(m: &[f64; 6], width: f64, height: f64) -> Vec<PathSegment> {
+    let corners = [(0.0, height), (width, height), (width, 0.0)];
+    let mut path = vec![PathSegment::MoveTo(transform(m, (0.0, 0.0)))];
+    path.extend(corners.map(|corner| PathSegment::LineTo(transform(m, corner))));
+    path.push(PathSegment::Close);
+    path
+}
+
+// The last `N` operands before the operator at `i`, when all are numbers.
+fn number_operands<const N: usize>(tokens: &[Token], i: usize) -> Option<[f64; N]> {
+    let operands = tokens.get(i.checked_sub(N)?..i)?;
+    let mut numbers = [0.0; N];
+    for (number, token) in numbers.iter_mut().zip(operands) {
+        match token {
+            Token::Number(n) => *number = *n as f64,
+            _ => return None,
+        }
+    }
+    Some(numbers)
+}
+
+// Looks up a named property list in the `/Properties` resources.
+fn property_list<'a>(
+    resources: &'a HashMap<String, PdfObj>,
+    name: &str,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+) -> Option<&'a PdfObj> {
+    match resolve(resources.get("Properties")?, objects) {
+        PdfObj::Dictionary(properties) => properties.get(name),
+        _ => None,
+    }
+}
+
+// Graphics state a Form XObject starts with: the caller's, with the form's
+// `/Matrix` prepended and its `/BBox` clipping.
+fn form_graphics(
+    form: &PdfStream,
+    graphics: &GraphicsState,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> GraphicsState {
+    let numbers = |key: &str| -> Vec<f64> {
+        match form.dict.get(key).map(|v| resolve(v, objects)) {
+            Some(PdfObj::Array(items)) => items
+                .iter()
+                .filter_map(|item| match resolve(item, objects) {
+                    PdfObj::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    let mut graphics = graphics.clone();
+    if let Ok(matrix) = <[f64; 6]>::try_from(numbers("Matrix")) {
+        graphics.concat(&matrix);
+    }
+    if let Ok(bbox) = <[f64; 4]>::try_from(numbers("BBox")) {
+        graphics.clip_to(transform_rect(&graphics.ctm, &bbox));
+    }
+    graphics
+}
+
+// Gap above which two glyphs are taken to be separate words, in ems. Between
+// two CJK characters only a gap of most of an em counts as a visible space.
+const WORD_GAP: f64 = 0.2;
+const CJK_GAP: f64 = 0.8;
+
+// Compares where a string is drawn with where the previous one ended. A string
+// on another baseline starts a new line; otherwise the horizontal gap in ems is
+// returned. `None` when the previous end position is unknown.
+fn separate_from_previous(
+    state: &TextState,
+    last_end: Option<(f64, f64)>,
+    output: &mut String,
+) -> Option<f64> {
+    let end = last_end?;
+    let (along, across) = state.offset_from(end);
+    let size = state.scaled_font_size();
+    if size <= 0.0 {
+        return None;
+    }
+    if across.abs() > size * 0.5 {
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+        return Some(0.0);
+    }
+    Some(along / size)
+}
+
+// Appends decoded text, preceded by a space when the gap before it is wide
+// enough for the scripts on either side, and consumes the gap.
+fn push_text(output: &mut String, text: &str, gap: &mut f64) {
+    let Some(next) = text.chars().next() else {
+        return;
+    };
+    if let Some(prev) = output.chars().next_back() {
+        let threshold = if is_unspaced_script(prev) && is_unspaced_script(next) {
+            CJK_GAP
+        } else {
+            WORD_GAP
+        };
+        if *gap > threshold && !prev.is_whitespace() && !next.is_whitespace() {
+            output.push(' ');
+        }
+    }
+    *gap = 0.0;
+    output.push_str(text);
+}
+
+// Points across a glyph whose surroundings decide whether it is seen, as
+// fractions of its advance and heights above the baseline in ems.
+const GLYPH_SAMPLES: [(f64, f64); 5] = [(0.5, 0.3), (0.2, 0.1), (0.8, 0.1), (0.2, 0.6), (0.8, 0.6)];
+
+// Shows a string glyph by glyph: records each glyph with the reasons it would
+// be hidden, appends its text unless it is hidden and only visible text is
+// wanted, and advances the text matrix. Returns where the string ended, or
+// `None` when the font's widths don't cover it.
+fn show_string(
+    state: &mut TextState,
+    graphics: &GraphicsState,
+    bytes: &[u8],
+    font: &PdfFont,
+    sink: &mut PageSink,
+    pending_gap: &mut f64,
+) -> Option<(f64, f64)> {
+    let mut widths_known = true;
+    for (code, mut text, mut source) in decode_glyphs(bytes, font) {
+        if let Some(database) = sink.glyph_fingerprints {
+            if source.is_guessed() || text.is_empty() {
+                if let Some(recognised) = recognise(font, code, database) {
+                    text = recognised;
+                    source = CharSource::GlyphFingerprint;
+                }
+            }
+        }
+        let width = state.glyph_width(code, font);
+        widths_known &= width.is_some();
+        let em = state.font_size * state.horizontal_scale;
+        let advance = width.unwrap_or(0.0);
+        if sink.hidden_layer_depth == 0 {
+            if let Some(raster) = &mut sink.raster {
+                draw_glyph(raster, state, graphics, font, code, &text, &sink.display);
+            }
+        }
+        if !text.is_empty() {
+            let rendering = multiply(&state.matrix, &graphics.ctm);
+            let (x, y) = transform(&sink.display, transform(&rendering, (0.0, 0.0)));
+            let size = (state.font_size * rendering[2].hypot(rendering[3]))
+                .min(em.abs() * rendering[0].hypot(rendering[1]));
+            let extent = width.unwrap_or(em * 0.5);
+            let samples = GLYPH_SAMPLES
+                .map(|(across, up)| transform(&rendering, (extent * across, state.font_size * up)));
+            let mut hidden = hidden_reasons(graphics, &sink.canvas, sink.crop_box, &samples, size);
+            let layer_hidden = sink.hidden_layer_depth > 0;
+            if layer_hidden {
+                hidden.push(HiddenReason::OptionalContentOff);
+            }
+            let cropped = sink.crop_to_page && hidden.contains(&HiddenReason::OutsideCropBox);
+            let shown = if sink.visible_only {
+                hidden.is_empty()
+            } else {
+                (!layer_hidden || sink.include_hidden_layers) && !cropped
+            };
+            let glyph = TextGlyph {
+                text,
+                x,
+                y,
+                size,
+                hidden,
+                source,
+            };
+            // Without a width every glyph of a string shares one position, so
+            // overprints can only be told apart when the width is known.
+            let overprint = shown
+                && width.is_some()
+                && sink
+                    .overprints
+                    .overprints(font.base_name.as_deref(), code, x, y, size);
+            if overprint {
+                sink.diagnostics.collapsed_overprints.push(glyph);
+            } else {
+                if shown {
+                    push_text(&mut sink.output, &glyph.text, pending_gap);
+                    let end = sink.output.len();
+                    let start = end - glyph.text.len();
+                    sink.text_sources.push((start, end, source));
+                } else if em != 0.0 {
+                    // The skipped glyph still leaves its space behind.
+                    *pending_gap += advance / em;
+                }
+                sink.glyphs.push(glyph);
+            }
+        }
+        state.advance(advance);
+    }
+    widths_known.then(|| state.origin())
+}
+
+// Draws one shown glyph as the text rendering mode asks: its outline from the
+// embedded font program, or a faint box the size of the glyph when the font
+// has none.
+fn draw_glyph(
+    raster: &mut Raster,
+    state: &TextState,
+    graphics: &GraphicsState,
+    font: &PdfFont,
+    code: u32,
+    text: &str,
+    display: &[f64; 6],
+) {
+    let (fills, strokes) = match graphics.render_mode {
+        0 | 4 => (true, false),
+        1 | 5 => (false, true),
+        2 | 6 => (true, true),
+        _ => return,
+    };
+    let size = [
+        state.font_size * state.horizontal_scale,
+        0.0,
+        0.0,
+        state.font_size,
+        0.0,
+        0.0,
+    ];
+    let text_to_page = multiply(&state.matrix, &multiply(&graphics.ctm, display));
+    let to_page = multiply(&size, &text_to_page);
+    let clip = graphics.clip.map(|clip| transform_rect(display, &clip));
+    let outline = font
+        .program
+        .as_ref()
+        .and_then(|program| glyph_outline(program, *program.glyph_ids.get(&code)?));
+    let Some(outline) = outline else {
+        if !text.trim().is_empty() {
+            let width = font.advance(code).unwrap_or(0.5);
+            let box_path = rect_path(&to_page, width, 0.7);
+            let color = graphics.fill.unwrap_or(UNKNOWN_PAINT);
+            raster.fill(&box_path, false, color, 0.3, clip);
+        }
+        return;
+    };
+    let glyph_to_page = multiply(&font.font_matrix, &to_page);
+    let path: Vec<PathSegment> = outline
+        .iter()
+        .map(|segment| segment.transformed(&glyph_to_page))
+        .collect();
+    if fills {
+        let color = graphics.fill.unwrap_or(UNKNOWN_PAINT);
+        raster.fill(&path, false, color, 1.0, clip);
+    }
+    if strokes {
+        let color = graphics.stroke.unwrap_or(UNKNOWN_PAINT);
+        raster.stroke(&path, line_width(graphics), color, clip);
+    }
 }
 
 #[cfg(test)]
@@ -998,6 +2405,1229 @@ mod extractor_tests {
             Err(e) => panic!("Failed to extract PDF text: {:?}", e),
         }
     }
+
+    // Builds a PDF whose objects are numbered from 1 in the order given;
+    // object 1 must be the catalog.
+    fn build_pdf(objects: &[String]) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.7\n".to_vec();
+        for (i, body) in objects.iter().enumerate() {
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, body).bytes());
+        }
+        pdf.extend(b"trailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        pdf
+    }
+
+    fn stream(dict: &str, data: &str) -> String {
+        format!(
+            "<< {} /Length {} >>\nstream\n{}\nendstream",
+            dict,
+            data.len(),
+            data
+        )
+    }
+
+    #[test]
+    fn type3_fonts_decode_charproc_names_and_space_by_widths() {
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> \
+             /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type3 /FontMatrix [0.01 0 0 0.01 0 0] \
+             /FontBBox [0 0 50 50] /FirstChar 65 /LastChar 67 /Widths [50 50 50] \
+             /Encoding << /Differences [65 /g41 /C66 /blank] >> \
+             /CharProcs << /g41 6 0 R /C66 6 0 R >> >>"
+                .to_string(),
+            stream("", "BT /F1 12 Tf (AB) Tj 20 0 Td (AC) Tj ET"),
+            stream("", "50 0 d0"),
+        ]);
+        let pages = super::extract_text(pdf).unwrap();
+        assert_eq!(pages[0], "AB A");
+    }
+
+    #[test]
+    fn cjk_text_joins_without_spurious_spaces() {
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> \
+             /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding << /Differences \
+             [1 /uni4EBA /uni6C11 /uni5E01 /uni91D1 /uni989D /uni3002] >> >>"
+                .to_string(),
+            stream(
+                "",
+                "BT /F1 10 Tf [(\\001) -300 (\\002\\003)] TJ 0 -12 Td (\\004\\005\\006) Tj \
+                 0 -12 Td [(\\001) -1000 (\\002)] TJ 0 -12 Td [(A) -300 (B)] TJ ET",
+            ),
+        ]);
+        let pages = super::extract_text(pdf).unwrap();
+        assert_eq!(pages[0], "人民币金额。\n人 民\nA B");
+    }
+
+    #[test]
+    fn hidden_glyphs_are_marked_and_left_out_of_visible_text() {
+        use crate::types::{ExtractOptions, HiddenReason};
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+             /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            stream(
+                "",
+                "BT /F1 12 Tf 10 180 Td (Shown) Tj ET \
+                 q BT /F1 12 Tf 3 Tr 10 160 Td (Mode) Tj ET Q \
+                 1 g BT /F1 12 Tf 10 140 Td (White) Tj ET \
+                 0 0 1 rg 0 0 200 40 re f BT /F1 12 Tf 10 20 Td (Blue) Tj ET \
+                 0 g BT /F1 12 Tf 300 100 Td (Off) Tj ET \
+                 q 0 0 50 50 re W n BT /F1 12 Tf 100 120 Td (Clip) Tj ET Q \
+                 BT /F1 0.01 Tf 10 100 Td (Tiny) Tj ET",
+            ),
+        ]);
+        let glyphs = super::extract_glyphs(pdf.clone()).unwrap();
+        let visible: String = glyphs[0]
+            .iter()
+            .filter(|glyph| glyph.is_visible())
+            .map(|glyph| glyph.text.as_str())
+            .collect();
+        assert_eq!(visible, "Shown");
+        let first = |text: &str| {
+            let glyph = glyphs[0].iter().find(|glyph| glyph.text == text).unwrap();
+            glyph.hidden.clone()
+        };
+        assert_eq!(first("M"), [HiddenReason::InvisibleRenderMode]);
+        assert_eq!(first("W"), [HiddenReason::SameColorAsBackground]);
+        assert_eq!(first("B"), [HiddenReason::SameColorAsBackground]);
+        assert_eq!(first("O"), [HiddenReason::OutsideCropBox]);
+        assert_eq!(first("C"), [HiddenReason::Clipped]);
+        assert_eq!(first("T"), [HiddenReason::ZeroScale]);
+
+        let all = super::extract_text(pdf.clone()).unwrap();
+        assert_eq!(all[0], "Shown\nMode\nWhite\nBlue\nOff\nClip\nTiny");
+        let options = ExtractOptions {
+            visible_only: true,
+            ..ExtractOptions::default()
+        };
+        let visible = super::extract_text_with_options(pdf, &options).unwrap();
+        assert_eq!(visible[0], "Shown");
+    }
+
+    #[test]
+    fn glyphs_that_cannot_be_judged_seen_are_marked_hidden() {
+        use crate::types::HiddenReason;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] \
+             /Resources << /Font << /F1 4 0 R >> \
+             /ColorSpace << /Spot [/Separation /Gold /DeviceCMYK 6 0 R] >> \
+             /ExtGState << /Clear << /ca 0 >> /Masked << /SMask << /S /Luminosity >> >> \
+             /Unmasked << /SMask /None >> >> /XObject << /Im 7 0 R >> >> \
+             /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+             /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            stream(
+                "",
+                "BT /F1 12 Tf 10 180 Td (Shown) Tj ET \
+                 q /Spot cs 1 scn BT /F1 12 Tf 10 160 Td (Spot) Tj ET Q \
+                 q /Pattern cs /P0 scn BT /F1 12 Tf 10 140 Td (Pattern) Tj ET Q \
+                 q /Clear gs BT /F1 12 Tf 10 120 Td (Alpha) Tj ET Q \
+                 q /Masked gs BT /F1 12 Tf 10 100 Td (Mask) Tj ET \
+                 /Unmasked gs BT /F1 12 Tf 10 80 Td (Unmasked) Tj ET Q \
+                 0 65 200 15 re f BT /F1 12 Tf 10 60 Td (Covered) Tj ET \
+                 q 0 0 200 44 re W n BT /F1 12 Tf 10 40 Td (Edge) Tj ET Q \
+                 q 200 0 0 30 0 10 cm /Im Do Q \
+                 1 g BT /F1 12 Tf 10 18 Td (Image) Tj ET",
+            ),
+            "<< /FunctionType 2 /Domain [0 1] /N 1 >>".to_string(),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 1 /Height 1 \
+                 /ColorSpace /DeviceGray /BitsPerComponent 8",
+                "0",
+            ),
+        ]);
+        let glyphs = super::extract_glyphs(pdf).unwrap();
+        let visible: String = glyphs[0]
+            .iter()
+            .filter(|glyph| glyph.is_visible())
+            .map(|glyph| glyph.text.as_str())
+            .collect();
+        assert_eq!(visible, "ShownUnmasked");
+        let first = |text: &str| {
+            let glyph = glyphs[0].iter().find(|glyph| glyph.text == text).unwrap();
+            glyph.hidden.clone()
+        };
+        assert_eq!(first("S"), []);
+        assert_eq!(first("p"), [HiddenReason::UnknownPaint]);
+        assert_eq!(first("P"), [HiddenReason::UnknownPaint]);
+        assert_eq!(first("A"), [HiddenReason::Transparent]);
+        assert_eq!(first("M"), [HiddenReason::SoftMasked]);
+        // Only the tops of these glyphs are covered or clipped.
+        assert_eq!(first("C"), [HiddenReason::SameColorAsBackground]);
+        assert_eq!(first("E"), [HiddenReason::Clipped]);
+        // White text over an image whose colours aren't tracked.
+        assert_eq!(first("I"), [HiddenReason::UnknownBackground]);
+    }
+
+    #[test]
+    fn content_in_layers_that_are_off_is_left_out() {
+        use crate::types::{ExtractOptions, HiddenReason};
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /OCProperties << /OCGs [6 0 R 7 0 R] \
+             /D << /OFF [6 0 R] >> >> >>"
+                .to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> \
+             /Properties << /W 6 0 R /V 7 0 R /M 8 0 R >> /XObject << /X 9 0 R >> >> \
+             /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            stream(
+                "",
+                "/OC /W BDC BT /F1 12 Tf 10 180 Td (Watermark) Tj ET EMC \
+                 /OC /V BDC BT /F1 12 Tf 10 160 Td (Body) Tj ET EMC \
+                 /OC /M BDC BT /F1 12 Tf 10 140 Td (Shown) Tj ET EMC /X Do",
+            ),
+            "<< /Type /OCG /Name (Watermark) >>".to_string(),
+            "<< /Type /OCG /Name (Visible) >>".to_string(),
+            "<< /Type /OCMD /VE [/And [/Not 6 0 R] 7 0 R] >>".to_string(),
+            stream(
+                "/Type /XObject /Subtype /Form /BBox [0 0 200 200] /OC 6 0 R \
+                 /Resources << /Font << /F1 4 0 R >> >>",
+                "BT /F1 12 Tf 10 120 Td (Form) Tj ET",
+            ),
+        ]);
+        let pages = super::extract_text(pdf.clone()).unwrap();
+        assert_eq!(pages[0], "Body\nShown");
+
+        let options = ExtractOptions {
+            include_hidden_layers: true,
+            ..ExtractOptions::default()
+        };
+        let all = super::extract_text_with_options(pdf.clone(), &options).unwrap();
+        assert_eq!(all[0], "Watermark\nBody\nShown\nForm");
+
+        let glyphs = super::extract_glyphs(pdf).unwrap();
+        let hidden: String = glyphs[0]
+            .iter()
+            .filter(|glyph| glyph.hidden == [HiddenReason::OptionalContentOff])
+            .map(|glyph| glyph.text.as_str())
+            .collect();
+        assert_eq!(hidden, "WatermarkForm");
+    }
+
+    #[test]
+    fn page_boxes_and_rotation_are_inherited_from_the_page_tree() {
+        use crate::types::ExtractOptions;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 200 100] /Rotate -270 \
+             /Resources << /Font << /F1 4 0 R >> >> >>"
+                .to_string(),
+            "<< /Type /Page /Parent 2 0 R /CropBox [0 0 150 300] /Contents 5 0 R >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            stream(
+                "",
+                "BT /F1 12 Tf 10 20 Td (In) Tj ET BT /F1 12 Tf 170 20 Td (Out) Tj ET",
+            ),
+        ]);
+        let (pages, _) = super::parse_pdf(&pdf).unwrap();
+        assert_eq!(pages[0].media_box, Some([0.0, 0.0, 200.0, 100.0]));
+        assert_eq!(pages[0].crop_box, Some([0.0, 0.0, 150.0, 100.0]));
+        assert_eq!(pages[0].rotate, 90);
+
+        let glyphs = super::extract_glyphs(pdf.clone()).unwrap();
+        assert_eq!((glyphs[0][0].x, glyphs[0][0].y), (20.0, 140.0));
+
+        assert_eq!(super::extract_text(pdf.clone()).unwrap()[0], "In\nOut");
+        let options = ExtractOptions {
+            crop_to_page: true,
+            ..ExtractOptions::default()
+        };
+        let cropped = super::extract_text_with_options(pdf, &options).unwrap();
+        assert_eq!(cropped[0], "In");
+    }
+
+    #[test]
+    fn rotated_pages_keep_reading_order_and_line_breaks() {
+        // Text turned a quarter turn in user space reads upright once the
+        // page is turned by /Rotate 90.
+        let page = |rotate: u16| {
+            build_pdf(&[
+                "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 300] /Rotate {} \
+                     /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>",
+                    rotate
+                ),
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+                     /FirstChar 32 /LastChar 119 /Widths [{}] >>",
+                    "600 ".repeat(88)
+                ),
+                stream(
+                    "",
+                    "BT /F1 12 Tf 0 1 -1 0 150 20 Tm (Hello) Tj ( world) Tj \
+                     0 -14 Td (Next) Tj ET",
+                ),
+            ])
+        };
+        let rotated = page(90);
+        for pdf in [rotated.clone(), page(0)] {
+            assert_eq!(super::extract_text(pdf).unwrap()[0], "Hello world\nNext");
+        }
+
+        // On the displayed page the first line runs left to right above the
+        // second.
+        let glyphs = super::extract_glyphs(rotated).unwrap();
+        let (h, e) = (&glyphs[0][0], &glyphs[0][1]);
+        assert_eq!((h.text.as_str(), h.x, h.y), ("H", 20.0, 50.0));
+        assert!(e.x > h.x && (e.y - h.y).abs() < 1e-9);
+        let n = glyphs[0].iter().find(|glyph| glyph.text == "N").unwrap();
+        assert!(n.y < h.y && (n.x - h.x).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fake_bold_overprints_are_collapsed_and_reported() {
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> \
+             /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /SimSun /FirstChar 1 /LastChar 2 \
+             /Widths [1000 1000] /Encoding << /Differences [1 /uni4ED8 /uni6B3E] >> >>"
+                .to_string(),
+            stream(
+                "",
+                "BT /F1 10 Tf 10 100 Td (\\001\\002) Tj 0.3 0 Td (\\001\\002) Tj ET \
+                 BT /F1 10 Tf 10 80 Td [(\\001) 970 (\\001) 30 (\\002) 970 (\\002)] TJ ET \
+                 BT /F1 10 Tf 10 60 Td (\\001\\001) Tj ET",
+            ),
+        ]);
+        let pages = super::extract_text_with_diagnostics(pdf, &Default::default()).unwrap();
+        let (text, diagnostics) = &pages[0];
+        // Wrapped CJK lines join without a break.
+        assert_eq!(text, "付款付款付付");
+        let collapsed: String = diagnostics
+            .collapsed_overprints
+            .iter()
+            .map(|glyph| glyph.text.as_str())
+            .collect();
+        assert_eq!(collapsed, "付款付款");
+    }
+
+    #[test]
+    fn annotation_appearances_and_contents_are_extracted() {
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 300 300] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R \
+             /Annots [6 0 R 8 0 R 9 0 R 11 0 R] >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            stream("", "BT /F1 12 Tf 10 280 Td (Body) Tj ET"),
+            "<< /Type /Annot /Subtype /Stamp /Rect [200 150 100 100] /Contents (Stamp) \
+             /AP << /N 7 0 R >> >>"
+                .to_string(),
+            stream(
+                "/Type /XObject /Subtype /Form /BBox [0 0 50 25] \
+                 /Resources << /Font << /F1 4 0 R >> >>",
+                "BT /F1 10 Tf 2 5 Td (APPROVED) Tj ET",
+            ),
+            "<< /Type /Annot /Subtype /Text /Rect [10 10 30 30] \
+             /Contents <FEFF5DF27B7E6536> >>"
+                .to_string(),
+            "<< /Type /Annot /Subtype /FreeText /Rect [10 40 110 60] /Contents (Note) \
+             /AP << /N << /On 10 0 R >> >> /AS /On >>"
+                .to_string(),
+            stream(
+                "/Type /XObject /Subtype /Form /BBox [0 0 100 20]",
+                "BT /F1 10 Tf 2 5 Td (Note) Tj ET",
+            ),
+            "<< /Type /Annot /Subtype /Text /F 2 /Rect [0 0 1 1] /Contents (Secret) >>".to_string(),
+        ]);
+        let pages = super::extract_text(pdf.clone()).unwrap();
+        assert_eq!(pages[0], "Body\nAPPROVED\n已签收\nNote");
+
+        let glyphs = super::extract_glyphs(pdf).unwrap();
+        let approved = glyphs[0].iter().find(|glyph| glyph.text == "A").unwrap();
+        assert_eq!((approved.x, approved.y), (104.0, 110.0));
+        assert_eq!(approved.size, 20.0);
+    }
+
+    #[test]
+    fn form_fields_have_qualified_names_and_decoded_values() {
+        use crate::types::{FieldKind, FieldValue};
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 8 0 R] >> >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Contents 9 0 R >>".to_string(),
+            "<< /T (applicant) /FT /Tx /Kids [5 0 R 6 0 R 7 0 R] >>".to_string(),
+            "<< /T (name) /TU (Full name) /V <FEFF5F204E09> /Parent 4 0 R >>".to_string(),
+            "<< /T (city) /V (Caf\\351) /Parent 4 0 R >>".to_string(),
+            "<< /T (agree) /FT /Btn /V /Yes /Parent 4 0 R \
+             /Kids [<< /Subtype /Widget /AS /Yes >>] >>"
+                .to_string(),
+            "<< /T (signature) /FT /Sig /V << /Type /Sig /Name (Jane Roe) >> >>".to_string(),
+            stream("", ""),
+        ]);
+        let fields = super::extract_form_fields(pdf).unwrap();
+        let summary: Vec<(&str, FieldKind, &FieldValue)> = fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.kind, &field.value))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "applicant.name",
+                    FieldKind::Text,
+                    &FieldValue::Text("张三".to_string())
+                ),
+                (
+                    "applicant.city",
+                    FieldKind::Text,
+                    &FieldValue::Text("Café".to_string())
+                ),
+                (
+                    "applicant.agree",
+                    FieldKind::Checkbox,
+                    &FieldValue::State("Yes".to_string())
+                ),
+                (
+                    "signature",
+                    FieldKind::Signature,
+                    &FieldValue::Signature {
+                        signer: Some("Jane Roe".to_string())
+                    }
+                ),
+            ]
+        );
+        assert_eq!(fields[0].alternate_name.as_deref(), Some("Full name"));
+    }
+
+    #[test]
+    fn xfa_datasets_are_reassembled_and_appended_to_text() {
+        use crate::types::ExtractOptions;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /NeedsRendering true \
+             /AcroForm << /Fields [] /XFA [(preamble) 4 0 R (datasets) 5 0 R \
+             (datasets) 6 0 R (postamble) 7 0 R] >> >>"
+                .to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Contents 8 0 R >>".to_string(),
+            stream("", "<xdp:xdp xmlns:xdp=\"http://ns.adobe.com/xdp/\">"),
+            stream(
+                "",
+                "<xfa:datasets xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\">\
+                 <xfa:data><form1><applicant><name>Jane &amp; John</name>",
+            ),
+            stream(
+                "",
+                "<note/></applicant><item><amount>10</amount></item>\
+                 <item><amount>25</amount></item><extra xfa:dataNode=\"dataGroup\"/>\
+                 </form1></xfa:data></xfa:datasets>",
+            ),
+            stream("", "</xdp:xdp>"),
+            stream("", ""),
+        ]);
+        let fields = super::extract_xfa_data(pdf.clone()).unwrap();
+        let summary: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.path.as_str(), field.value.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("form1.applicant.name", "Jane & John"),
+                ("form1.applicant.note", ""),
+                ("form1.item[0].amount", "10"),
+                ("form1.item[1].amount", "25"),
+            ]
+        );
+        assert_eq!(
+            super::extract_text(pdf.clone()).unwrap(),
+            ["form1.applicant.name: Jane & John\n\
+              form1.item[0].amount: 10\n\
+              form1.item[1].amount: 25"]
+        );
+
+        // The data is drawn nowhere, so visible text leaves it out.
+        let options = ExtractOptions {
+            visible_only: true,
+            ..ExtractOptions::default()
+        };
+        let visible = super::extract_text_with_options(pdf, &options).unwrap();
+        assert_eq!(visible, [""]);
+    }
+
+    #[test]
+    fn deeply_nested_xfa_and_xmp_packets_are_read_without_overflowing() {
+        let depth = 100_000;
+        let nested = format!("{}deep{}", "<a>".repeat(depth), "</a>".repeat(depth));
+        let xfa = format!(
+            "<xfa:datasets xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\">\
+             <xfa:data>{nested}</xfa:data></xfa:datasets>"
+        );
+        let xmp = format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF \
+             xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">{nested}\
+             <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+             <dc:title>Statement</dc:title></rdf:Description></rdf:RDF></x:xmpmeta>"
+        );
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /NeedsRendering true /Metadata 5 0 R \
+             /AcroForm << /XFA 4 0 R >> >>"
+                .to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            stream("", &xfa),
+            stream("/Type /Metadata /Subtype /XML", &xmp),
+        ]);
+
+        let text = super::extract_text(pdf.clone()).unwrap();
+        assert!(text[0].ends_with(": deep"), "{:?}", &text[0][..40]);
+        let metadata = super::extract_metadata(pdf).unwrap();
+        assert_eq!(metadata.xmp_value("dc:title"), Some("Statement"));
+    }
+
+    #[test]
+    fn metadata_reads_info_strings_dates_and_xmp() {
+        use crate::types::PdfDate;
+
+        let xmp = "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF \
+             xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+             <rdf:Description rdf:about=\"\" xmlns:xap=\"http://ns.adobe.com/xap/1.0/\" \
+             xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" xap:CreatorTool=\"Writer\">\
+             <xap:CreateDate>2024-03-05T10:20:30.25+08:00</xap:CreateDate>\
+             <pdf:Producer>Acme PDF 2.1</pdf:Producer></rdf:Description>\
+             <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+             <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">Statement</rdf:li></rdf:Alt>\
+             </dc:title><dc:creator><rdf:Seq><rdf:li>A</rdf:li><rdf:li>B</rdf:li></rdf:Seq>\
+             </dc:creator></rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end=\"w\"?>";
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /Metadata 4 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            stream("/Type /Metadata /Subtype /XML", xmp),
+            "<< /Title <FEFF001B006A0061001B8D2653558868> /Producer (Acme PDF 2.1) \
+             /Author (Ren\\351e) /CreationDate (D:20240305102030+08'00') \
+             /ModDate (D:2024) /Reviewer (QA) /Trapped /False >>"
+                .to_string(),
+        ]);
+        let text = String::from_utf8(pdf).unwrap();
+        let pdf = text.replace("<< /Root 1 0 R >>", "<< /Root 1 0 R /Info 5 0 R >>");
+        let metadata = super::extract_metadata(pdf.into_bytes()).unwrap();
+
+        let info = &metadata.info;
+        assert_eq!(info.title.as_deref(), Some("账单表"));
+        assert_eq!(info.author.as_deref(), Some("Renée"));
+        assert_eq!(info.producer.as_deref(), Some("Acme PDF 2.1"));
+        assert_eq!(info.custom, [("Reviewer".to_string(), "QA".to_string())]);
+        let created = info.creation_date.unwrap();
+        assert_eq!(
+            created,
+            PdfDate {
+                year: 2024,
+                month: 3,
+                day: 5,
+                hour: 10,
+                minute: 20,
+                second: 30,
+                utc_offset_minutes: Some(480),
+            }
+        );
+        assert_eq!(created.unix_timestamp(), 1_709_605_230);
+        assert_eq!(
+            info.mod_date.map(|d| (d.year, d.month, d.day)),
+            Some((2024, 1, 1))
+        );
+
+        assert_eq!(metadata.xmp_value("xmp:CreatorTool"), Some("Writer"));
+        assert_eq!(metadata.xmp_value("pdf:Producer"), Some("Acme PDF 2.1"));
+        assert_eq!(metadata.xmp_value("dc:title"), Some("Statement"));
+        let creators = metadata
+            .xmp
+            .iter()
+            .find(|p| p.name == "dc:creator")
+            .unwrap();
+        assert_eq!(creators.values, ["A", "B"]);
+        let xmp_created = super::parse_date(metadata.xmp_value("xmp:CreateDate").unwrap());
+        assert_eq!(xmp_created, Some(created));
+        assert_eq!(super::parse_date("D:2024133"), None);
+    }
+
+    #[test]
+    fn outline_destinations_and_labels_resolve_to_page_indices() {
+        use crate::types::{NamedDestination, OutlineItem};
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /Outlines 7 0 R /Dests << /old << /D [6 0 R /Fit] >> >> \
+             /Names << /Dests 11 0 R >> /PageLabels << /Nums [0 << /S /r >> \
+             2 << /S /D /P (A-) /St 5 >> 3 << /S /A /St 27 >>] >> >>"
+                .to_string(),
+            "<< /Type /Pages /Kids [3 0 R 4 0 R 5 0 R 6 0 R] /Count 4 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R >>".to_string(),
+            "<< /Type /Outlines /First 8 0 R /Last 10 0 R >>".to_string(),
+            "<< /Title (Summary) /Dest [3 0 R /XYZ 0 792 0] /First 9 0 R /Next 10 0 R >>"
+                .to_string(),
+            "<< /Title <FEFF660E7EC6> /A << /S /GoTo /D (chap2) >> >>".to_string(),
+            "<< /Title (Fees) /Dest /old >>".to_string(),
+            "<< /Kids [<< /Names [(chap2) [5 0 R /Fit] (gone) [99 0 R /Fit]] >>] >>".to_string(),
+        ]);
+        let navigation = super::extract_navigation(pdf).unwrap();
+
+        assert_eq!(
+            navigation.outline,
+            [
+                OutlineItem {
+                    title: "Summary".to_string(),
+                    page: Some(0),
+                    children: vec![OutlineItem {
+                        title: "明细".to_string(),
+                        page: Some(2),
+                        children: Vec::new(),
+                    }],
+                },
+                OutlineItem {
+                    title: "Fees".to_string(),
+                    page: Some(3),
+                    children: Vec::new(),
+                },
+            ]
+        );
+        let destination = |name: &str, page: Option<usize>| NamedDestination {
+            name: name.to_string(),
+            page,
+        };
+        assert_eq!(
+            navigation.destinations,
+            [
+                destination("old", Some(3)),
+                destination("chap2", Some(2)),
+                destination("gone", None),
+            ]
+        );
+        assert_eq!(navigation.page_labels, ["i", "ii", "A-5", "AA"]);
+        assert_eq!(navigation.page_for_label("ii"), Some(1));
+    }
+
+    #[test]
+    fn links_carry_actions_and_anchor_text() {
+        use crate::types::LinkAction;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+             /Resources << /Font << /F1 5 0 R >> >> /Contents 6 0 R \
+             /Annots [7 0 R 8 0 R 9 0 R] >>"
+                .to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            stream(
+                "",
+                "BT /F1 12 Tf 100 700 Td (Pay now) Tj ET \
+                 BT /F1 12 Tf 100 600 Td (Terms) Tj ET \
+                 BT /F1 12 Tf 100 500 Td (Open) Tj ET",
+            ),
+            "<< /Type /Annot /Subtype /Link /Rect [200 715 95 695] \
+             /A << /S /URI /URI (https://pay.example/ok) >> >>"
+                .to_string(),
+            "<< /Type /Annot /Subtype /Link /Rect [95 595 200 615] /Dest [4 0 R /Fit] >>"
+                .to_string(),
+            "<< /Type /Annot /Subtype /Link /Rect [95 495 200 515] \
+             /A << /S /GoTo /D [3 0 R /Fit] /Next [<< /S /JavaScript /JS (app.alert(1)) >> \
+             << /S /Launch /F << /Type /Filespec /F (cmd.exe) >> >>] >> >>"
+                .to_string(),
+        ]);
+        let links = super::extract_links(pdf).unwrap();
+        assert_eq!(links.len(), 2);
+        assert!(links[1].is_empty());
+
+        let page = &links[0];
+        assert_eq!(page[0].rect, [95.0, 695.0, 200.0, 715.0]);
+        let uri = LinkAction::Uri("https://pay.example/ok".to_string());
+        assert_eq!(page[0].actions, [uri]);
+        assert_eq!(page[0].anchor_text, "Pay now");
+        assert!(!page[0].is_suspicious());
+
+        assert_eq!(page[1].actions, [LinkAction::GoTo { page: Some(1) }]);
+        assert_eq!(page[1].anchor_text, "Terms");
+
+        assert_eq!(
+            page[2].actions,
+            [
+                LinkAction::GoTo { page: Some(0) },
+                LinkAction::JavaScript("app.alert(1)".to_string()),
+                LinkAction::Launch {
+                    file: Some("cmd.exe".to_string())
+                },
+            ]
+        );
+        assert_eq!(page[2].anchor_text, "Open");
+        assert!(page[2].is_suspicious());
+    }
+
+    #[test]
+    fn embedded_files_are_listed_with_params_and_data() {
+        let xml = "<Invoice><ID>42</ID></Invoice>";
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /Names << /EmbeddedFiles \
+             << /Names [(factur-x.xml) 4 0 R] >> >> >>"
+                .to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Annots [6 0 R 7 0 R] >>".to_string(),
+            "<< /Type /Filespec /F (invoice.xml) /UF <FEFF53D17968002E0078006D006C> \
+             /Desc (Invoice data) /AFRelationship /Alternative /EF << /F 5 0 R >> >>"
+                .to_string(),
+            stream(
+                &format!(
+                    "/Type /EmbeddedFile /Subtype /text#2Fxml /Params << /Size {} \
+                     /CheckSum <0A0B> /ModDate (D:20240102030405Z) >>",
+                    xml.len()
+                ),
+                xml,
+            ),
+            "<< /Type /Annot /Subtype /FileAttachment /Rect [0 0 10 10] /FS 4 0 R >>".to_string(),
+            "<< /Type /Annot /Subtype /FileAttachment /Rect [0 0 10 10] \
+             /FS << /Type /Filespec /F (notes.txt) /EF << /F 8 0 R >> >> >>"
+                .to_string(),
+            stream("/Filter /ASCIIHexDecode", "4142>"),
+        ]);
+        let files = super::extract_embedded_files(pdf).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let invoice = &files[0];
+        assert_eq!(invoice.name, "发票.xml");
+        assert_eq!(invoice.description.as_deref(), Some("Invoice data"));
+        assert_eq!(invoice.mime_type.as_deref(), Some("text/xml"));
+        assert_eq!(invoice.relationship.as_deref(), Some("Alternative"));
+        assert_eq!(invoice.size, Some(xml.len()));
+        assert_eq!(invoice.checksum.as_deref(), Some(&[0x0A, 0x0B][..]));
+        assert_eq!(invoice.mod_date.map(|d| d.second), Some(5));
+        assert_eq!(invoice.page, None);
+        assert_eq!(invoice.data.as_deref(), Some(xml.as_bytes()));
+
+        assert_eq!(files[1].name, "notes.txt");
+        assert_eq!(files[1].page, Some(0));
+        assert_eq!(files[1].data, None);
+    }
+
+    #[test]
+    fn images_are_listed_with_placement_and_exported() {
+        use crate::types::ImageExport;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] \
+             /Resources << /Font << /F1 4 0 R >> /XObject << /Seal 5 0 R >> >> \
+             /Contents 6 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 64 /Height 32 \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode",
+                "JPEGDATA",
+            ),
+            stream(
+                "",
+                "q 100 0 0 50 10 20 cm /Seal Do Q \
+                 q 4 0 0 4 150 150 cm BI /W 2 /H 2 /CS /G /BPC 8 ID a EI EI Q \
+                 q 2 0 0 1 0 0 cm BI /W 2 /H 1 /CS [/I /RGB 1 <FF000000FF00>] /BPC 8 \
+                 /F /AHx ID 0001> EI Q \
+                 BT /F1 12 Tf 10 100 Td (After) Tj ET",
+            ),
+        ]);
+        assert_eq!(super::extract_text(pdf.clone()).unwrap(), ["After"]);
+        let pages = super::extract_images(pdf).unwrap();
+        let images = &pages[0];
+        assert_eq!(images.len(), 3);
+
+        let seal = &images[0];
+        assert_eq!(seal.name.as_deref(), Some("Seal"));
+        assert_eq!(seal.rect, [10.0, 20.0, 110.0, 70.0]);
+        assert_eq!((seal.width, seal.height), (64, 32));
+        assert_eq!(seal.color_space.as_deref(), Some("DeviceRGB"));
+        assert_eq!((seal.components, seal.bits_per_component), (3, 8));
+        assert_eq!(seal.filters, ["DCTDecode"]);
+        assert_eq!(
+            super::export_image(seal),
+            Some(ImageExport::Jpeg(b"JPEGDATA".to_vec()))
+        );
+
+        let gray = &images[1];
+        assert_eq!(gray.name, None);
+        assert_eq!(gray.rect, [150.0, 150.0, 154.0, 154.0]);
+        assert_eq!(gray.data, b"a EI");
+        assert_eq!(gray.color_space.as_deref(), Some("DeviceGray"));
+
+        let indexed = &images[2];
+        assert_eq!(indexed.filters, ["ASCIIHexDecode"]);
+        let palette = [255, 0, 0, 0, 255, 0];
+        assert_eq!(indexed.palette.as_deref(), Some(&palette[..]));
+        assert_eq!(super::image_samples(indexed), Some(vec![0, 1]));
+        let Some(ImageExport::Png(png)) = super::export_image(indexed) else {
+            panic!("indexed image should export as PNG");
+        };
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // IHDR: width 2, height 1, 8-bit palette colour.
+        assert_eq!(&png[16..26], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 3]);
+    }
+
+    #[test]
+    fn qr_codes_are_decoded_from_images_with_their_position() {
+        let text = "01,10,044031900111,12345678,260.50,20260115";
+        let (grid, size) = crate::qr::tests::symbol(text.as_bytes(), 4, 2);
+        let (side, pixels) = crate::qr::tests::render(&grid, size, 2);
+        // One bit per pixel, black where dark, as hex.
+        let mut hex = String::new();
+        for row in pixels.chunks(side) {
+            for byte in row.chunks(8) {
+                let bits = byte
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (i, &p)| acc | (u8::from(p != 0) << (7 - i)));
+                hex.push_str(&format!("{bits:02X}"));
+            }
+        }
+        hex.push('>');
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 800] >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 800] \
+             /Resources << /XObject << /Code 5 0 R >> >> /Contents 6 0 R >>"
+                .to_string(),
+            stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {side} /Height {side} \
+                     /ColorSpace /DeviceGray /BitsPerComponent 1 /Filter /ASCIIHexDecode"
+                ),
+                &hex,
+            ),
+            stream("", "q 100 0 0 100 50 600 cm /Code Do Q"),
+        ]);
+        let codes = super::extract_qr_codes(pdf).unwrap();
+        assert_eq!(codes.len(), 1);
+        let code = &codes[0];
+        assert_eq!((code.page, code.version), (1, 4));
+        assert_eq!(code.text, text);
+        assert_eq!(code.payload, text.as_bytes());
+        // The symbol sits inside a four-module quiet zone of 41 modules.
+        let expected = [
+            50.0 + 400.0 / 41.0,
+            600.0 + 400.0 / 41.0,
+            50.0 + 3700.0 / 41.0,
+            600.0 + 3700.0 / 41.0,
+        ];
+        for (got, want) in code.rect.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-6, "{:?}", code.rect);
+        }
+    }
+
+    #[test]
+    fn pages_render_paths_images_and_embedded_glyphs() {
+        // A TrueType font with no cmap or post table whose glyph 1 is a
+        // triangle, spliced into the file in place of a placeholder.
+        let triangle = crate::truetype::tests::simple_glyph(&[
+            (256, 256, true),
+            (256, 768, true),
+            (768, 768, true),
+        ]);
+        let font = crate::truetype::tests::glyph_font(1024, &[Vec::new(), triangle]);
+        let placeholder = "#".repeat(font.len());
+
+        let mut pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] \
+             /Resources << /Font << /F1 4 0 R >> /XObject << /Im 8 0 R >> >> \
+             /Contents 9 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Square /Encoding /Identity-H \
+             /DescendantFonts [5 0 R] >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Square \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+             /FontDescriptor 6 0 R /CIDToGIDMap /Identity >>"
+                .to_string(),
+            "<< /Type /FontDescriptor /FontName /Square /Flags 4 /FontFile2 7 0 R >>".to_string(),
+            stream("", &placeholder),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 2 /Height 1 /ColorSpace /DeviceRGB \
+                 /BitsPerComponent 8 /Filter /ASCIIHexDecode",
+                "00FF00 000000>",
+            ),
+            stream(
+                "",
+                "1 0 0 rg 10 10 30 20 re f \
+                 0 0 1 RG 4 w 60 20 m 90 20 l S \
+                 q 20 0 0 10 10 60 cm /Im Do Q \
+                 0 g BT /F1 20 Tf 60 60 Td <0001> Tj ET",
+            ),
+        ]);
+        splice(&mut pdf, &font);
+        let page = super::render_page(pdf, 0, 1.0).unwrap();
+        assert_eq!((page.width, page.height), (100, 100));
+        // Pixels are addressed from the top left; the page's origin is at the
+        // bottom left.
+        let pixel = |x: usize, y: usize| -> [u8; 4] {
+            let at = ((99 - y) * 100 + x) * 4;
+            page.pixels[at..at + 4].try_into().unwrap()
+        };
+        assert_eq!(pixel(25, 20), [255, 0, 0, 255]);
+        assert_eq!(pixel(75, 20), [0, 0, 255, 255]);
+        assert_eq!(pixel(75, 24), [255, 255, 255, 255]);
+        assert_eq!(pixel(15, 65), [0, 255, 0, 255]);
+        assert_eq!(pixel(25, 65), [0, 0, 0, 255]);
+        // The glyph spans 5 to 15 points from the text origin, above its
+        // diagonal.
+        assert_eq!(pixel(67, 72), [0, 0, 0, 255]);
+        assert_eq!(pixel(72, 67), [255, 255, 255, 255]);
+        assert!(page.to_png().starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        assert!(super::render_page(build_pdf(&[]), 0, 1.0).is_err());
+    }
+
+    #[test]
+    fn oversized_rasters_and_images_are_refused_or_scaled_down() {
+        use crate::raster::Raster;
+
+        let tall = Raster::new(100.0, 1e9, 1.0);
+        assert_eq!((tall.width, tall.height), (1, 32768));
+        assert_eq!(tall.pixels.len(), 32768 * 4);
+        let endless = Raster::new(f64::INFINITY, 10.0, 1.0);
+        assert_eq!((endless.width, endless.height), (1, 1));
+
+        // An image claiming ten billion pixels over a few bytes of data.
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] \
+             /Resources << /XObject << /Im 4 0 R >> >> /Contents 5 0 R >>"
+                .to_string(),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 100000 /Height 100000 \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8",
+                "abc",
+            ),
+            stream("", "q 100 0 0 100 0 0 cm /Im Do Q"),
+        ]);
+        let images = super::extract_images(pdf.clone()).unwrap();
+        assert_eq!(super::rgb_pixels(&images[0][0]), None);
+        let page = super::render_page(pdf, 0, 1.0).unwrap();
+        assert_eq!((page.width, page.height), (100, 100));
+
+        // Sizes whose byte counts overflow: an inline image, an image at
+        // 16 bits per sample, and predictor rows past the address space.
+        let flate = miniz_oxide::deflate::compress_to_vec_zlib(&[0, 1, 2], 6);
+        let placeholder = "#".repeat(flate.len());
+        let mut pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] \
+             /Resources << /XObject << /Wide 4 0 R /Rows 5 0 R >> >> /Contents 6 0 R >>"
+                .to_string(),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 4294967295 /Height 4294967295 \
+                 /ColorSpace /DeviceGray /BitsPerComponent 16",
+                "abc",
+            ),
+            stream(
+                "/Type /XObject /Subtype /Image /Width 1 /Height 1 \
+                 /ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /FlateDecode \
+                 /DecodeParms << /Predictor 12 /Columns 4294967296.0 /Colors 4294967296.0 >>",
+                &placeholder,
+            ),
+            stream(
+                "",
+                "q BI /W 100000000000 /H 100000000000 /CS /RGB /BPC 8 ID abc EI Q \
+                 /Wide Do /Rows Do",
+            ),
+        ]);
+        splice(&mut pdf, &flate);
+        assert!(super::extract_text(pdf.clone()).is_ok());
+        let images = super::extract_images(pdf).unwrap();
+        assert_eq!(images[0].len(), 3);
+        for image in &images[0] {
+            assert!(super::export_image(image).is_none());
+        }
+    }
+
+    #[test]
+    fn cid_ranges_running_past_the_last_cid_are_skipped() {
+        let font = crate::truetype::tests::glyph_font(1024, &[Vec::new(), Vec::new()]);
+        let placeholder = "#".repeat(font.len());
+        let mut pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> \
+             /Contents 8 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Blank /Encoding 7 0 R \
+             /DescendantFonts [5 0 R] >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Blank \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+             /FontDescriptor 6 0 R /CIDToGIDMap /Identity >>"
+                .to_string(),
+            "<< /Type /FontDescriptor /FontName /Blank /Flags 4 /FontFile2 9 0 R >>".to_string(),
+            stream(
+                "/Type /CMap",
+                "1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                 2 begincidrange <0000> <0001> 0 <0010> <0020> 4294967290 endcidrange",
+            ),
+            stream("", "BT /F1 12 Tf <00010015> Tj ET"),
+            stream("", &placeholder),
+        ]);
+        splice(&mut pdf, &font);
+        assert!(super::extract_text(pdf).is_ok());
+    }
+
+    // Puts binary data into a built file in place of a placeholder of `#`s as
+    // long as it.
+    fn splice(pdf: &mut [u8], data: &[u8]) {
+        let placeholder = "#".repeat(data.len());
+        let at = pdf
+            .windows(data.len())
+            .position(|w| w == placeholder.as_bytes())
+            .unwrap();
+        pdf[at..at + data.len()].copy_from_slice(data);
+    }
+
+    #[test]
+    fn unmapped_glyphs_are_read_by_their_outlines() {
+        use crate::types::{CharSource, ExtractOptions, FingerprintDatabase};
+
+        // The same glyph in two subset fonts without cmap or post tables, at
+        // different units per em; only the first has a ToUnicode map.
+        let document = |units_per_em: i16, to_unicode: bool| {
+            let scale = |v: i16| v * (units_per_em / 1000);
+            let glyph = crate::truetype::tests::simple_glyph(&[
+                (scale(100), scale(0), true),
+                (scale(100), scale(700), true),
+                (scale(500), scale(900), false),
+                (scale(900), scale(700), true),
+                (scale(900), scale(0), true),
+            ]);
+            let font =
+                crate::truetype::tests::glyph_font(units_per_em as u16, &[Vec::new(), glyph]);
+            let to_unicode_entry = if to_unicode { "/ToUnicode 8 0 R" } else { "" };
+            let mut pdf = build_pdf(&[
+                "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] \
+                 /Resources << /Font << /F1 4 0 R >> >> /Contents 9 0 R >>"
+                    .to_string(),
+                format!(
+                    "<< /Type /Font /Subtype /Type0 /BaseFont /ABCDEF+Subset \
+                     /Encoding /Identity-H /DescendantFonts [5 0 R] {} >>",
+                    to_unicode_entry
+                ),
+                "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /ABCDEF+Subset \
+                 /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                 /FontDescriptor 6 0 R /CIDToGIDMap /Identity >>"
+                    .to_string(),
+                "<< /Type /FontDescriptor /FontName /ABCDEF+Subset /Flags 4 /FontFile2 7 0 R >>"
+                    .to_string(),
+                stream("", &"#".repeat(font.len())),
+                stream(
+                    "",
+                    "/CIDInit /ProcSet findresource begin 12 dict begin begincmap \
+                     1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                     1 beginbfchar <0001> <4EBA> endbfchar endcmap",
+                ),
+                stream("", "BT /F1 20 Tf 10 10 Td <0001> Tj ET"),
+            ]);
+            splice(&mut pdf, &font);
+            pdf
+        };
+
+        let mut database = FingerprintDatabase::new();
+        assert_eq!(
+            super::learn_glyph_fingerprints(document(1000, true), &mut database).unwrap(),
+            1
+        );
+        let database = FingerprintDatabase::from_text(&database.to_text());
+
+        let unmapped = document(2000, false);
+        let guessed = super::extract_text(unmapped.clone()).unwrap();
+        assert_ne!(guessed[0], "人");
+        let options = ExtractOptions {
+            glyph_fingerprints: Some(database),
+            ..ExtractOptions::default()
+        };
+        let recognised = super::extract_text_with_diagnostics(unmapped, &options).unwrap();
+        let (text, diagnostics) = &recognised[0];
+        assert_eq!(text, "人");
+        // A match by outline is still a guess that no mapping backs.
+        assert_eq!(diagnostics.char_sources, [CharSource::GlyphFingerprint]);
+        assert!(CharSource::GlyphFingerprint.is_guessed());
+    }
+
+    #[test]
+    fn each_character_records_how_it_was_decoded() {
+        use crate::types::CharSource::*;
+
+        let pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] \
+             /Resources << /Font << /F1 4 0 R /F2 7 0 R >> >> /Contents 6 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Mapped /Encoding /Identity-H \
+             /DescendantFonts [] /ToUnicode 5 0 R >>"
+                .to_string(),
+            stream(
+                "",
+                "1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                 1 beginbfrange <0001> <0003> <0040> endbfrange \
+                 1 beginbfchar <0001> <4EBA> endbfchar",
+            ),
+            stream(
+                "",
+                "BT /F1 12 Tf 10 100 Td <00010002000300500100> Tj ET \
+                 BT /F2 12 Tf 10 50 Td (Hi) Tj ET",
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+             /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ]);
+        let pages = super::extract_text_with_diagnostics(pdf.clone(), &Default::default()).unwrap();
+        let (text, diagnostics) = &pages[0];
+        assert_eq!(text, "人ABP\u{FFFD}\nHi");
+        assert_eq!(
+            diagnostics.char_sources,
+            [
+                ToUnicodeChar,
+                ToUnicodeRange,
+                ToUnicodeRange,
+                AsciiFallback,
+                Replacement,
+                Layout,
+                GlyphName,
+                GlyphName
+            ]
+        );
+        let glyphs = super::extract_glyphs(pdf).unwrap();
+        let guessed: Vec<&str> = glyphs[0]
+            .iter()
+            .filter(|g| g.source.is_guessed())
+            .map(|g| g.text.as_str())
+            .collect();
+        assert_eq!(guessed, ["P", "\u{FFFD}"]);
+    }
+
+    #[test]
+    fn suspicious_to_unicode_entries_are_repaired_from_other_sources() {
+        use crate::types::CharSource::*;
+
+        // An embedded font drawing A, B, C and D as glyphs 1 to 4.
+        let font = crate::truetype::tests::cmap_font(&['A', 'B', 'C', 'D']);
+        let placeholder = "#".repeat(font.len());
+        let mut pdf = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] \
+             /Resources << /Font << /F1 4 0 R /F2 7 0 R /F3 9 0 R >> >> /Contents 6 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Gothic /Encoding /Identity-H \
+             /DescendantFonts [<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Gothic \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Japan1) /Supplement 6 >> >>] \
+             /ToUnicode 5 0 R >>"
+                .to_string(),
+            stream(
+                "",
+                "1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                 5 beginbfchar <0011> <4EBA> <0022> <E000> <0023> <0000> \
+                 <0024> <0058> <0025> <0058> endbfchar",
+            ),
+            stream(
+                "",
+                "BT /F1 12 Tf 10 100 Td <00110022002300240025> Tj ET \
+                 BT /F2 12 Tf 10 50 Td (AB) Tj ET \
+                 BT /F3 12 Tf 10 20 Td <0001000200030004> Tj ET",
+            ),
+            "<< /Type /Font /Subtype /TrueType /BaseFont /Symbolic \
+             /Encoding /WinAnsiEncoding /ToUnicode 8 0 R >>"
+                .to_string(),
+            stream("", "2 beginbfchar <41> <F041> <42> <0042> endbfchar"),
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Subset /Encoding /Identity-H \
+             /DescendantFonts [<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Subset \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+             /FontDescriptor 10 0 R /CIDToGIDMap /Identity >>] /ToUnicode 12 0 R >>"
+                .to_string(),
+            "<< /Type /FontDescriptor /FontName /Subset /Flags 4 /FontFile2 11 0 R >>".to_string(),
+            stream("", &placeholder),
+            stream(
+                "",
+                "1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                 4 beginbfchar <0001> <0041> <0002> <0041> <0003> <0058> <0004> <0058> \
+                 endbfchar",
+            ),
+        ]);
+        splice(&mut pdf, &font);
+        let pages = super::extract_text_with_diagnostics(pdf.clone(), &Default::default()).unwrap();
+        let (text, diagnostics) = &pages[0];
+        // The duplicate X of F1 and of F3's last two codes is left alone, with
+        // nothing, or everything, telling its codes apart; the embedded font
+        // tells F3's second A apart from the first.
+        assert_eq!(text, "人ABXX\nAB\nABXX");
+        assert_eq!(
+            diagnostics.char_sources,
+            [
+                ToUnicodeChar,
+                PredefinedCMap,
+                PredefinedCMap,
+                ToUnicodeChar,
+                ToUnicodeChar,
+                Layout,
+                GlyphName,
+                ToUnicodeChar,
+                Layout,
+                ToUnicodeChar,
+                EmbeddedCmap,
+                ToUnicodeChar,
+                ToUnicodeChar
+            ]
+        );
+
+        let document = super::parse_document(&pdf).unwrap();
+        let fonts = &document.pages[0].fonts;
+        assert_eq!(fonts["F1"].warnings.len(), 2, "{:?}", fonts["F1"].warnings);
+        assert_eq!(
+            fonts["F2"].warnings,
+            ["ToUnicode: <41> maps to U+F041 (a private use character); \
+              using U+0041 from the glyph name"]
+        );
+        let repaired = "ToUnicode: <0002> maps to U+0041 (also the text of another code); \
+                        using U+0042 from the embedded font";
+        assert_eq!(fonts["F3"].warnings, [repaired]);
+    }
 }
 
 #[cfg(feature = "private_tests")]
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::encoding::decode_text_string;
+use crate::graphics::intersect;
+use crate::navigation::Destinations;
+use crate::types::{Link, LinkAction, PageContent, PdfDocument, PdfObj};
+use crate::{decode_stream, resolve, text_in_region};
+
+// Longest `/Next` chain of actions followed.
+const MAX_ACTIONS: usize = 32;
+
+/// The `/Link` annotations of each page, with their actions and the text
+/// under them.
+pub fn document_links(document: &PdfDocument) -> Vec<Vec<Link>> {
+    let destinations = Destinations::new(document);
+    document
+        .pages
+        .iter()
+        .map(|page| page_links(page, document, &destinations))
+        .collect()
+}
+
+fn page_links(
+    page: &PageContent,
+    document: &PdfDocument,
+    destinations: &Destinations,
+) -> Vec<Link> {
+    let objects = &document.objects;
+    let mut links = Vec::new();
+    for annotation in &page.annotations {
+        let PdfObj::Dictionary(annotation) = resolve(annotation, objects) else {
+            continue;
+        };
+        if !matches!(annotation.get("Subtype"), Some(PdfObj::Name(s)) if s == "Link") {
+            continue;
+        }
+        let Some(rect) = rect(annotation.get("Rect"), objects) else {
+            continue;
+        };
+        let actions = match (annotation.get("A"), annotation.get("Dest")) {
+            (Some(action), _) => actions(action, destinations, objects),
+            (None, Some(dest)) => vec![LinkAction::GoTo {
+                page: destinations.page(dest),
+            }],
+            (None, None) => Vec::new(),
+        };
+        let region = page.crop_box.map_or(rect, |crop| intersect(&rect, &crop));
+        links.push(Link {
+            rect,
+            actions,
+            anchor_text: text_in_region(page, objects, region),
+        });
+    }
+    links
+}
+
+fn rect(obj: Option<&PdfObj>, objects: &HashMap<(u32, u16), PdfObj>) -> Option<[f64; 4]> {
+    let PdfObj::Array(items) = resolve(obj?, objects) else {
+        return None;
+    };
+    let numbers: Vec<f64> = items
+        .iter()
+        .filter_map(|item| match resolve(item, objects) {
+            PdfObj::Number(n) => Some(*n),
+            _ => None,
+        })
+        .collect();
+    let [x0, y0, x1, y1] = <[f64; 4]>::try_from(numbers).ok()?;
+    Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+}
+
+// The action and the actions chained after it through `/Next`, depth first.
+fn actions(
+    first: &PdfObj,
+    destinations: &Destinations,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> Vec<LinkAction> {
+    let mut actions = Vec::new();
+    let mut pending = vec![first];
+    let mut visited = HashSet::new();
+    while let Some(next) = pending.pop() {
+        if actions.len() >= MAX_ACTIONS {
+            break;
+        }
+        if let PdfObj::Reference(id) = next {
+            if !visited.insert(*id) {
+                continue;
+            }
+        }
+        match resolve(next, objects) {
+            PdfObj::Dictionary(action) => {
+                actions.push(link_action(action, destinations, objects));
+                if let Some(next) = action.get("Next") {
+                    pending.push(next);
+                }
+            }
+            PdfObj::Array(chain) => pending.extend(chain.iter().rev()),
+            _ => {}
+        }
+    }
+    actions
+}
+
+fn link_action(
+    action: &HashMap<String, PdfObj>,
+    destinations: &Destinations,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> LinkAction {
+    let kind = match action.get("S") {
+        Some(PdfObj::Name(kind)) => kind.as_str(),
+        _ => "",
+    };
+    match kind {
+        "URI" => match action.get("URI").map(|u| resolve(u, objects)) {
+            // URIs are 7-bit ASCII, but some writers use text strings.
+            Some(PdfObj::String(uri)) => LinkAction::Uri(decode_text_string(uri)),
+            _ => LinkAction::Uri(String::new()),
+        },
+        "GoTo" => LinkAction::GoTo {
+            page: destinations.action_page(action),
+        },
+        "GoToR" => LinkAction::GoToRemote {
+            file: action.get("F").and_then(|f| file_name(f, objects)),
+        },
+        "Launch" => LinkAction::Launch {
+            file: action
+                .get("F")
+                .or_else(|| match action.get("Win").map(|w| resolve(w, objects)) {
+                    Some(PdfObj::Dictionary(win)) => win.get("F"),
+                    _ => None,
+                })
+                .and_then(|f| file_name(f, objects)),
+        },
+        "JavaScript" => {
+            LinkAction::JavaScript(match action.get("JS").map(|js| resolve(js, objects)) {
+                Some(PdfObj::String(script)) => decode_text_string(script),
+                Some(PdfObj::Stream(script)) => decode_text_string(&decode_stream(script)),
+                _ => String::new(),
+            })
+        }
+        other => LinkAction::Other(other.to_string()),
+    }
+}
+
+// A file specification: a string, or a dictionary with `/UF` or `/F`.
+fn file_name(spec: &PdfObj, objects: &HashMap<(u32, u16), PdfObj>) -> Option<String> {
+    let name = match resolve(spec, objects) {
+        PdfObj::Dictionary(spec) => resolve(spec.get("UF").or_else(|| spec.get("F"))?, objects),
+        name => name,
+    };
+    match name {
+        PdfObj::String(name) => Some(decode_text_string(name)),
+        _ => None,
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use crate::encoding::decode_text_string;
+use crate::types::{DocumentInfo, DocumentMetadata, PdfDate, PdfDocument, PdfObj, XmpProperty};
+use crate::xml::{self, Element};
+use crate::{decode_stream, resolve};
+
+// The XMP namespaces read, by URI, with the prefix they are reported under.
+const NAMESPACES: [(&str, &str); 4] = [
+    ("http://purl.org/dc/elements/1.1/", "dc"),
+    ("http://ns.adobe.com/xap/1.0/", "xmp"),
+    ("http://ns.adobe.com/pdf/1.3/", "pdf"),
+    // Written by a few old producers instead of the `xap` URI.
+    ("http://ns.adobe.com/xmp/1.0/", "xmp"),
+];
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Metadata from the trailer's `/Info` and the catalog's `/Metadata` XMP.
+pub fn document_metadata(document: &PdfDocument) -> DocumentMetadata {
+    DocumentMetadata {
+        info: document_info(document),
+        xmp: xmp_properties(document),
+    }
+}
+
+fn document_info(document: &PdfDocument) -> DocumentInfo {
+    let objects = &document.objects;
+    let Some(PdfObj::Dictionary(dict)) = document.trailer.get("Info").map(|i| resolve(i, objects))
+    else {
+        return DocumentInfo::default();
+    };
+    let mut info = DocumentInfo::default();
+    let mut keys: Vec<&String> = dict.keys().collect();
+    keys.sort();
+    for key in keys {
+        let PdfObj::String(bytes) = resolve(&dict[key], objects) else {
+            continue;
+        };
+        let text = decode_text_string(bytes);
+        match key.as_str() {
+            "Title" => info.title = Some(text),
+            "Author" => info.author = Some(text),
+            "Subject" => info.subject = Some(text),
+            "Keywords" => info.keywords = Some(text),
+            "Creator" => info.creator = Some(text),
+            "Producer" => info.producer = Some(text),
+            "CreationDate" => {
+                info.creation_date = parse_date(&text);
+                info.raw_creation_date = Some(text);
+            }
+            "ModDate" => {
+                info.mod_date = parse_date(&text);
+                info.raw_mod_date = Some(text);
+            }
+            _ => info.custom.push((key.clone(), text)),
+        }
+    }
+    info
+}
+
+/// Parses a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`, everything after the year
+/// optional) or an XMP date (`YYYY-MM-DDThh:mm:ss.sTZD`, likewise).
+pub fn parse_date(text: &str) -> Option<PdfDate> {
+    let text = text.trim();
+    if text.as_bytes().get(4) == Some(&b'-') {
+        return parse_iso_date(text);
+    }
+    let text = text.strip_prefix("D:").unwrap_or(text);
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (fields, zone) = text.split_at(digits);
+    if fields.len() < 4 || fields.len() > 14 || fields.len() % 2 != 0 {
+        return None;
+    }
+    let field = |start: usize, default: u8| -> u8 {
+        fields
+            .get(start..start + 2)
+            .and_then(|f| f.parse().ok())
+            .unwrap_or(default)
+    };
+    let date = PdfDate {
+        year: fields[..4].parse().ok()?,
+        month: field(4, 1),
+        day: field(6, 1),
+        hour: field(8, 0),
+        minute: field(10, 0),
+        second: field(12, 0),
+        utc_offset_minutes: parse_zone(zone, '\'')?,
+    };
+    valid(date)
+}
+
+fn parse_iso_date(text: &str) -> Option<PdfDate> {
+    let (day_part, time_part) = text.split_once('T').unwrap_or((text, ""));
+    let mut day_fields = day_part.split('-');
+    let year = day_fields.next()?;
+    if year.len() != 4 {
+        return None;
+    }
+    let number = |field: Option<&str>| -> Option<u8> {
+        match field {
+            Some(f) if f.len() == 2 => f.parse().ok(),
+            Some(_) => None,
+            None => Some(0),
+        }
+    };
+    let month = number(day_fields.next())?.max(1);
+    let day = number(day_fields.next())?.max(1);
+    let zone_start = time_part.find(['Z', '+', '-']).unwrap_or(time_part.len());
+    let (clock, zone) = time_part.split_at(zone_start);
+    let mut clock_fields = clock.split(':');
+    let hour = number(clock_fields.next().filter(|f| !f.is_empty()))?;
+    let minute = number(clock_fields.next())?;
+    // Fractions of a second are dropped.
+    let second = number(
+        clock_fields
+            .next()
+            .map(|s| s.split('.').next().unwrap_or(s)),
+    )?;
+    valid(PdfDate {
+        year: year.parse().ok()?,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        utc_offset_minutes: parse_zone(zone, ':')?,
+    })
+}
+
+// `Z`, `+HH'mm'` or `-HH:mm` style zones; `Some(None)` when there is none.
+fn parse_zone(zone: &str, separator: char) -> Option<Option<i16>> {
+    let sign = match zone.chars().next() {
+        None => return Some(None),
+        Some('Z') => return Some(Some(0)),
+        Some('+') => 1,
+        Some('-') => -1,
+        Some(_) => return None,
+    };
+    let mut parts = zone[1..]
+        .split(separator)
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<i16>().ok());
+    let hours = parts.next().flatten()?;
+    let minutes = parts.next().unwrap_or(Some(0))?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(Some(sign * (hours * 60 + minutes)))
+}
+
+fn valid(date: PdfDate) -> Option<PdfDate> {
+    let ok = (1..=12).contains(&date.month)
+        && (1..=31).contains(&date.day)
+        && date.hour < 24
+        && date.minute < 60
+        && date.second < 60;
+    ok.then_some(date)
+}
+
+fn xmp_properties(document: &PdfDocument) -> Vec<XmpProperty> {
+    let objects = &document.objects;
+    let Some(PdfObj::Stream(stream)) = document
+        .catalog()
+        .and_then(|catalog| catalog.get("Metadata"))
+        .map(|m| resolve(m, objects))
+    else {
+        return Vec::new();
+    };
+    let bytes = decode_stream(stream);
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    let root = xml::parse(&String::from_utf8_lossy(bytes));
+    let mut properties = Vec::new();
+    collect_descriptions(&root, &mut HashMap::new(), &mut properties);
+    properties
+}
+
+// Walks the packet for `rdf:Description` elements, tracking the namespace
+// prefixes declared on the way.
+fn collect_descriptions(
+    element: &Element,
+    scope: &mut HashMap<String, String>,
+    properties: &mut Vec<XmpProperty>,
+) {
+    for child in element.elements() {
+        let outer = declare(child, scope);
+        if qualified(&child.name, scope) == Some((RDF, "Description")) {
+            description_properties(child, scope, properties);
+        } else {
+            collect_descriptions(child, scope, properties);
+        }
+        *scope = outer;
+    }
+}
+
+// Properties are given either as attributes of the description or as its
+// child elements.
+fn description_properties(
+    description: &Element,
+    scope: &mut HashMap<String, String>,
+    properties: &mut Vec<XmpProperty>,
+) {
+    for (name, value) in &description.attributes {
+        if let Some(name) = property_name(name, scope) {
+            properties.push(XmpProperty {
+                name,
+                values: vec![value.clone()],
+            });
+        }
+    }
+    for child in description.elements() {
+        let outer = declare(child, scope);
+        if let Some(name) = property_name(&child.name, scope) {
+            if let Some(values) = property_values(child, scope) {
+                properties.push(XmpProperty { name, values });
+            }
+        }
+        *scope = outer;
+    }
+}
+
+fn property_values(property: &Element, scope: &HashMap<String, String>) -> Option<Vec<String>> {
+    let Some(first) = property.elements().next() else {
+        if let Some(resource) = rdf_attribute(property, "resource", scope) {
+            return Some(vec![resource.to_string()]);
+        }
+        return Some(vec![property.text().trim().to_string()]);
+    };
+    match qualified(&first.name, scope) {
+        Some((RDF, "Alt" | "Seq" | "Bag")) => Some(
+            first
+                .elements()
+                .filter(|item| qualified(&item.name, scope) == Some((RDF, "li")))
+                .map(|item| item.text().trim().to_string())
+                .collect(),
+        ),
+        // Structured values are not flattened.
+        _ => None,
+    }
+}
+
+fn rdf_attribute<'a>(
+    element: &'a Element,
+    local_name: &str,
+    scope: &HashMap<String, String>,
+) -> Option<&'a str> {
+    element
+        .attributes
+        .iter()
+        .find(|(name, _)| qualified(name, scope) == Some((RDF, local_name)))
+        .map(|(_, value)| value.as_str())
+}
+
+// Adds the element's `xmlns:` declarations to `scope`, returning the scope
+// to restore once the element is done.
+fn declare(element: &Element, scope: &mut HashMap<String, String>) -> HashMap<String, String> {
+    let outer = scope.clone();
+    for (name, value) in &element.attributes {
+        if let Some(prefix) = name.strip_prefix("xmlns:") {
+            scope.insert(prefix.to_string(), value.clone());
+        }
+    }
+    outer
+}
+
+// The namespace URI and local part of a prefixed name.
+fn qualified<'a>(name: &'a str, scope: &'a HashMap<String, String>) -> Option<(&'a str, &'a str)> {
+    let (prefix, local) = name.split_once(':')?;
+    Some((scope.get(prefix)?.as_str(), local))
+}
+
+fn property_name(name: &str, scope: &HashMap<String, String>) -> Option<String> {
+    let (uri, local) = qualified(name, scope)?;
+    let (_, prefix) = NAMESPACES.iter().find(|(known, _)| *known == uri)?;
+    Some(format!("{prefix}:{local}"))
+}
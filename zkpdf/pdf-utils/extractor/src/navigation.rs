@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::encoding::decode_text_string;
+use crate::resolve;
+use crate::types::{DocumentNavigation, NamedDestination, OutlineItem, PdfDocument, PdfObj};
+
+// Name and number trees, and outlines, nested deeper than this are cut off.
+const MAX_TREE_DEPTH: usize = 32;
+// Larger page numbers are written in decimal whatever the label style.
+const MAX_LETTER_NUMBER: usize = 10_000;
+
+/// Resolves destinations to the page indices used by `extract_text`.
+pub struct Destinations<'a> {
+    document: &'a PdfDocument,
+    page_index: HashMap<(u32, u16), usize>,
+    named: HashMap<Vec<u8>, &'a PdfObj>,
+}
+
+impl<'a> Destinations<'a> {
+    pub fn new(document: &'a PdfDocument) -> Self {
+        let page_index = document
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| Some((page.id?, index)))
+            .collect();
+        let named = named_destinations(document).into_iter().collect();
+        Destinations {
+            document,
+            page_index,
+            named,
+        }
+    }
+
+    /// The page an explicit destination (`[page /XYZ ...]`), a named one
+    /// (a name or string) or a destination dictionary (`<< /D ... >>`)
+    /// points to.
+    pub fn page(&self, destination: &PdfObj) -> Option<usize> {
+        self.page_at(destination, 0)
+    }
+
+    fn page_at(&self, destination: &PdfObj, depth: usize) -> Option<usize> {
+        if depth > 4 {
+            return None;
+        }
+        match resolve(destination, &self.document.objects) {
+            PdfObj::Array(items) => match items.first()? {
+                PdfObj::Reference(id) => self.page_index.get(id).copied(),
+                // Page numbers belong in remote destinations, but some
+                // writers use them for local ones too.
+                PdfObj::Number(n) if *n >= 0.0 => {
+                    Some(*n as usize).filter(|index| *index < self.document.pages.len())
+                }
+                _ => None,
+            },
+            PdfObj::Name(name) => self.page_at(self.named.get(name.as_bytes())?, depth + 1),
+            PdfObj::String(name) => self.page_at(self.named.get(name)?, depth + 1),
+            PdfObj::Dictionary(dict) => self.page_at(dict.get("D")?, depth + 1),
+            _ => None,
+        }
+    }
+
+    /// The target page of a `GoTo` action.
+    pub fn action_page(&self, action: &HashMap<String, PdfObj>) -> Option<usize> {
+        match action.get("S") {
+            Some(PdfObj::Name(kind)) if kind == "GoTo" => self.page(action.get("D")?),
+            _ => None,
+        }
+    }
+}
+
+/// The outline, named destinations and page labels of a document.
+pub fn document_navigation(document: &PdfDocument) -> DocumentNavigation {
+    let destinations = Destinations::new(document);
+    let named = named_destinations(document)
+        .into_iter()
+        .map(|(name, destination)| NamedDestination {
+            name: decode_text_string(&name),
+            page: destinations.page(destination),
+        })
+        .collect();
+    DocumentNavigation {
+        outline: outline(document, &destinations),
+        destinations: named,
+        page_labels: page_labels(document),
+    }
+}
+
+fn catalog_entry<'a>(document: &'a PdfDocument, key: &str) -> Option<&'a PdfObj> {
+    Some(resolve(document.catalog()?.get(key)?, &document.objects))
+}
+
+// The catalog's `/Dests` dictionary (PDF 1.1), sorted by name, followed by the
+// `/Names /Dests` name tree.
+fn named_destinations(document: &PdfDocument) -> Vec<(Vec<u8>, &PdfObj)> {
+    let objects = &document.objects;
+    let mut named = Vec::new();
+    if let Some(PdfObj::Dictionary(dests)) = catalog_entry(document, "Dests") {
+        let mut entries: Vec<_> = dests
+            .iter()
+            .map(|(name, dest)| (name.as_bytes().to_vec(), dest))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        named.extend(entries);
+    }
+    if let Some(PdfObj::Dictionary(names)) = catalog_entry(document, "Names") {
+        if let Some(root) = names.get("Dests") {
+            let mut visited = HashSet::new();
+            collect_name_tree(root, objects, &mut visited, 0, &mut named);
+        }
+    }
+    named
+}
+
+/// Collects the keys and values of a name tree (`/Names` and `/Kids`).
+pub fn collect_name_tree<'a>(
+    node: &'a PdfObj,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+    visited: &mut HashSet<(u32, u16)>,
+    depth: usize,
+    entries: &mut Vec<(Vec<u8>, &'a PdfObj)>,
+) {
+    if let PdfObj::Reference(id) = node {
+        if !visited.insert(*id) {
+            return;
+        }
+    }
+    let PdfObj::Dictionary(dict) = resolve(node, objects) else {
+        return;
+    };
+    if let Some(PdfObj::Array(pairs)) = dict.get("Names").map(|n| resolve(n, objects)) {
+        for pair in pairs.chunks_exact(2) {
+            if let PdfObj::String(name) = resolve(&pair[0], objects) {
+                entries.push((name.clone(), &pair[1]));
+            }
+        }
+    }
+    if depth < MAX_TREE_DEPTH {
+        if let Some(PdfObj::Array(kids)) = dict.get("Kids").map(|k| resolve(k, objects)) {
+            for kid in kids {
+                collect_name_tree(kid, objects, visited, depth + 1, entries);
+            }
+        }
+    }
+}
+
+fn outline(document: &PdfDocument, destinations: &Destinations) -> Vec<OutlineItem> {
+    let Some(PdfObj::Dictionary(root)) = catalog_entry(document, "Outlines") else {
+        return Vec::new();
+    };
+    let mut visited = HashSet::new();
+    outline_items(root.get("First"), document, destinations, &mut visited, 0)
+}
+
+// The items from `first` along the `/Next` chain, with their `/First` children.
+fn outline_items(
+    first: Option<&PdfObj>,
+    document: &PdfDocument,
+    destinations: &Destinations,
+    visited: &mut HashSet<(u32, u16)>,
+    depth: usize,
+) -> Vec<OutlineItem> {
+    let objects = &document.objects;
+    let mut items = Vec::new();
+    let mut next = first;
+    while let Some(PdfObj::Reference(id)) = next {
+        if depth > MAX_TREE_DEPTH || !visited.insert(*id) {
+            break;
+        }
+        let Some(PdfObj::Dictionary(item)) = objects.get(id) else {
+            break;
+        };
+        let title = match item.get("Title").map(|t| resolve(t, objects)) {
+            Some(PdfObj::String(bytes)) => decode_text_string(bytes),
+            _ => String::new(),
+        };
+        let page = match item.get("Dest") {
+            Some(dest) => destinations.page(dest),
+            None => match item.get("A").map(|a| resolve(a, objects)) {
+                Some(PdfObj::Dictionary(action)) => destinations.action_page(action),
+                _ => None,
+            },
+        };
+        let children = outline_items(
+            item.get("First"),
+            document,
+            destinations,
+            visited,
+            depth + 1,
+        );
+        items.push(OutlineItem {
+            title,
+            page,
+            children,
+        });
+        next = item.get("Next");
+    }
+    items
+}
+
+// One label per page from the `/PageLabels` number tree; the 1-based page
+// number when the document has none.
+fn page_labels(document: &PdfDocument) -> Vec<String> {
+    let objects = &document.objects;
+    let mut ranges = Vec::new();
+    if let Some(root) = document.catalog().and_then(|c| c.get("PageLabels")) {
+        collect_number_tree(root, objects, &mut HashSet::new(), 0, &mut ranges);
+    }
+    ranges.sort_by_key(|(start, _)| *start);
+    (0..document.pages.len())
+        .map(|index| {
+            let Some((start, style)) = ranges.iter().rev().find(|(start, _)| *start <= index)
+            else {
+                return (index + 1).to_string();
+            };
+            let PdfObj::Dictionary(style) = resolve(style, objects) else {
+                return (index + 1).to_string();
+            };
+            let prefix = match style.get("P").map(|p| resolve(p, objects)) {
+                Some(PdfObj::String(bytes)) => decode_text_string(bytes),
+                _ => String::new(),
+            };
+            let first = match style.get("St").map(|s| resolve(s, objects)) {
+                Some(PdfObj::Number(n)) if *n >= 1.0 => *n as usize,
+                _ => 1,
+            };
+            let number = first.saturating_add(index - start);
+            let numeral = match style.get("S") {
+                // Roman and alphabetic labels this long are not meant.
+                Some(PdfObj::Name(_)) if number > MAX_LETTER_NUMBER => number.to_string(),
+                Some(PdfObj::Name(s)) if s == "D" => number.to_string(),
+                Some(PdfObj::Name(s)) if s == "R" => roman(number),
+                Some(PdfObj::Name(s)) if s == "r" => roman(number).to_lowercase(),
+                Some(PdfObj::Name(s)) if s == "A" => letters(number),
+                Some(PdfObj::Name(s)) if s == "a" => letters(number).to_lowercase(),
+                // Without a style the label is the prefix alone.
+                _ => String::new(),
+            };
+            prefix + &numeral
+        })
+        .collect()
+}
+
+fn collect_number_tree<'a>(
+    node: &'a PdfObj,
+    objects: &'a HashMap<(u32, u16), PdfObj>,
+    visited: &mut HashSet<(u32, u16)>,
+    depth: usize,
+    entries: &mut Vec<(usize, &'a PdfObj)>,
+) {
+    if let PdfObj::Reference(id) = node {
+        if !visited.insert(*id) {
+            return;
+        }
+    }
+    let PdfObj::Dictionary(dict) = resolve(node, objects) else {
+        return;
+    };
+    if let Some(PdfObj::Array(pairs)) = dict.get("Nums").map(|n| resolve(n, objects)) {
+        for pair in pairs.chunks_exact(2) {
+            if let PdfObj::Number(key) = resolve(&pair[0], objects) {
+                if *key >= 0.0 {
+                    entries.push((*key as usize, &pair[1]));
+                }
+            }
+        }
+    }
+    if depth < MAX_TREE_DEPTH {
+        if let Some(PdfObj::Array(kids)) = dict.get("Kids").map(|k| resolve(k, objects)) {
+            for kid in kids {
+                collect_number_tree(kid, objects, visited, depth + 1, entries);
+            }
+        }
+    }
+}
+
+fn roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            out.push_str(numeral);
+            number -= value;
+        }
+    }
+    out
+}
+
+// A to Z, then AA to ZZ, AAA to ZZZ and so on.
+fn letters(number: usize) -> String {
+    let Some(index) = number.checked_sub(1) else {
+        return String::new();
+    };
+    let letter = char::from(b'A' + (index % 26) as u8);
+    letter.to_string().repeat(index / 26 + 1)
+}
@@ -0,0 +1,176 @@
+use crate::unicode_tables::{
+    COMBINING_CLASSES, COMPATIBILITY_IDEOGRAPHS, COMPOSITIONS, DECOMPOSITIONS, KANGXI_RADICALS,
+    RADICALS_SUPPLEMENT, WIDTH_FORMS,
+};
+
+/// Normalises extracted text for comparison: radicals and compatibility
+/// ideographs become unified ideographs, full-width ASCII and half-width
+/// katakana become their ordinary forms, and the result is put in NFC.
+pub fn normalize_text(text: &str) -> String {
+    normalize_chars(text.chars().map(|c| (c, ())), |_, _| ())
+        .into_iter()
+        .map(|(c, _)| c)
+        .collect()
+}
+
+/// [`normalize_text`] over characters that each carry a note; the parts of
+/// a decomposed character carry its note, and a composed character carries
+/// what `merge` makes of the notes of its parts.
+pub fn normalize_chars<T: Clone>(
+    chars: impl Iterator<Item = (char, T)>,
+    merge: impl Fn(T, T) -> T,
+) -> Vec<(char, T)> {
+    let mut decomposed = Vec::new();
+    for (c, note) in chars {
+        decompose(fold_char(c), &note, &mut decomposed);
+    }
+    reorder(&mut decomposed);
+    compose(decomposed, merge)
+}
+
+fn fold_char(c: char) -> char {
+    let cp = c as u32;
+    let mapped = match cp {
+        0x2F00..=0x2FD5 => Some(KANGXI_RADICALS[(cp - 0x2F00) as usize]),
+        0x2E80..=0x2EFF => lookup(&RADICALS_SUPPLEMENT, cp),
+        0xF900..=0xFAFF | 0x2F800..=0x2FA1F => lookup(&COMPATIBILITY_IDEOGRAPHS, cp),
+        0x3000 | 0xFF00..=0xFFEF => lookup(&WIDTH_FORMS, cp),
+        _ => None,
+    };
+    mapped.and_then(char::from_u32).unwrap_or(c)
+}
+
+fn lookup(table: &[(u32, u32)], cp: u32) -> Option<u32> {
+    table
+        .binary_search_by_key(&cp, |&(from, _)| from)
+        .ok()
+        .map(|i| table[i].1)
+}
+
+// Full canonical decomposition, Hangul syllables included.
+fn decompose<T: Clone>(c: char, note: &T, out: &mut Vec<(char, T)>) {
+    let cp = c as u32;
+    if (HANGUL_BASE..HANGUL_BASE + 11172).contains(&cp) {
+        let index = cp - HANGUL_BASE;
+        let l = JAMO_L_BASE + index / (21 * 28);
+        let v = JAMO_V_BASE + index % (21 * 28) / 28;
+        let t = JAMO_T_BASE + index % 28;
+        let jamo = [l, v, t];
+        let count = if t == JAMO_T_BASE { 2 } else { 3 };
+        out.extend(
+            jamo[..count]
+                .iter()
+                .filter_map(|&j| char::from_u32(j))
+                .map(|j| (j, note.clone())),
+        );
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&cp, |&(from, _, _)| from) {
+        Ok(i) => {
+            let (_, first, second) = DECOMPOSITIONS[i];
+            for part in [first, second].into_iter().filter(|&p| p != 0) {
+                if let Some(part) = char::from_u32(part) {
+                    decompose(part, note, out);
+                }
+            }
+        }
+        Err(_) => out.push((c, note.clone())),
+    }
+}
+
+fn combining_class(c: char) -> u8 {
+    let cp = c as u32;
+    let i = COMBINING_CLASSES.partition_point(|&(_, end, _)| end < cp);
+    match COMBINING_CLASSES.get(i) {
+        Some(&(start, _, class)) if start <= cp => class,
+        _ => 0,
+    }
+}
+
+// Canonical ordering: each run of combining marks is sorted, stably, by
+// combining class.
+fn reorder<T>(chars: &mut [(char, T)]) {
+    let mut start = 0;
+    while start < chars.len() {
+        let len = chars[start..]
+            .iter()
+            .take_while(|(c, _)| combining_class(*c) != 0)
+            .count();
+        chars[start..start + len].sort_by_key(|(c, _)| combining_class(*c));
+        start += len.max(1);
+    }
+}
+
+// Canonical composition: each character joins the last starter unless a
+// character of the same or higher combining class stands between them.
+fn compose<T: Clone>(chars: Vec<(char, T)>, merge: impl Fn(T, T) -> T) -> Vec<(char, T)> {
+    let mut out: Vec<(char, T)> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    let mut last_class = 0;
+    for (c, note) in chars {
+        let class = combining_class(c);
+        if let Some(s) = starter {
+            let adjacent = out.len() == s + 1;
+            if adjacent || (last_class != 0 && last_class < class) {
+                if let Some(composed) = compose_pair(out[s].0, c) {
+                    out[s] = (composed, merge(out[s].1.clone(), note));
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+        }
+        last_class = class;
+        out.push((c, note));
+    }
+    out
+}
+
+const HANGUL_BASE: u32 = 0xAC00;
+const JAMO_L_BASE: u32 = 0x1100;
+const JAMO_V_BASE: u32 = 0x1161;
+const JAMO_T_BASE: u32 = 0x11A7;
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    // Hangul syllables compose algorithmically: L + V, then LV + T.
+    if (JAMO_L_BASE..JAMO_L_BASE + 19).contains(&a) && (JAMO_V_BASE..JAMO_V_BASE + 21).contains(&b)
+    {
+        return char::from_u32(HANGUL_BASE + ((a - JAMO_L_BASE) * 21 + (b - JAMO_V_BASE)) * 28);
+    }
+    if (HANGUL_BASE..HANGUL_BASE + 11172).contains(&a)
+        && (a - HANGUL_BASE).is_multiple_of(28)
+        && (JAMO_T_BASE + 1..JAMO_T_BASE + 28).contains(&b)
+    {
+        return char::from_u32(a + (b - JAMO_T_BASE));
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(a, b), |&(starter, mark, _)| (starter, mark))
+        .ok()
+        .and_then(|i| char::from_u32(COMPOSITIONS[i].2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_text;
+
+    #[test]
+    fn folds_cjk_variants_widths_and_composes() {
+        assert_eq!(normalize_text("\u{2F08}民币"), "人民币");
+        assert_eq!(normalize_text("\u{2EA0}\u{F900}"), "民\u{8C48}");
+        assert_eq!(normalize_text("ＲＭＢ　１２３．５０"), "RMB 123.50");
+        assert_eq!(normalize_text("ｶﾞｷﾞｸ"), "ガギク");
+        assert_eq!(
+            normalize_text("\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF}"),
+            "한글"
+        );
+        assert_eq!(normalize_text("Cafe\u{301} a\u{323}\u{302}"), "Café ậ");
+        // Marks out of canonical order, and a precomposed character with a
+        // further mark, still compose.
+        assert_eq!(normalize_text("a\u{302}\u{323}"), "ậ");
+        assert_eq!(normalize_text("\u{E2}\u{323}"), "ậ");
+        assert_eq!(normalize_text("\u{212B}"), "\u{C5}");
+        assert_eq!(normalize_text("\u{D55C}\u{11A8}"), "\u{D55C}\u{11A8}");
+    }
+}
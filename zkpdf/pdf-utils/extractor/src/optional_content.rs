@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::resolve;
+use crate::types::PdfObj;
+
+// Visibility expressions nested deeper than this are taken as visible.
+const MAX_EXPRESSION_DEPTH: usize = 16;
+
+/// Optional content groups that are off in the default configuration
+/// (`/OCProperties /D`) of a document catalog.
+pub fn hidden_groups(
+    catalog: &HashMap<String, PdfObj>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> HashSet<(u32, u16)> {
+    let Some(PdfObj::Dictionary(properties)) =
+        catalog.get("OCProperties").map(|p| resolve(p, objects))
+    else {
+        return HashSet::new();
+    };
+    let Some(PdfObj::Dictionary(config)) = properties.get("D").map(|d| resolve(d, objects)) else {
+        return HashSet::new();
+    };
+    let groups = |key: &str, dict: &HashMap<String, PdfObj>| -> Vec<(u32, u16)> {
+        match dict.get(key).map(|v| resolve(v, objects)) {
+            Some(PdfObj::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    PdfObj::Reference(id) => Some(*id),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    let base_off = matches!(config.get("BaseState"), Some(PdfObj::Name(state)) if state == "OFF");
+    if base_off {
+        let on: HashSet<(u32, u16)> = groups("ON", config).into_iter().collect();
+        groups("OCGs", properties)
+            .into_iter()
+            .filter(|id| !on.contains(id))
+            .collect()
+    } else {
+        groups("OFF", config).into_iter().collect()
+    }
+}
+
+/// Whether content marked with `membership`, an optional content group or
+/// membership dictionary, is shown when the groups in `hidden` are off.
+pub fn is_visible(
+    membership: &PdfObj,
+    hidden: &HashSet<(u32, u16)>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+) -> bool {
+    expression_visible(membership, hidden, objects, 0)
+}
+
+fn expression_visible(
+    obj: &PdfObj,
+    hidden: &HashSet<(u32, u16)>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    depth: usize,
+) -> bool {
+    if depth > MAX_EXPRESSION_DEPTH {
+        return true;
+    }
+    let group_id = match obj {
+        PdfObj::Reference(id) => Some(*id),
+        _ => None,
+    };
+    match resolve(obj, objects) {
+        PdfObj::Dictionary(dict) => match dict.get("Type") {
+            Some(PdfObj::Name(t)) if t == "OCMD" => {
+                membership_visible(dict, hidden, objects, depth)
+            }
+            _ => group_id.is_none_or(|id| !hidden.contains(&id)),
+        },
+        // A visibility expression: `[/And ...]`, `[/Or ...]` or `[/Not x]`.
+        PdfObj::Array(items) => {
+            let mut operands = items
+                .iter()
+                .skip(1)
+                .map(|item| expression_visible(item, hidden, objects, depth + 1));
+            match items.first() {
+                Some(PdfObj::Name(op)) if op == "And" => operands.all(|on| on),
+                Some(PdfObj::Name(op)) if op == "Or" => operands.any(|on| on),
+                Some(PdfObj::Name(op)) if op == "Not" => !operands.next().unwrap_or(false),
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
+
+// An optional content membership dictionary: `/VE` when present, otherwise
+// the `/P` policy applied to `/OCGs`.
+fn membership_visible(
+    dict: &HashMap<String, PdfObj>,
+    hidden: &HashSet<(u32, u16)>,
+    objects: &HashMap<(u32, u16), PdfObj>,
+    depth: usize,
+) -> bool {
+    if let Some(expression) = dict.get("VE") {
+        return expression_visible(expression, hidden, objects, depth + 1);
+    }
+    let groups: Vec<bool> = match dict.get("OCGs") {
+        Some(PdfObj::Reference(id)) => match objects.get(id) {
+            Some(PdfObj::Array(items)) => items
+                .iter()
+                .map(|item| expression_visible(item, hidden, objects, depth + 1))
+                .collect(),
+            _ => vec![!hidden.contains(id)],
+        },
+        Some(PdfObj::Array(items)) => items
+            .iter()
+            .map(|item| expression_visible(item, hidden, objects, depth + 1))
+            .collect(),
+        _ => return true,
+    };
+    if groups.is_empty() {
+        return true;
+    }
+    match dict.get("P") {
+        Some(PdfObj::Name(policy)) if policy == "AllOn" => groups.iter().all(|on| *on),
+        Some(PdfObj::Name(policy)) if policy == "AnyOff" => groups.iter().any(|on| !on),
+        Some(PdfObj::Name(policy)) if policy == "AllOff" => groups.iter().all(|on| !on),
+        _ => groups.iter().any(|on| *on),
+    }
+}
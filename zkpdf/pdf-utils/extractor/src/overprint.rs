@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+// Two identical glyphs closer than this fraction of an em are one glyph drawn
+// twice; fake bold and drop shadows offset the copy by well under a point.
+const OVERPRINT_DISTANCE: f64 = 0.2;
+// Grid cell size in points, and the largest offset recognised at any size.
+const CELL: f64 = 2.0;
+
+/// Glyphs already emitted on a page, indexed by position, for recognising the
+/// same glyph drawn again almost on top of itself.
+#[derive(Debug, Default)]
+pub struct OverprintIndex {
+    cells: HashMap<(u32, i64, i64), Vec<Placed>>,
+}
+
+#[derive(Debug)]
+struct Placed {
+    font: Option<String>,
+    x: f64,
+    y: f64,
+    size: f64,
+}
+
+impl OverprintIndex {
+    /// Whether a glyph with this font and code at (`x`, `y`) overprints one
+    /// recorded earlier. A glyph that doesn't is recorded.
+    pub fn overprints(&mut self, font: Option<&str>, code: u32, x: f64, y: f64, size: f64) -> bool {
+        let reach = (OVERPRINT_DISTANCE * size).min(CELL);
+        let (cx, cy) = ((x / CELL).floor() as i64, (y / CELL).floor() as i64);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(placed) = self.cells.get(&(code, cx + dx, cy + dy)) else {
+                    continue;
+                };
+                let duplicate = placed.iter().any(|p| {
+                    p.font.as_deref() == font
+                        && (p.size - size).abs() <= size * 0.1
+                        && (p.x - x).hypot(p.y - y) <= reach
+                });
+                if duplicate {
+                    return true;
+                }
+            }
+        }
+        self.cells.entry((code, cx, cy)).or_default().push(Placed {
+            font: font.map(str::to_string),
+            x,
+            y,
+            size,
+        });
+        false
+    }
+}
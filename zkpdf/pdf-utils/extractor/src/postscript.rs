@@ -0,0 +1,179 @@
+use crate::parser_utils::{hex_value, is_delimiter, parse_hex_string, parse_literal_string};
+
+/// Lexical tokens of the PostScript subset used by CMaps and font programs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PsToken {
+    Integer(i64),
+    Real(f64),
+    /// Literal `( )` or hexadecimal `< >` string, already decoded to bytes.
+    String(Vec<u8>),
+    /// Literal name, written `/Name` in the source.
+    Name(String),
+    /// Executable name such as `def`, `begincmap` or `usecmap`.
+    Operator(String),
+    ArrayStart,
+    ArrayEnd,
+    ProcStart,
+    ProcEnd,
+    DictStart,
+    DictEnd,
+}
+
+/// Splits PostScript source into tokens, skipping whitespace and comments.
+pub struct PsTokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PsTokenizer<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        PsTokenizer { data, pos: 0 }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while self.pos < self.data.len() {
+            match self.data[self.pos] {
+                0x00 | 0x09 | 0x0A | 0x0C | 0x0D | 0x20 => self.pos += 1,
+                b'%' => {
+                    while self.pos < self.data.len()
+                        && self.data[self.pos] != b'\n'
+                        && self.data[self.pos] != b'\r'
+                    {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn at_stray_delimiter(&self) -> bool {
+        match self.data.get(self.pos) {
+            Some(b')') => true,
+            Some(b'>') => self.data.get(self.pos + 1) != Some(&b'>'),
+            _ => false,
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.pos < self.data.len() {
+            let c = self.data[self.pos];
+            if c.is_ascii_whitespace() || c == 0 || is_delimiter(c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+}
+
+impl Iterator for PsTokenizer<'_> {
+    type Item = PsToken;
+
+    fn next(&mut self) -> Option<PsToken> {
+        self.skip_whitespace_and_comments();
+        // Stray closing delimiters are skipped rather than stalling on them.
+        while self.at_stray_delimiter() {
+            self.pos += 1;
+            self.skip_whitespace_and_comments();
+        }
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let byte = self.data[self.pos];
+        let token = match byte {
+            b'(' => {
+                let (bytes, next) = parse_literal_string(self.data, self.pos);
+                self.pos = next;
+                PsToken::String(bytes)
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                PsToken::DictStart
+            }
+            b'<' => {
+                let (bytes, next) = parse_hex_string(self.data, self.pos);
+                self.pos = next;
+                PsToken::String(bytes)
+            }
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                PsToken::DictEnd
+            }
+            b'[' => {
+                self.pos += 1;
+                PsToken::ArrayStart
+            }
+            b']' => {
+                self.pos += 1;
+                PsToken::ArrayEnd
+            }
+            b'{' => {
+                self.pos += 1;
+                PsToken::ProcStart
+            }
+            b'}' => {
+                self.pos += 1;
+                PsToken::ProcEnd
+            }
+            b'/' => {
+                self.pos += 1;
+                // `//name` is an immediately evaluated name; treat it as a literal one.
+                if self.data.get(self.pos) == Some(&b'/') {
+                    self.pos += 1;
+                }
+                PsToken::Name(decode_name(self.read_regular()))
+            }
+            _ => {
+                let word = self.read_regular();
+                classify_word(word)
+            }
+        };
+        Some(token)
+    }
+}
+
+fn decode_name(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'#' && i + 2 < bytes.len() {
+            if let (Some(h), Some(l)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                out.push((h << 4) | l);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn classify_word(word: &[u8]) -> PsToken {
+    let text = String::from_utf8_lossy(word);
+    if let Ok(n) = text.parse::<i64>() {
+        return PsToken::Integer(n);
+    }
+    if let Some((radix, digits)) = text.split_once('#') {
+        if let Ok(radix) = radix.parse::<u32>() {
+            if (2..=36).contains(&radix) {
+                if let Ok(n) = i64::from_str_radix(digits, radix) {
+                    return PsToken::Integer(n);
+                }
+            }
+        }
+    }
+    let looks_numeric = text
+        .bytes()
+        .next()
+        .map(|c| c.is_ascii_digit() || matches!(c, b'+' | b'-' | b'.'))
+        .unwrap_or(false);
+    if looks_numeric {
+        if let Ok(n) = text.parse::<f64>() {
+            return PsToken::Real(n);
+        }
+    }
+    PsToken::Operator(text.into_owned())
+}
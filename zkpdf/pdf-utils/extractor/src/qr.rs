@@ -0,0 +1,1066 @@
+//! QR code detection and decoding in page images. Finder patterns are
+//! located on a binarised image, the module grid is sampled through the
+//! affine map the three patterns span (images in PDFs are not seen in
+//! perspective), and the codewords are corrected with Reed-Solomon before
+//! their segments are decoded.
+
+use crate::graphics::{transform, PathBounds};
+use crate::images::gray_pixels;
+use crate::types::{PageImage, QrCode};
+
+// Finder pattern candidates kept for pairing into symbols, most seen first.
+const MAX_FINDERS: usize = 16;
+
+// Error correction codewords per block, by level (L, M, Q, H) and version.
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+// Error correction blocks, by level (L, M, Q, H) and version.
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Decodes the QR codes in an image drawn on page `page`. Images whose
+/// pixels cannot be decoded (CCITT, JBIG2, JPEG 2000, progressive JPEG)
+/// yield nothing.
+pub fn image_qr_codes(image: &PageImage, page: usize) -> Vec<QrCode> {
+    let Some((width, height, pixels)) = gray_pixels(image) else {
+        return Vec::new();
+    };
+    let bitmap = Bitmap::new(width, height, &pixels);
+    let mut symbols = bitmap.symbols();
+    if symbols.is_empty() {
+        // Light modules on a dark ground.
+        symbols = bitmap.inverted().symbols();
+    }
+    symbols
+        .into_iter()
+        .map(|symbol| {
+            // Pixel corners to the unit square, whose top row is the
+            // image's first, and on to the page.
+            let mut bounds = PathBounds::default();
+            for (x, y) in symbol.corners {
+                let unit = (x / width as f64, 1.0 - y / height as f64);
+                bounds.add_point(transform(&image.matrix, unit));
+            }
+            let text = match String::from_utf8(symbol.payload.clone()) {
+                Ok(text) => text,
+                Err(_) => symbol.payload.iter().map(|&b| char::from(b)).collect(),
+            };
+            QrCode {
+                page,
+                rect: bounds.take().unwrap_or(image.rect),
+                version: symbol.version,
+                payload: symbol.payload,
+                text,
+            }
+        })
+        .collect()
+}
+
+struct Symbol {
+    // Image coordinates of the symbol's corners, in pixels from the top left.
+    corners: [(f64, f64); 4],
+    version: u8,
+    payload: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct Finder {
+    x: f64,
+    y: f64,
+    module: f64,
+    seen: usize,
+}
+
+struct Bitmap {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+impl Bitmap {
+    // Splits the pixels at the threshold that best separates the two
+    // levels of the histogram (Otsu's method).
+    fn new(width: usize, height: usize, pixels: &[u8]) -> Self {
+        let mut histogram = [0usize; 256];
+        for &p in pixels {
+            histogram[usize::from(p)] += 1;
+        }
+        let total = pixels.len() as f64;
+        let sum: f64 = (0..256).map(|i| i as f64 * histogram[i] as f64).sum();
+        let (mut below, mut below_sum) = (0.0, 0.0);
+        let (mut best, mut threshold) = (0.0, 128);
+        for (level, &count) in histogram.iter().enumerate() {
+            below += count as f64;
+            below_sum += level as f64 * count as f64;
+            let above = total - below;
+            if below == 0.0 || above == 0.0 {
+                continue;
+            }
+            let gap = below_sum / below - (sum - below_sum) / above;
+            let variance = below * above * gap * gap;
+            if variance > best {
+                best = variance;
+                threshold = level;
+            }
+        }
+        Bitmap {
+            width,
+            height,
+            dark: pixels
+                .iter()
+                .map(|&p| usize::from(p) <= threshold)
+                .collect(),
+        }
+    }
+
+    fn inverted(&self) -> Self {
+        Bitmap {
+            width: self.width,
+            height: self.height,
+            dark: self.dark.iter().map(|d| !d).collect(),
+        }
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.width + x]
+    }
+
+    // Every symbol whose three finder patterns are found and whose data
+    // decodes.
+    fn symbols(&self) -> Vec<Symbol> {
+        let finders = self.finders();
+        let mut used = vec![false; finders.len()];
+        let mut symbols = Vec::new();
+        for i in 0..finders.len() {
+            for j in i + 1..finders.len() {
+                for k in j + 1..finders.len() {
+                    if used[i] || used[j] || used[k] {
+                        continue;
+                    }
+                    if let Some(symbol) = self.symbol([finders[i], finders[j], finders[k]]) {
+                        used[i] = true;
+                        used[j] = true;
+                        used[k] = true;
+                        symbols.push(symbol);
+                    }
+                }
+            }
+        }
+        symbols
+    }
+
+    // Scans each row for the 1:1:3:1:1 runs across a finder pattern, then
+    // confirms them down the column and across the row through the centre.
+    fn finders(&self) -> Vec<Finder> {
+        let mut finders: Vec<Finder> = Vec::new();
+        for y in 0..self.height {
+            let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+            for x in 0..self.width {
+                let dark = self.is_dark(x, y);
+                match runs.last_mut() {
+                    Some((_, len, colour)) if *colour == dark => *len += 1,
+                    _ => runs.push((x, 1, dark)),
+                }
+            }
+            for window in runs.windows(5) {
+                let counts = [
+                    window[0].1,
+                    window[1].1,
+                    window[2].1,
+                    window[3].1,
+                    window[4].1,
+                ];
+                if !window[0].2 || !finder_ratio(&counts) {
+                    continue;
+                }
+                let centre = window[2].0 + window[2].1 / 2;
+                let Some((cy, vertical)) = self.cross_check(centre, y, true) else {
+                    continue;
+                };
+                let Some((cx, horizontal)) = self.cross_check(centre, cy as usize, false) else {
+                    continue;
+                };
+                let module = (vertical + horizontal) / 14.0;
+                match finders.iter_mut().find(|f| {
+                    (f.x - cx).abs() <= f.module.max(module)
+                        && (f.y - cy).abs() <= f.module.max(module)
+                }) {
+                    Some(f) => {
+                        let weight = f.seen as f64;
+                        f.x = (f.x * weight + cx) / (weight + 1.0);
+                        f.y = (f.y * weight + cy) / (weight + 1.0);
+                        f.module = (f.module * weight + module) / (weight + 1.0);
+                        f.seen += 1;
+                    }
+                    None => finders.push(Finder {
+                        x: cx,
+                        y: cy,
+                        module,
+                        seen: 1,
+                    }),
+                }
+            }
+        }
+        // A pattern crosses at least its three centre rows.
+        finders.retain(|f| f.seen >= 2);
+        finders.sort_by_key(|f| std::cmp::Reverse(f.seen));
+        finders.truncate(MAX_FINDERS);
+        finders
+    }
+
+    // Counts the five runs through (x, y) along a column or row. Returns the
+    // centre of the middle run and the length of all five.
+    fn cross_check(&self, x: usize, y: usize, vertical: bool) -> Option<(f64, f64)> {
+        let (centre, len) = if vertical {
+            (y, self.height)
+        } else {
+            (x, self.width)
+        };
+        let at = |i: usize| {
+            if vertical {
+                self.is_dark(x, i)
+            } else {
+                self.is_dark(i, y)
+            }
+        };
+        if !at(centre) {
+            return None;
+        }
+        let mut counts = [0usize; 5];
+        let mut i = centre as isize;
+        for state in [2, 1, 0] {
+            while i >= 0 && at(i as usize) == (state != 1) {
+                counts[state] += 1;
+                i -= 1;
+            }
+        }
+        let mut end = centre + 1;
+        for state in [2, 3, 4] {
+            while end < len && at(end) == (state != 3) {
+                counts[state] += 1;
+                end += 1;
+            }
+        }
+        if !finder_ratio(&counts) {
+            return None;
+        }
+        let middle = end as f64 - (counts[4] + counts[3]) as f64 - counts[2] as f64 / 2.0;
+        Some((middle, counts.iter().sum::<usize>() as f64))
+    }
+
+    // Orders three finder patterns as the top-left corner and its two
+    // neighbours, and tries the symbol sizes their spacing allows.
+    fn symbol(&self, finders: [Finder; 3]) -> Option<Symbol> {
+        let modules = finders.map(|f| f.module);
+        let (smallest, largest) = (
+            modules.iter().copied().fold(f64::MAX, f64::min),
+            modules.iter().copied().fold(0.0, f64::max),
+        );
+        if largest > smallest * 1.5 {
+            return None;
+        }
+        for corner in 0..3 {
+            let top_left = finders[corner];
+            let (mut a, mut b) = (finders[(corner + 1) % 3], finders[(corner + 2) % 3]);
+            let (ax, ay) = (a.x - top_left.x, a.y - top_left.y);
+            let (bx, by) = (b.x - top_left.x, b.y - top_left.y);
+            let (la, lb) = (ax.hypot(ay), bx.hypot(by));
+            if la == 0.0 || lb == 0.0 || (la - lb).abs() > 0.2 * la.max(lb) {
+                continue;
+            }
+            if ((ax * bx + ay * by) / (la * lb)).abs() > 0.2 {
+                continue;
+            }
+            // With y pointing down, top right follows top left clockwise.
+            if ax * by - ay * bx < 0.0 {
+                std::mem::swap(&mut a, &mut b);
+            }
+            let module = modules.iter().sum::<f64>() / 3.0;
+            let estimate = ((la + lb) / 2.0 / module).round() as i64 + 7;
+            let nearest = (estimate - 17 + 2).div_euclid(4) * 4 + 17;
+            for size in [nearest, nearest - 4, nearest + 4] {
+                if !(21..=177).contains(&size) {
+                    continue;
+                }
+                let size = size as usize;
+                let grid = self.sample(&top_left, &a, &b, size);
+                if let Some((version, payload)) = decode_grid(&grid, size) {
+                    let corner = |u: f64, v: f64| {
+                        let span = size as f64 - 7.0;
+                        (
+                            top_left.x
+                                + (u - 3.5) / span * (a.x - top_left.x)
+                                + (v - 3.5) / span * (b.x - top_left.x),
+                            top_left.y
+                                + (u - 3.5) / span * (a.y - top_left.y)
+                                + (v - 3.5) / span * (b.y - top_left.y),
+                        )
+                    };
+                    let s = size as f64;
+                    return Some(Symbol {
+                        corners: [
+                            corner(0.0, 0.0),
+                            corner(s, 0.0),
+                            corner(0.0, s),
+                            corner(s, s),
+                        ],
+                        version,
+                        payload,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    // Reads the module at the centre of each grid cell, given the finder
+    // pattern centres, which sit 3.5 modules in from the symbol's corners.
+    fn sample(&self, top_left: &Finder, right: &Finder, below: &Finder, size: usize) -> Vec<bool> {
+        let span = size as f64 - 7.0;
+        let mut grid = Vec::with_capacity(size * size);
+        for row in 0..size {
+            for col in 0..size {
+                let (u, v) = ((col as f64 - 3.0) / span, (row as f64 - 3.0) / span);
+                let x = top_left.x + u * (right.x - top_left.x) + v * (below.x - top_left.x);
+                let y = top_left.y + u * (right.y - top_left.y) + v * (below.y - top_left.y);
+                let dark = x >= 0.0
+                    && y >= 0.0
+                    && (x as usize) < self.width
+                    && (y as usize) < self.height
+                    && self.is_dark(x as usize, y as usize);
+                grid.push(dark);
+            }
+        }
+        grid
+    }
+}
+
+// Whether five runs are in the 1:1:3:1:1 proportion of a finder pattern,
+// allowing half a module either way.
+fn finder_ratio(counts: &[usize; 5]) -> bool {
+    let total: usize = counts.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f64 / 7.0;
+    let tolerance = module / 2.0;
+    counts
+        .iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(&count, share)| (count as f64 - module * share).abs() < tolerance * share)
+}
+
+/// Decodes a symbol of `size` modules a side, given row by row with dark
+/// modules `true`. Returns its version and payload.
+pub fn decode_grid(grid: &[bool], size: usize) -> Option<(u8, Vec<u8>)> {
+    let version = (size.checked_sub(17)? / 4) as u8;
+    if !(1..=40).contains(&version) || size != 17 + 4 * usize::from(version) {
+        return None;
+    }
+    let module = |x: usize, y: usize| grid[y * size + x];
+    let (level, mask) = format_info(&module, size)?;
+    let function = function_modules(version);
+
+    // Codewords run in two-module columns from the bottom right, upwards
+    // and downwards in turn, skipping the vertical timing pattern.
+    let total = raw_data_modules(version) / 8;
+    let mut codewords = vec![0u8; total];
+    let mut bit = 0;
+    let mut right = size - 1;
+    while right >= 1 && bit < total * 8 {
+        if right == 6 {
+            right = 5;
+        }
+        for vert in 0..size {
+            for j in 0..2 {
+                let x = right - j;
+                let upward = (right + 1) & 2 == 0;
+                let y = if upward { size - 1 - vert } else { vert };
+                if function[y * size + x] || bit >= total * 8 {
+                    continue;
+                }
+                if module(x, y) != masked(mask, x, y) {
+                    codewords[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        if right < 2 {
+            break;
+        }
+        right -= 2;
+    }
+
+    let data = correct_blocks(&codewords, version, level)?;
+    Some((version, decode_segments(&data, version)?))
+}
+
+// The error correction level (as an index into the block tables) and mask
+// from either copy of the 15-bit format information.
+fn format_info(module: &dyn Fn(usize, usize) -> bool, size: usize) -> Option<(usize, u8)> {
+    let mut first = 0u32;
+    let mut second = 0u32;
+    for i in 0..15 {
+        let (x, y) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        first |= u32::from(module(x, y)) << i;
+        let (x, y) = if i < 8 {
+            (size - 1 - i, 8)
+        } else {
+            (8, size - 15 + i)
+        };
+        second |= u32::from(module(x, y)) << i;
+    }
+    let (distance, data) = (0..32u32)
+        .flat_map(|data| {
+            let code = format_bits(data);
+            [first, second].map(|read| ((read ^ code).count_ones(), data))
+        })
+        .min()?;
+    if distance > 3 {
+        return None;
+    }
+    // Levels are coded M, L, H, Q.
+    let level = [1, 0, 3, 2][(data >> 3) as usize];
+    Some((level, (data & 7) as u8))
+}
+
+// Five bits of level and mask with their BCH check bits, masked.
+fn format_bits(data: u32) -> u32 {
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    ((data << 10) | remainder) ^ 0x5412
+}
+
+fn masked(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+// Modules given to finder, timing and alignment patterns and to format and
+// version information, which carry no data.
+fn function_modules(version: u8) -> Vec<bool> {
+    let size = 17 + 4 * usize::from(version);
+    let mut function = vec![false; size * size];
+    let mut fill = |x0: usize, y0: usize, w: usize, h: usize| {
+        for y in y0..y0 + h {
+            for x in x0..x0 + w {
+                function[y * size + x] = true;
+            }
+        }
+    };
+    // Finders with separators and format information.
+    fill(0, 0, 9, 9);
+    fill(size - 8, 0, 8, 9);
+    fill(0, size - 8, 9, 8);
+    // Timing patterns.
+    fill(6, 0, 1, size);
+    fill(0, 6, size, 1);
+    let positions = alignment_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, &y) in positions.iter().enumerate() {
+        for (j, &x) in positions.iter().enumerate() {
+            if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                continue;
+            }
+            fill(x - 2, y - 2, 5, 5);
+        }
+    }
+    if version >= 7 {
+        fill(size - 11, 0, 3, 6);
+        fill(0, size - 11, 6, 3);
+    }
+    function
+}
+
+fn alignment_positions(version: u8) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let version = usize::from(version);
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let mut positions = vec![6];
+    let mut pos = 17 + 4 * version - 7;
+    while positions.len() < count {
+        positions.insert(1, pos);
+        pos -= step;
+    }
+    positions
+}
+
+fn raw_data_modules(version: u8) -> usize {
+    let v = usize::from(version);
+    let mut modules = (16 * v + 128) * v + 64;
+    if v >= 2 {
+        let alignments = v / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if v >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+// Separates the interleaved blocks, corrects each, and joins their data
+// codewords.
+fn correct_blocks(codewords: &[u8], version: u8, level: usize) -> Option<Vec<u8>> {
+    let v = usize::from(version);
+    let blocks = usize::from(ERROR_CORRECTION_BLOCKS[level][v]);
+    let ecc = usize::from(ECC_CODEWORDS_PER_BLOCK[level][v]);
+    let short_blocks = blocks - codewords.len() % blocks;
+    let short_len = codewords.len() / blocks;
+    // Long blocks carry one more data codeword, interleaved after the last
+    // one short blocks have.
+    let mut split: Vec<Vec<u8>> = vec![Vec::with_capacity(short_len + 1); blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_len {
+        for (b, block) in split.iter_mut().enumerate() {
+            if i == short_len - ecc && b < short_blocks {
+                continue;
+            }
+            block.push(*next.next()?);
+        }
+    }
+    let mut data = Vec::new();
+    for mut block in split {
+        reed_solomon::correct(&mut block, ecc)?;
+        data.extend_from_slice(&block[..block.len() - ecc]);
+    }
+    Some(data)
+}
+
+struct BitStream<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitStream<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, n: usize) -> Option<u32> {
+        if n > self.remaining() {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..n {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+// The bytes of every segment in order: digits and alphanumeric characters
+// as ASCII, byte segments as they are and kanji as Shift JIS.
+fn decode_segments(data: &[u8], version: u8) -> Option<Vec<u8>> {
+    let group = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut bits = BitStream { data, pos: 0 };
+    let mut payload = Vec::new();
+    while bits.remaining() >= 4 {
+        match bits.read(4)? {
+            0 => break,
+            1 => {
+                let mut count = bits.read([10, 12, 14][group])? as usize;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = bits.read([4, 7, 10][digits - 1])?;
+                    if value >= 10u32.pow(digits as u32) {
+                        return None;
+                    }
+                    payload.extend(format!("{value:0digits$}").bytes());
+                    count -= digits;
+                }
+            }
+            2 => {
+                let mut count = bits.read([9, 11, 13][group])? as usize;
+                while count > 0 {
+                    if count >= 2 {
+                        let value = bits.read(11)? as usize;
+                        payload.push(*ALPHANUMERIC.get(value / 45)?);
+                        payload.push(ALPHANUMERIC[value % 45]);
+                        count -= 2;
+                    } else {
+                        payload.push(*ALPHANUMERIC.get(bits.read(6)? as usize)?);
+                        count -= 1;
+                    }
+                }
+            }
+            4 => {
+                let count = bits.read([8, 16, 16][group])?;
+                for _ in 0..count {
+                    payload.push(bits.read(8)? as u8);
+                }
+            }
+            8 => {
+                let count = bits.read([8, 10, 12][group])?;
+                for _ in 0..count {
+                    let value = bits.read(13)?;
+                    let packed = ((value / 0xC0) << 8) | (value % 0xC0);
+                    let code = packed + if packed < 0x1F00 { 0x8140 } else { 0xC140 };
+                    payload.extend_from_slice(&(code as u16).to_be_bytes());
+                }
+            }
+            // ECI designators: the payload is kept as bytes.
+            7 => {
+                let first = bits.read(8)?;
+                if first & 0x80 != 0 {
+                    bits.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            // Structured append: position, count and parity.
+            3 => {
+                bits.read(16)?;
+            }
+            5 => {}
+            9 => {
+                bits.read(8)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(payload)
+}
+
+mod reed_solomon {
+    //! Reed-Solomon decoding over GF(256) with the QR code generator
+    //! polynomial, whose roots are α^0 to α^(n-1).
+
+    struct Field {
+        exp: [u8; 512],
+        log: [u8; 256],
+    }
+
+    impl Field {
+        fn new() -> Self {
+            let mut field = Field {
+                exp: [0; 512],
+                log: [0; 256],
+            };
+            let mut x = 1u16;
+            for i in 0..255 {
+                field.exp[i] = x as u8;
+                field.log[x as usize] = i as u8;
+                x <<= 1;
+                if x & 0x100 != 0 {
+                    x ^= 0x11D;
+                }
+            }
+            for i in 255..512 {
+                field.exp[i] = field.exp[i - 255];
+            }
+            field
+        }
+
+        fn mul(&self, a: u8, b: u8) -> u8 {
+            if a == 0 || b == 0 {
+                0
+            } else {
+                self.exp
+                    [usize::from(self.log[usize::from(a)]) + usize::from(self.log[usize::from(b)])]
+            }
+        }
+
+        fn div(&self, a: u8, b: u8) -> u8 {
+            if a == 0 {
+                0
+            } else {
+                self.exp[usize::from(self.log[usize::from(a)]) + 255
+                    - usize::from(self.log[usize::from(b)])]
+            }
+        }
+
+        // α^power.
+        fn pow(&self, power: usize) -> u8 {
+            self.exp[power % 255]
+        }
+
+        // Evaluates a polynomial given lowest degree first.
+        fn eval(&self, poly: &[u8], x: u8) -> u8 {
+            poly.iter().rev().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+        }
+    }
+
+    // Syndromes of a block whose first codeword is the highest-degree
+    // coefficient.
+    fn syndromes(field: &Field, block: &[u8], ecc: usize) -> Vec<u8> {
+        (0..ecc)
+            .map(|i| {
+                let x = field.pow(i);
+                block.iter().fold(0, |acc, &c| field.mul(acc, x) ^ c)
+            })
+            .collect()
+    }
+
+    /// Corrects `block` in place, whose last `ecc` codewords are error
+    /// correction. `None` when there are more errors than can be corrected.
+    pub fn correct(block: &mut [u8], ecc: usize) -> Option<()> {
+        let field = Field::new();
+        let syndromes = syndromes(&field, block, ecc);
+        if syndromes.iter().all(|&s| s == 0) {
+            return Some(());
+        }
+
+        // Berlekamp-Massey for the error locator, lowest degree first.
+        let mut locator = vec![1u8];
+        let mut previous = vec![1u8];
+        let (mut errors, mut shift, mut last) = (0, 1, 1u8);
+        for n in 0..ecc {
+            let discrepancy = (1..=errors).fold(syndromes[n], |acc, i| {
+                acc ^ field.mul(*locator.get(i).unwrap_or(&0), syndromes[n - i])
+            });
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+            let scale = field.div(discrepancy, last);
+            let mut updated = locator.clone();
+            updated.resize(updated.len().max(previous.len() + shift), 0);
+            for (i, &c) in previous.iter().enumerate() {
+                updated[i + shift] ^= field.mul(scale, c);
+            }
+            if 2 * errors <= n {
+                previous = std::mem::replace(&mut locator, updated);
+                errors = n + 1 - errors;
+                last = discrepancy;
+                shift = 1;
+            } else {
+                locator = updated;
+                shift += 1;
+            }
+        }
+        if 2 * errors > ecc {
+            return None;
+        }
+
+        // The evaluator Ω = SΛ mod x^ecc, and the locator's formal
+        // derivative, which keeps the odd terms.
+        let mut evaluator = vec![0u8; ecc];
+        for (i, &s) in syndromes.iter().enumerate() {
+            for (j, &l) in locator.iter().enumerate() {
+                if i + j < ecc {
+                    evaluator[i + j] ^= field.mul(s, l);
+                }
+            }
+        }
+        let derivative: Vec<u8> = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, &c)| if i % 2 == 1 { c } else { 0 })
+            .collect();
+
+        // Chien search over the block's positions, with Forney's formula
+        // for each error's value.
+        let len = block.len();
+        let mut found = 0;
+        for (position, codeword) in block.iter_mut().enumerate() {
+            let power = len - 1 - position;
+            let inverse = field.pow(255 - power % 255);
+            if field.eval(&locator, inverse) != 0 {
+                continue;
+            }
+            let denominator = field.eval(&derivative, inverse);
+            if denominator == 0 {
+                return None;
+            }
+            let value = field.div(field.eval(&evaluator, inverse), denominator);
+            *codeword ^= field.mul(field.pow(power), value);
+            found += 1;
+        }
+        if found != errors || syndromes_nonzero(&field, block, ecc) {
+            return None;
+        }
+        Some(())
+    }
+
+    fn syndromes_nonzero(field: &Field, block: &[u8], ecc: usize) -> bool {
+        syndromes(field, block, ecc).iter().any(|&s| s != 0)
+    }
+
+    #[cfg(test)]
+    pub(super) fn remainder(data: &[u8], ecc: usize) -> Vec<u8> {
+        let field = Field::new();
+        // The generator Π (x - α^i), highest degree first.
+        let mut generator = vec![1u8];
+        for i in 0..ecc {
+            let mut next = vec![0u8; generator.len() + 1];
+            for (j, &g) in generator.iter().enumerate() {
+                next[j] ^= g;
+                next[j + 1] ^= field.mul(g, field.pow(i));
+            }
+            generator = next;
+        }
+        let mut remainder = data.to_vec();
+        remainder.resize(data.len() + ecc, 0);
+        for i in 0..data.len() {
+            let factor = remainder[i];
+            for (j, &g) in generator.iter().enumerate() {
+                remainder[i + j] ^= field.mul(g, factor);
+            }
+        }
+        remainder.split_off(data.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a byte-mode symbol for `text` at `version`, level M (index 1)
+    /// and `mask`, row by row with dark modules `true`.
+    pub(crate) fn symbol(text: &[u8], version: u8, mask: u8) -> (Vec<bool>, usize) {
+        let level = 1;
+        let v = usize::from(version);
+        let size = 17 + 4 * v;
+        let total = raw_data_modules(version) / 8;
+        let blocks = usize::from(ERROR_CORRECTION_BLOCKS[level][v]);
+        let ecc = usize::from(ECC_CODEWORDS_PER_BLOCK[level][v]);
+        let data_len = total - blocks * ecc;
+
+        let mut bits = Vec::new();
+        let mut push = |value: u32, n: usize| {
+            for i in (0..n).rev() {
+                bits.push((value >> i) & 1 == 1);
+            }
+        };
+        push(4, 4);
+        push(text.len() as u32, if v < 10 { 8 } else { 16 });
+        for &b in text {
+            push(u32::from(b), 8);
+        }
+        push(0, 4);
+        bits.resize(bits.len().div_ceil(8) * 8, false);
+        let mut data: Vec<u8> = bits
+            .chunks(8)
+            .map(|c| c.iter().fold(0, |acc, &b| (acc << 1) | u8::from(b)))
+            .collect();
+        assert!(data.len() <= data_len);
+        for pad in [0xEC, 0x11].iter().cycle() {
+            if data.len() >= data_len {
+                break;
+            }
+            data.push(*pad);
+        }
+
+        let short_blocks = blocks - total % blocks;
+        let short_len = total / blocks;
+        let mut split = Vec::new();
+        let mut offset = 0;
+        for b in 0..blocks {
+            let len = short_len - ecc + usize::from(b >= short_blocks);
+            let mut block = data[offset..offset + len].to_vec();
+            offset += len;
+            let check = reed_solomon::remainder(&block, ecc);
+            if b < short_blocks {
+                block.push(0);
+            }
+            block.extend(check);
+            split.push(block);
+        }
+        let mut codewords = Vec::new();
+        for i in 0..=short_len {
+            for (b, block) in split.iter().enumerate() {
+                if i != short_len - ecc || b >= short_blocks {
+                    codewords.push(block[i]);
+                }
+            }
+        }
+
+        let mut grid = vec![false; size * size];
+        let mut set = |x: usize, y: usize, dark: bool| grid[y * size + x] = dark;
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if x < 0 || y < 0 || x >= size as i32 || y >= size as i32 {
+                        continue;
+                    }
+                    let ring = dx.abs().max(dy.abs());
+                    set(x as usize, y as usize, ring != 2 && ring != 4);
+                }
+            }
+        }
+        for i in 8..size - 8 {
+            set(6, i, i % 2 == 0);
+            set(i, 6, i % 2 == 0);
+        }
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &y) in positions.iter().enumerate() {
+            for (j, &x) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let ring = dx.abs().max(dy.abs());
+                        set(
+                            (x as i32 + dx) as usize,
+                            (y as i32 + dy) as usize,
+                            ring != 1,
+                        );
+                    }
+                }
+            }
+        }
+        let format = format_bits(mask as u32);
+        for i in 0..15 {
+            let dark = (format >> i) & 1 == 1;
+            let (x, y) = match i {
+                0..=5 => (8, i),
+                6 => (8, 7),
+                7 => (8, 8),
+                8 => (7, 8),
+                _ => (14 - i, 8),
+            };
+            set(x, y, dark);
+            let (x, y) = if i < 8 {
+                (size - 1 - i, 8)
+            } else {
+                (8, size - 15 + i)
+            };
+            set(x, y, dark);
+        }
+        set(8, size - 8, true);
+
+        let function = function_modules(version);
+        let mut bit = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vert in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let y = if (right + 1) & 2 == 0 {
+                        size - 1 - vert
+                    } else {
+                        vert
+                    };
+                    if function[y * size + x] {
+                        continue;
+                    }
+                    let value =
+                        bit < codewords.len() * 8 && codewords[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                    set(x, y, value ^ masked(mask, x, y));
+                    bit += 1;
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+        (grid, size)
+    }
+
+    /// Draws a symbol as gray pixels, `scale` pixels a module, inside a
+    /// four-module quiet zone.
+    pub(crate) fn render(grid: &[bool], size: usize, scale: usize) -> (usize, Vec<u8>) {
+        let side = (size + 8) * scale;
+        let mut pixels = vec![255u8; side * side];
+        for y in 0..size {
+            for x in 0..size {
+                if grid[y * size + x] {
+                    for py in 0..scale {
+                        for px in 0..scale {
+                            pixels[((y + 4) * scale + py) * side + (x + 4) * scale + px] = 0;
+                        }
+                    }
+                }
+            }
+        }
+        (side, pixels)
+    }
+
+    #[test]
+    fn decodes_symbols_and_corrects_errors() {
+        // Known check codewords for "HELLO WORLD" at 1-M.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            reed_solomon::remainder(&data, 10),
+            [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+        // Level L, mask 0.
+        assert_eq!(format_bits(0b01000), 0b111011111000100);
+
+        let text = "01,10,044031900111,12345678,1,260.50,20260115,1234567890,".as_bytes();
+        for (version, mask) in [(4, 3), (7, 6), (10, 1)] {
+            let (mut grid, size) = symbol(text, version, mask);
+            assert_eq!(decode_grid(&grid, size), Some((version, text.to_vec())));
+            // Damage a few data modules.
+            for x in size - 4..size {
+                grid[(size - 1) * size + x] ^= true;
+            }
+            assert_eq!(decode_grid(&grid, size), Some((version, text.to_vec())));
+
+            let (side, pixels) = render(&grid, size, 3);
+            let symbols = Bitmap::new(side, side, &pixels).symbols();
+            assert_eq!(symbols.len(), 1);
+            assert_eq!(symbols[0].payload, text);
+            let (x, y) = symbols[0].corners[0];
+            assert!((x - 12.0).abs() < 1e-9 && (y - 12.0).abs() < 1e-9);
+        }
+    }
+}
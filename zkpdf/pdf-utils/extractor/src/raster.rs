@@ -0,0 +1,373 @@
+//! A small anti-aliased rasteriser for drawing a page to check what it shows:
+//! opaque fills, strokes and images in RGB over white paper. Shadings,
+//! patterns, blend modes, soft masks and dash patterns are not drawn.
+
+use crate::graphics::{multiply, transform, PathSegment};
+
+// Horizontal lines sampled per pixel row when computing coverage.
+const SUBSAMPLES: usize = 4;
+
+// Sides of the polygons standing in for round joins and caps.
+const ROUND_SIDES: usize = 16;
+
+// Largest raster, in pixels and along either side; bigger pages are drawn at
+// a smaller scale.
+const MAX_PIXELS: usize = 1 << 26;
+const MAX_SIDE: usize = 1 << 15;
+
+/// RGBA pixels of a page drawn at some scale.
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    // From the displayed page, in points with the origin at the lower left,
+    // to pixels with the origin at the top left.
+    device: [f64; 6],
+}
+
+impl Raster {
+    /// A white page of `page_width` by `page_height` points drawn at `scale`
+    /// pixels per point, or at the largest scale that keeps the raster within
+    /// `MAX_PIXELS` pixels and `MAX_SIDE` on a side.
+    pub fn new(page_width: f64, page_height: f64, scale: f64) -> Raster {
+        let (page_width, page_height) = (page_width.abs(), page_height.abs());
+        let limit = (MAX_SIDE as f64 / page_width.max(page_height))
+            .min((MAX_PIXELS as f64 / (page_width * page_height)).sqrt());
+        let scale = scale.min(limit);
+        let side = |points: f64| ((points * scale).ceil().max(1.0) as usize).min(MAX_SIDE);
+        let (width, height) = (side(page_width), side(page_height));
+        let (width, height, len) = match width.checked_mul(height).and_then(|n| n.checked_mul(4)) {
+            Some(len) => (width, height, len),
+            None => (1, 1, 4),
+        };
+        Raster {
+            width,
+            height,
+            pixels: vec![255; len],
+            device: [scale, 0.0, 0.0, -scale, 0.0, page_height * scale],
+        }
+    }
+
+    /// Pixels per point.
+    pub fn scale(&self) -> f64 {
+        self.device[0]
+    }
+
+    /// Fills `path`, given on the displayed page, with the nonzero or
+    /// even-odd rule, within the `clip` box when there is one.
+    pub fn fill(
+        &mut self,
+        path: &[PathSegment],
+        even_odd: bool,
+        color: [f64; 3],
+        alpha: f64,
+        clip: Option<[f64; 4]>,
+    ) {
+        let polygons: Vec<_> = self.subpaths(path).into_iter().map(|(p, _)| p).collect();
+        self.fill_polygons(&polygons, even_odd, color, alpha, clip);
+    }
+
+    /// Strokes `path` at `width` points with round joins and caps; hairlines
+    /// are drawn one pixel wide.
+    pub fn stroke(
+        &mut self,
+        path: &[PathSegment],
+        width: f64,
+        color: [f64; 3],
+        clip: Option<[f64; 4]>,
+    ) {
+        let radius = (width * self.scale()).max(1.0) / 2.0;
+        let mut polygons = Vec::new();
+        for (points, closed) in self.subpaths(path) {
+            let mut points = points;
+            if closed && points.len() > 1 {
+                points.push(points[0]);
+            }
+            for pair in points.windows(2) {
+                let [(x0, y0), (x1, y1)] = [pair[0], pair[1]];
+                let length = (x1 - x0).hypot(y1 - y0);
+                if length > 0.0 {
+                    let (nx, ny) = (-(y1 - y0) / length * radius, (x1 - x0) / length * radius);
+                    polygons.push(vec![
+                        (x0 + nx, y0 + ny),
+                        (x1 + nx, y1 + ny),
+                        (x1 - nx, y1 - ny),
+                        (x0 - nx, y0 - ny),
+                    ]);
+                }
+            }
+            for &(x, y) in &points {
+                polygons.push(
+                    (0..ROUND_SIDES)
+                        .map(|k| {
+                            let angle = k as f64 * std::f64::consts::TAU / ROUND_SIDES as f64;
+                            (x + radius * angle.cos(), y + radius * angle.sin())
+                        })
+                        .collect(),
+                );
+            }
+        }
+        // Give every piece the same orientation so that the nonzero rule
+        // paints their union once.
+        for polygon in &mut polygons {
+            if signed_area(polygon) < 0.0 {
+                polygon.reverse();
+            }
+        }
+        self.fill_polygons(&polygons, false, color, 1.0, clip);
+    }
+
+    /// Draws an image of `width` by `height` RGB pixels, first row at the
+    /// top, into the unit square mapped by `matrix` onto the displayed page.
+    /// With `mask_color`, the image is a stencil painting that colour where
+    /// its pixels are black.
+    pub fn draw_image(
+        &mut self,
+        matrix: &[f64; 6],
+        width: usize,
+        height: usize,
+        rgb: &[u8],
+        mask_color: Option<[f64; 3]>,
+        clip: Option<[f64; 4]>,
+    ) {
+        let m = multiply(matrix, &self.device);
+        let det = m[0] * m[3] - m[1] * m[2];
+        if det.abs() < 1e-12 || width == 0 || height == 0 || rgb.len() < width * height * 3 {
+            return;
+        }
+        let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|p| transform(&m, p));
+        let Some((x_range, y_range)) = self.pixel_range(&corners, clip) else {
+            return;
+        };
+        for py in y_range {
+            for px in x_range.clone() {
+                // Map the pixel centre back into the unit square.
+                let (dx, dy) = (px as f64 + 0.5 - m[4], py as f64 + 0.5 - m[5]);
+                let u = (dx * m[3] - dy * m[2]) / det;
+                let v = (dy * m[0] - dx * m[1]) / det;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let column = ((u * width as f64) as usize).min(width - 1);
+                let row = (((1.0 - v) * height as f64) as usize).min(height - 1);
+                let sample = &rgb[(row * width + column) * 3..][..3];
+                let color = match mask_color {
+                    Some(color) if sample[0] == 0 => color.map(|c| c * 255.0),
+                    Some(_) => continue,
+                    None => [sample[0], sample[1], sample[2]].map(f64::from),
+                };
+                self.blend(px, py, color, 1.0);
+            }
+        }
+    }
+
+    // Fills polygons given in pixels, sampling several lines per pixel row
+    // and covering partial pixels along each line exactly.
+    fn fill_polygons(
+        &mut self,
+        polygons: &[Vec<(f64, f64)>],
+        even_odd: bool,
+        color: [f64; 3],
+        alpha: f64,
+        clip: Option<[f64; 4]>,
+    ) {
+        let points: Vec<(f64, f64)> = polygons.iter().flatten().copied().collect();
+        let Some((x_range, y_range)) = self.pixel_range(&points, clip) else {
+            return;
+        };
+        let edges: Vec<((f64, f64), (f64, f64))> = polygons
+            .iter()
+            .filter(|p| p.len() > 2)
+            .flat_map(|p| (0..p.len()).map(move |k| (p[k], p[(k + 1) % p.len()])))
+            .filter(|(a, b)| a.1 != b.1)
+            .collect();
+        let (left, right) = (x_range.start as f64, x_range.end as f64);
+        let mut coverage = vec![0.0f64; x_range.len()];
+        let mut crossings: Vec<(f64, i32)> = Vec::new();
+        for py in y_range {
+            coverage.iter_mut().for_each(|c| *c = 0.0);
+            for sub in 0..SUBSAMPLES {
+                let y = py as f64 + (sub as f64 + 0.5) / SUBSAMPLES as f64;
+                crossings.clear();
+                for &((x0, y0), (x1, y1)) in &edges {
+                    if (y0 <= y) != (y1 <= y) {
+                        let x = x0 + (y - y0) / (y1 - y0) * (x1 - x0);
+                        crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    let inside = if even_odd {
+                        winding % 2 != 0
+                    } else {
+                        winding != 0
+                    };
+                    if inside {
+                        let (x0, x1) = (pair[0].0.clamp(left, right), pair[1].0.clamp(left, right));
+                        add_span(&mut coverage, x0 - left, x1 - left);
+                    }
+                }
+            }
+            for (k, &cover) in coverage.iter().enumerate() {
+                let cover = (cover / SUBSAMPLES as f64).min(1.0);
+                if cover > 0.0 {
+                    self.blend(
+                        x_range.start + k,
+                        py,
+                        color.map(|c| c * 255.0),
+                        alpha * cover,
+                    );
+                }
+            }
+        }
+    }
+
+    // The pixel rows and columns covering `points`, limited to the raster
+    // and to `clip`, given on the displayed page.
+    fn pixel_range(
+        &self,
+        points: &[(f64, f64)],
+        clip: Option<[f64; 4]>,
+    ) -> Option<(std::ops::Range<usize>, std::ops::Range<usize>)> {
+        let mut bounds = [0.0, 0.0, self.width as f64, self.height as f64];
+        if let Some([x0, y0, x1, y1]) = clip {
+            let (a, b) = (
+                transform(&self.device, (x0, y0)),
+                transform(&self.device, (x1, y1)),
+            );
+            bounds = [
+                bounds[0].max(a.0.min(b.0)),
+                bounds[1].max(a.1.min(b.1)),
+                bounds[2].min(a.0.max(b.0)),
+                bounds[3].min(a.1.max(b.1)),
+            ];
+        }
+        let min_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        let max_x = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+        let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let x0 = min_x.max(bounds[0]).floor().max(0.0) as usize;
+        let x1 = max_x.min(bounds[2]).ceil().max(0.0) as usize;
+        let y0 = min_y.max(bounds[1]).floor().max(0.0) as usize;
+        let y1 = max_y.min(bounds[3]).ceil().max(0.0) as usize;
+        (x0 < x1 && y0 < y1).then_some((x0..x1, y0..y1))
+    }
+
+    // The subpaths of a path as lines between points in pixels, with curves
+    // split into short lines, and whether each was closed.
+    fn subpaths(&self, path: &[PathSegment]) -> Vec<(Vec<(f64, f64)>, bool)> {
+        let mut subpaths = Vec::new();
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        let mut start = (0.0, 0.0);
+        for segment in path {
+            let current = points.last().copied().unwrap_or(start);
+            match segment.transformed(&self.device) {
+                PathSegment::MoveTo(p) => {
+                    if !points.is_empty() {
+                        subpaths.push((std::mem::take(&mut points), false));
+                    }
+                    start = p;
+                    points.push(p);
+                }
+                PathSegment::LineTo(p) => {
+                    if points.is_empty() {
+                        points.push(current);
+                    }
+                    points.push(p);
+                }
+                PathSegment::QuadTo(c, p) => {
+                    if points.is_empty() {
+                        points.push(current);
+                    }
+                    let steps = curve_steps(&[current, c, p]);
+                    points.extend((1..=steps).map(|k| {
+                        let t = k as f64 / steps as f64;
+                        let s = 1.0 - t;
+                        (
+                            s * s * current.0 + 2.0 * s * t * c.0 + t * t * p.0,
+                            s * s * current.1 + 2.0 * s * t * c.1 + t * t * p.1,
+                        )
+                    }));
+                }
+                PathSegment::CubicTo(c1, c2, p) => {
+                    if points.is_empty() {
+                        points.push(current);
+                    }
+                    let steps = curve_steps(&[current, c1, c2, p]);
+                    points.extend((1..=steps).map(|k| {
+                        let t = k as f64 / steps as f64;
+                        let s = 1.0 - t;
+                        let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+                        (
+                            a * current.0 + b * c1.0 + c * c2.0 + d * p.0,
+                            a * current.1 + b * c1.1 + c * c2.1 + d * p.1,
+                        )
+                    }));
+                }
+                PathSegment::Close => {
+                    if !points.is_empty() {
+                        subpaths.push((std::mem::take(&mut points), true));
+                    }
+                }
+            }
+        }
+        if !points.is_empty() {
+            subpaths.push((points, false));
+        }
+        subpaths
+    }
+
+    // Paints `color`, in 0..=255 per channel, over a pixel with `alpha`.
+    fn blend(&mut self, x: usize, y: usize, color: [f64; 3], alpha: f64) {
+        let pixel = &mut self.pixels[(y * self.width + x) * 4..][..3];
+        for (channel, value) in pixel.iter_mut().zip(color) {
+            let mixed = f64::from(*channel) * (1.0 - alpha) + value * alpha;
+            *channel = mixed.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// Lines to split a curve into, from the length of its control polygon.
+fn curve_steps(points: &[(f64, f64)]) -> usize {
+    let length: f64 = points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .sum();
+    ((length / 3.0).ceil() as usize).clamp(1, 100)
+}
+
+// Adds the coverage of a span from `x0` to `x1` to a row, one sample line's
+// worth, with partial coverage at both ends.
+fn add_span(row: &mut [f64], x0: f64, x1: f64) {
+    if x1 <= x0 {
+        return;
+    }
+    let (first, last) = (x0.floor() as usize, x1.floor() as usize);
+    if first == last {
+        if let Some(cell) = row.get_mut(first) {
+            *cell += x1 - x0;
+        }
+        return;
+    }
+    if let Some(cell) = row.get_mut(first) {
+        *cell += first as f64 + 1.0 - x0;
+    }
+    for cell in row.iter_mut().take(last).skip(first + 1) {
+        *cell += 1.0;
+    }
+    if let Some(cell) = row.get_mut(last) {
+        *cell += x1 - last as f64;
+    }
+}
+
+fn signed_area(polygon: &[(f64, f64)]) -> f64 {
+    (0..polygon.len())
+        .map(|k| {
+            let (a, b) = (polygon[k], polygon[(k + 1) % polygon.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        / 2.0
+}
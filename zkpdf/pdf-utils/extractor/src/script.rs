@@ -0,0 +1,22 @@
+/// Whether `c` belongs to a script written without spaces between words:
+/// Han ideographs, kana, CJK punctuation and full-width forms. Hangul is
+/// excluded, since Korean separates words with spaces.
+pub fn is_unspaced_script(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x2E80..=0x2FDF
+            | 0x3000..=0x30FF
+            | 0x31F0..=0x31FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xF900..=0xFAFF
+            | 0xFF00..=0xFFEF
+            | 0x20000..=0x3FFFF
+    )
+}
+
+/// Sentence-final punctuation after which a line break is kept even between
+/// CJK lines.
+pub fn ends_sentence(c: char) -> bool {
+    matches!(c, '。' | '．' | '！' | '？' | '!' | '?' | '.')
+}
@@ -0,0 +1,108 @@
+use crate::types::PdfFont;
+
+const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// The text-positioning part of the graphics state: text and line matrices
+/// plus the parameters set by `Tc`, `Tw`, `Tz`, `TL` and `Tf`.
+#[derive(Debug, Clone)]
+pub struct TextState {
+    pub matrix: [f64; 6],
+    line_matrix: [f64; 6],
+    pub font_size: f64,
+    pub char_spacing: f64,
+    pub word_spacing: f64,
+    /// `Tz` value divided by 100.
+    pub horizontal_scale: f64,
+    pub leading: f64,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+        }
+    }
+}
+
+impl TextState {
+    /// `BT`: both matrices start over at the identity.
+    pub fn begin_text(&mut self) {
+        self.matrix = IDENTITY;
+        self.line_matrix = IDENTITY;
+    }
+
+    /// `Tm`
+    pub fn set_matrix(&mut self, matrix: [f64; 6]) {
+        self.matrix = matrix;
+        self.line_matrix = matrix;
+    }
+
+    /// `Td`, and `TD` after it has set the leading.
+    pub fn move_line(&mut self, tx: f64, ty: f64) {
+        self.line_matrix = translate(&self.line_matrix, tx, ty);
+        self.matrix = self.line_matrix;
+    }
+
+    /// `T*`, and the implicit line move of `'` and `"`.
+    pub fn next_line(&mut self) {
+        self.move_line(0.0, -self.leading);
+    }
+
+    /// Moves the text matrix along the baseline by `tx` text-space units.
+    pub fn advance(&mut self, tx: f64) {
+        self.matrix = translate(&self.matrix, tx, 0.0);
+    }
+
+    /// Horizontal displacement of one shown glyph in text space, or `None`
+    /// when the font has no width for its code.
+    pub fn glyph_width(&self, code: u32, font: &PdfFont) -> Option<f64> {
+        // Word spacing applies to the single-byte code 32 only.
+        let word_spacing = if code == 32 && font.subtype.as_deref() != Some("Type0") {
+            self.word_spacing
+        } else {
+            0.0
+        };
+        let width = font.advance(code)? * self.font_size + self.char_spacing + word_spacing;
+        Some(width * self.horizontal_scale)
+    }
+
+    /// Origin of the text matrix in the content stream's coordinate space.
+    pub fn origin(&self) -> (f64, f64) {
+        (self.matrix[4], self.matrix[5])
+    }
+
+    /// Font size after scaling by the text matrix.
+    pub fn scaled_font_size(&self) -> f64 {
+        self.font_size * self.matrix[2].hypot(self.matrix[3])
+    }
+
+    /// Offset of `point` from the current origin, measured along the baseline
+    /// and perpendicular to it.
+    pub fn offset_from(&self, point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.origin();
+        let (dx, dy) = (x - point.0, y - point.1);
+        let (a, b) = (self.matrix[0], self.matrix[1]);
+        let len = a.hypot(b);
+        if len == 0.0 {
+            return (dx, dy);
+        }
+        ((dx * a + dy * b) / len, (dy * a - dx * b) / len)
+    }
+}
+
+fn translate(m: &[f64; 6], tx: f64, ty: f64) -> [f64; 6] {
+    [
+        m[0],
+        m[1],
+        m[2],
+        m[3],
+        tx * m[0] + ty * m[2] + m[4],
+        tx * m[1] + ty * m[3] + m[5],
+    ]
+}
@@ -0,0 +1,1047 @@
+use std::collections::HashMap;
+
+use crate::encoding::glyph_to_unicode;
+use crate::graphics::{multiply, transform, PathSegment};
+
+// Largest number of codes expanded from a single format 12 group.
+const MAX_GROUP_LEN: u32 = 0x10000;
+
+// Most codes visited in one cmap subtable, across all of its segments or
+// groups, before the rest of the subtable is ignored.
+const MAX_CMAP_CODES: usize = 0x40000;
+
+/// The parts of an embedded TrueType (`FontFile2`) program needed to recover text.
+#[derive(Debug, Default)]
+pub struct TrueTypeFont {
+    pub num_glyphs: u16,
+    /// Character-to-glyph subtables keyed by (platform ID, encoding ID).
+    pub cmaps: HashMap<(u16, u16), HashMap<u32, u16>>,
+    /// Glyph names from the `post` table, indexed by glyph ID.
+    pub glyph_names: Vec<Option<String>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&self, offset: usize) -> Option<i16> {
+        self.u16(offset).map(|v| v as i16)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn slice(&self, offset: usize, len: usize) -> Option<&'a [u8]> {
+        self.data.get(offset..offset.checked_add(len)?)
+    }
+}
+
+// The tables of an sfnt program by tag, from the first font of a collection.
+fn table_directory(data: &[u8]) -> Result<HashMap<&[u8], &[u8]>, &'static str> {
+    let reader = Reader { data };
+    let mut base = 0;
+    if reader.u32(0) == Some(0x7474_6366) {
+        // TrueType collection ('ttcf'): use the first font.
+        base = reader.u32(12).ok_or("truncated collection header")? as usize;
+    }
+    let num_tables = reader.u16(base + 4).ok_or("truncated sfnt header")?;
+    let mut tables = HashMap::new();
+    for i in 0..num_tables as usize {
+        let record = base + 12 + i * 16;
+        let tag = reader.slice(record, 4).ok_or("truncated table directory")?;
+        let offset = reader.u32(record + 8).ok_or("truncated table directory")? as usize;
+        let length = reader.u32(record + 12).ok_or("truncated table directory")? as usize;
+        if let Some(table) = reader.slice(offset, length) {
+            tables.insert(tag, table);
+        }
+    }
+    Ok(tables)
+}
+
+/// The table `tag` of an sfnt program, such as the `CFF ` table of an
+/// OpenType font.
+pub fn sfnt_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    table_directory(data).ok()?.get(&tag[..]).copied()
+}
+
+/// The number of glyphs in an sfnt program, from its `maxp` table.
+pub fn glyph_count(data: &[u8]) -> Option<u16> {
+    Reader {
+        data: sfnt_table(data, b"maxp")?,
+    }
+    .u16(4)
+}
+
+/// Parses the `maxp`, `cmap` and `post` tables of an sfnt font program.
+pub fn parse_truetype(data: &[u8]) -> Result<TrueTypeFont, &'static str> {
+    let tables = table_directory(data)?;
+
+    let mut font = TrueTypeFont::default();
+    if let Some(maxp) = tables.get(&b"maxp"[..]) {
+        font.num_glyphs = Reader { data: maxp }.u16(4).unwrap_or(0);
+    }
+    if let Some(cmap) = tables.get(&b"cmap"[..]) {
+        font.cmaps = parse_cmap_table(cmap);
+    }
+    if let Some(post) = tables.get(&b"post"[..]) {
+        font.glyph_names = parse_post_table(post, font.num_glyphs);
+    }
+    if font.cmaps.is_empty() && font.glyph_names.is_empty() {
+        return Err("font has neither cmap nor post glyph names");
+    }
+    Ok(font)
+}
+
+impl TrueTypeFont {
+    /// Builds the reverse glyph ID to Unicode table from the Unicode `cmap`
+    /// subtables, filling gaps from `post` glyph names. Of several code points
+    /// sharing a glyph, the lowest wins, except that radicals and
+    /// compatibility ideographs give way to the unified ideograph they look
+    /// like.
+    pub fn gid_to_unicode(&self) -> HashMap<u16, String> {
+        let mut reverse: HashMap<u16, String> = HashMap::new();
+        for key in [
+            (3, 10),
+            (0, 4),
+            (3, 1),
+            (0, 3),
+            (0, 6),
+            (0, 0),
+            (0, 1),
+            (0, 2),
+        ] {
+            if let Some(subtable) = self.cmaps.get(&key) {
+                let mut entries: Vec<(&u32, &u16)> = subtable.iter().collect();
+                entries.sort();
+                for (&code, &gid) in entries {
+                    if gid == 0 {
+                        continue;
+                    }
+                    let Some(ch) = char::from_u32(code) else {
+                        continue;
+                    };
+                    match reverse.get(&gid).and_then(|text| text.chars().next()) {
+                        Some(known) if !is_variant_form(known) || is_variant_form(ch) => {}
+                        _ => {
+                            reverse.insert(gid, ch.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        for (gid, name) in self.glyph_names.iter().enumerate() {
+            if let Some(name) = name {
+                if gid == 0 || gid > u16::MAX as usize {
+                    continue;
+                }
+                if let Some(text) = glyph_to_unicode(name) {
+                    reverse.entry(gid as u16).or_insert(text);
+                }
+            }
+        }
+        reverse
+    }
+
+    /// Glyph ID for a single-byte code of a simple font, using the symbolic
+    /// (3,0) subtable or the Macintosh (1,0) subtable.
+    pub fn simple_code_to_gid(&self, code: u8) -> Option<u16> {
+        if let Some(symbol) = self.cmaps.get(&(3, 0)) {
+            for candidate in [0xF000 | code as u32, code as u32, 0xF100 | code as u32] {
+                if let Some(&gid) = symbol.get(&candidate) {
+                    return Some(gid);
+                }
+            }
+        }
+        self.cmaps.get(&(1, 0))?.get(&(code as u32)).copied()
+    }
+}
+
+// Composite glyphs nest components at most this deep.
+const MAX_COMPOSITE_DEPTH: usize = 8;
+
+// Most glyphs placed plus segments drawn for one outline, however the
+// components fan out.
+const MAX_OUTLINE_WORK: usize = 1 << 18;
+
+/// The outline of glyph `gid` from the `glyf` table, in ems: font units
+/// divided by `unitsPerEm`. Empty for glyphs that draw nothing.
+pub fn glyph_outline(data: &[u8], gid: u16) -> Option<Vec<PathSegment>> {
+    let tables = table_directory(data).ok()?;
+    let head = Reader {
+        data: tables.get(&b"head"[..])?,
+    };
+    let units_per_em = f64::from(head.u16(18)?.max(1));
+    let long_offsets = head.i16(50)? != 0;
+    let glyphs = Glyphs {
+        loca: Reader {
+            data: tables.get(&b"loca"[..])?,
+        },
+        glyf: tables.get(&b"glyf"[..])?,
+        long_offsets,
+    };
+    let scale = 1.0 / units_per_em;
+    let mut outline = Vec::new();
+    let mut budget = MAX_OUTLINE_WORK;
+    let m = [scale, 0.0, 0.0, scale, 0.0, 0.0];
+    glyphs.append(gid, &m, &mut outline, 0, &mut budget)?;
+    Some(outline)
+}
+
+struct Glyphs<'a> {
+    loca: Reader<'a>,
+    glyf: &'a [u8],
+    long_offsets: bool,
+}
+
+impl Glyphs<'_> {
+    // Appends the contours of `gid`, transformed by `m`, taking the glyph
+    // and its segments out of `budget`.
+    fn append(
+        &self,
+        gid: u16,
+        m: &[f64; 6],
+        outline: &mut Vec<PathSegment>,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Option<()> {
+        *budget = budget.checked_sub(1)?;
+        let index = usize::from(gid);
+        let (start, end) = if self.long_offsets {
+            (
+                self.loca.u32(index * 4)? as usize,
+                self.loca.u32(index * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                usize::from(self.loca.u16(index * 2)?) * 2,
+                usize::from(self.loca.u16(index * 2 + 2)?) * 2,
+            )
+        };
+        if end <= start {
+            return Some(());
+        }
+        let glyph = Reader {
+            data: self.glyf.get(start..end)?,
+        };
+        let contours = glyph.i16(0)?;
+        if contours >= 0 {
+            let before = outline.len();
+            append_simple(&glyph, contours as usize, m, outline)?;
+            *budget = budget.checked_sub(outline.len() - before)?;
+            Some(())
+        } else if depth < MAX_COMPOSITE_DEPTH {
+            self.append_composite(&glyph, m, outline, depth, budget)
+        } else {
+            None
+        }
+    }
+
+    fn append_composite(
+        &self,
+        glyph: &Reader,
+        m: &[f64; 6],
+        outline: &mut Vec<PathSegment>,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Option<()> {
+        const WORDS: u16 = 0x0001;
+        const XY_VALUES: u16 = 0x0002;
+        const SCALE: u16 = 0x0008;
+        const MORE: u16 = 0x0020;
+        const XY_SCALE: u16 = 0x0040;
+        const TWO_BY_TWO: u16 = 0x0080;
+        let f2dot14 = |offset: usize| glyph.i16(offset).map(|v| f64::from(v) / 16384.0);
+        let mut at = 10;
+        loop {
+            let flags = glyph.u16(at)?;
+            let component = glyph.u16(at + 2)?;
+            at += 4;
+            let (dx, dy) = if flags & WORDS != 0 {
+                at += 4;
+                (f64::from(glyph.i16(at - 4)?), f64::from(glyph.i16(at - 2)?))
+            } else {
+                at += 2;
+                let bytes = glyph.slice(at - 2, 2)?;
+                (f64::from(bytes[0] as i8), f64::from(bytes[1] as i8))
+            };
+            // Components placed by matching points are left unmoved.
+            let (dx, dy) = if flags & XY_VALUES != 0 {
+                (dx, dy)
+            } else {
+                (0.0, 0.0)
+            };
+            let [a, b, c, d] = if flags & SCALE != 0 {
+                at += 2;
+                let s = f2dot14(at - 2)?;
+                [s, 0.0, 0.0, s]
+            } else if flags & XY_SCALE != 0 {
+                at += 4;
+                [f2dot14(at - 4)?, 0.0, 0.0, f2dot14(at - 2)?]
+            } else if flags & TWO_BY_TWO != 0 {
+                at += 8;
+                [
+                    f2dot14(at - 8)?,
+                    f2dot14(at - 6)?,
+                    f2dot14(at - 4)?,
+                    f2dot14(at - 2)?,
+                ]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            let placed = multiply(&[a, b, c, d, dx, dy], m);
+            self.append(component, &placed, outline, depth + 1, budget)?;
+            if flags & MORE == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+// Appends a simple glyph's contours, whose points are on the curve or are
+// quadratic control points with on-curve points implied halfway between two
+// of them.
+fn append_simple(
+    glyph: &Reader,
+    contours: usize,
+    m: &[f64; 6],
+    outline: &mut Vec<PathSegment>,
+) -> Option<()> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME: u8 = 0x10;
+    const Y_SAME: u8 = 0x20;
+    let ends: Vec<usize> = (0..contours)
+        .map(|i| glyph.u16(10 + i * 2).map(usize::from))
+        .collect::<Option<_>>()?;
+    let count = ends.last().map_or(0, |&e| e + 1);
+    let instructions = usize::from(glyph.u16(10 + contours * 2)?);
+    let mut at = 12 + contours * 2 + instructions;
+    let mut flags = Vec::with_capacity(count);
+    while flags.len() < count {
+        let flag = *glyph.data.get(at)?;
+        at += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let repeats = *glyph.data.get(at)?;
+            at += 1;
+            flags.extend(std::iter::repeat_n(flag, usize::from(repeats)));
+        }
+    }
+    flags.truncate(count);
+    let mut coordinates = |short: u8, same: u8| -> Option<Vec<f64>> {
+        let mut value = 0i32;
+        let mut values = Vec::with_capacity(count);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = i32::from(*glyph.data.get(at)?);
+                at += 1;
+                value += if flag & same != 0 { delta } else { -delta };
+            } else if flag & same == 0 {
+                value += i32::from(glyph.i16(at)?);
+                at += 2;
+            }
+            values.push(f64::from(value));
+        }
+        Some(values)
+    };
+    let xs = coordinates(X_SHORT, X_SAME)?;
+    let ys = coordinates(Y_SHORT, Y_SAME)?;
+
+    let mut first = 0;
+    for &end in &ends {
+        let points: Vec<((f64, f64), bool)> = (first..=end.min(count.saturating_sub(1)))
+            .map(|i| (transform(m, (xs[i], ys[i])), flags[i] & ON_CURVE != 0))
+            .collect();
+        first = end + 1;
+        if points.is_empty() {
+            continue;
+        }
+        let n = points.len();
+        let middle = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        // Start on a point on the curve, or halfway between the last and first
+        // control points when there is none.
+        let (start, skip) = match points.iter().position(|p| p.1) {
+            Some(i) => (points[i].0, i + 1),
+            None => (middle(points[n - 1].0, points[0].0), 0),
+        };
+        outline.push(PathSegment::MoveTo(start));
+        let mut control: Option<(f64, f64)> = None;
+        for k in 0..n {
+            let (point, on_curve) = points[(skip + k) % n];
+            match (on_curve, control) {
+                (true, Some(c)) => {
+                    outline.push(PathSegment::QuadTo(c, point));
+                    control = None;
+                }
+                (true, None) => outline.push(PathSegment::LineTo(point)),
+                (false, Some(c)) => {
+                    outline.push(PathSegment::QuadTo(c, middle(c, point)));
+                    control = Some(point);
+                }
+                (false, None) => control = Some(point),
+            }
+        }
+        if let Some(c) = control {
+            outline.push(PathSegment::QuadTo(c, start));
+        }
+        outline.push(PathSegment::Close);
+    }
+    Some(())
+}
+
+// Radicals and compatibility ideographs, which CJK fonts draw with the same
+// glyph as a unified ideograph.
+fn is_variant_form(c: char) -> bool {
+    matches!(c as u32, 0x2E80..=0x2FDF | 0xF900..=0xFAFF | 0x2F800..=0x2FA1F)
+}
+
+fn parse_cmap_table(table: &[u8]) -> HashMap<(u16, u16), HashMap<u32, u16>> {
+    let reader = Reader { data: table };
+    let mut subtables = HashMap::new();
+    let count = reader.u16(2).unwrap_or(0);
+    for i in 0..count as usize {
+        let record = 4 + i * 8;
+        let (Some(platform), Some(encoding), Some(offset)) = (
+            reader.u16(record),
+            reader.u16(record + 2),
+            reader.u32(record + 4),
+        ) else {
+            break;
+        };
+        if let Some(map) = parse_cmap_subtable(&reader, offset as usize) {
+            subtables.entry((platform, encoding)).or_insert(map);
+        }
+    }
+    subtables
+}
+
+fn parse_cmap_subtable(reader: &Reader, offset: usize) -> Option<HashMap<u32, u16>> {
+    let mut map = HashMap::new();
+    let mut budget = MAX_CMAP_CODES;
+    match reader.u16(offset)? {
+        0 => {
+            let glyphs = reader.slice(offset + 6, 256)?;
+            for (code, &gid) in glyphs.iter().enumerate() {
+                if gid != 0 {
+                    map.insert(code as u32, gid as u16);
+                }
+            }
+        }
+        4 => {
+            let seg_count = reader.u16(offset + 6)? as usize / 2;
+            let ends = offset + 14;
+            let starts = ends + seg_count * 2 + 2;
+            let deltas = starts + seg_count * 2;
+            let range_offsets = deltas + seg_count * 2;
+            for seg in 0..seg_count {
+                let end = reader.u16(ends + seg * 2)?;
+                let start = reader.u16(starts + seg * 2)?;
+                let delta = reader.i16(deltas + seg * 2)?;
+                let range_offset_pos = range_offsets + seg * 2;
+                let range_offset = reader.u16(range_offset_pos)?;
+                if start > end {
+                    continue;
+                }
+                for code in start..=end {
+                    if code == 0xFFFF {
+                        break;
+                    }
+                    if budget == 0 {
+                        return Some(map);
+                    }
+                    budget -= 1;
+                    let gid = if range_offset == 0 {
+                        code.wrapping_add(delta as u16)
+                    } else {
+                        let addr =
+                            range_offset_pos + range_offset as usize + (code - start) as usize * 2;
+                        match reader.u16(addr) {
+                            Some(0) | None => 0,
+                            Some(g) => g.wrapping_add(delta as u16),
+                        }
+                    };
+                    if gid != 0 {
+                        map.insert(code as u32, gid);
+                    }
+                }
+            }
+        }
+        6 => {
+            let first = reader.u16(offset + 6)? as u32;
+            let count = reader.u16(offset + 8)? as usize;
+            for i in 0..count {
+                let gid = reader.u16(offset + 10 + i * 2)?;
+                if gid != 0 {
+                    map.insert(first + i as u32, gid);
+                }
+            }
+        }
+        12 => {
+            let groups = reader.u32(offset + 12)? as usize;
+            for i in 0..groups {
+                let group = offset + 16 + i * 12;
+                let start = reader.u32(group)?;
+                let end = reader.u32(group + 4)?;
+                let start_gid = reader.u32(group + 8)?;
+                if start > end || end - start >= MAX_GROUP_LEN {
+                    continue;
+                }
+                for code in start..=end {
+                    if budget == 0 {
+                        return Some(map);
+                    }
+                    budget -= 1;
+                    let Some(gid) = start_gid.checked_add(code - start) else {
+                        break;
+                    };
+                    if gid != 0 && gid <= u16::MAX as u32 {
+                        map.insert(code, gid as u16);
+                    }
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(map)
+}
+
+fn parse_post_table(table: &[u8], num_glyphs: u16) -> Vec<Option<String>> {
+    let reader = Reader { data: table };
+    match reader.u32(0) {
+        Some(0x0001_0000) => MAC_GLYPH_NAMES
+            .iter()
+            .take(num_glyphs.max(1) as usize)
+            .map(|n| Some(n.to_string()))
+            .collect(),
+        Some(0x0002_0000) => {
+            let count = reader.u16(32).unwrap_or(0) as usize;
+            let mut custom = Vec::new();
+            let mut pos = 34 + count * 2;
+            while let Some(&len) = table.get(pos) {
+                let Some(bytes) = reader.slice(pos + 1, len as usize) else {
+                    break;
+                };
+                custom.push(String::from_utf8_lossy(bytes).into_owned());
+                pos += 1 + len as usize;
+            }
+            (0..count)
+                .map(|gid| {
+                    let index = reader.u16(34 + gid * 2)? as usize;
+                    if index < MAC_GLYPH_NAMES.len() {
+                        Some(MAC_GLYPH_NAMES[index].to_string())
+                    } else {
+                        custom.get(index - MAC_GLYPH_NAMES.len()).cloned()
+                    }
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// The 258 standard Macintosh glyph names used by `post` table formats 1 and 2.
+const MAC_GLYPH_NAMES: [&str; 258] = [
+    ".notdef",
+    ".null",
+    "nonmarkingreturn",
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quotesingle",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "Adieresis",
+    "Aring",
+    "Ccedilla",
+    "Eacute",
+    "Ntilde",
+    "Odieresis",
+    "Udieresis",
+    "aacute",
+    "agrave",
+    "acircumflex",
+    "adieresis",
+    "atilde",
+    "aring",
+    "ccedilla",
+    "eacute",
+    "egrave",
+    "ecircumflex",
+    "edieresis",
+    "iacute",
+    "igrave",
+    "icircumflex",
+    "idieresis",
+    "ntilde",
+    "oacute",
+    "ograve",
+    "ocircumflex",
+    "odieresis",
+    "otilde",
+    "uacute",
+    "ugrave",
+    "ucircumflex",
+    "udieresis",
+    "dagger",
+    "degree",
+    "cent",
+    "sterling",
+    "section",
+    "bullet",
+    "paragraph",
+    "germandbls",
+    "registered",
+    "copyright",
+    "trademark",
+    "acute",
+    "dieresis",
+    "notequal",
+    "AE",
+    "Oslash",
+    "infinity",
+    "plusminus",
+    "lessequal",
+    "greaterequal",
+    "yen",
+    "mu",
+    "partialdiff",
+    "summation",
+    "product",
+    "pi",
+    "integral",
+    "ordfeminine",
+    "ordmasculine",
+    "Omega",
+    "ae",
+    "oslash",
+    "questiondown",
+    "exclamdown",
+    "logicalnot",
+    "radical",
+    "florin",
+    "approxequal",
+    "Delta",
+    "guillemotleft",
+    "guillemotright",
+    "ellipsis",
+    "nonbreakingspace",
+    "Agrave",
+    "Atilde",
+    "Otilde",
+    "OE",
+    "oe",
+    "endash",
+    "emdash",
+    "quotedblleft",
+    "quotedblright",
+    "quoteleft",
+    "quoteright",
+    "divide",
+    "lozenge",
+    "ydieresis",
+    "Ydieresis",
+    "fraction",
+    "currency",
+    "guilsinglleft",
+    "guilsinglright",
+    "fi",
+    "fl",
+    "daggerdbl",
+    "periodcentered",
+    "quotesinglbase",
+    "quotedblbase",
+    "perthousand",
+    "Acircumflex",
+    "Ecircumflex",
+    "Aacute",
+    "Edieresis",
+    "Egrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Igrave",
+    "Oacute",
+    "Ocircumflex",
+    "apple",
+    "Ograve",
+    "Uacute",
+    "Ucircumflex",
+    "Ugrave",
+    "dotlessi",
+    "circumflex",
+    "tilde",
+    "macron",
+    "breve",
+    "dotaccent",
+    "ring",
+    "cedilla",
+    "hungarumlaut",
+    "ogonek",
+    "caron",
+    "Lslash",
+    "lslash",
+    "Scaron",
+    "scaron",
+    "Zcaron",
+    "zcaron",
+    "brokenbar",
+    "Eth",
+    "eth",
+    "Yacute",
+    "yacute",
+    "Thorn",
+    "thorn",
+    "minus",
+    "multiply",
+    "onesuperior",
+    "twosuperior",
+    "threesuperior",
+    "onehalf",
+    "onequarter",
+    "threequarters",
+    "franc",
+    "Gbreve",
+    "gbreve",
+    "Idotaccent",
+    "Scedilla",
+    "scedilla",
+    "Cacute",
+    "cacute",
+    "Ccaron",
+    "ccaron",
+    "dcroat",
+];
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{glyph_outline, parse_cmap_subtable, parse_truetype, Reader};
+    use crate::graphics::PathSegment;
+    use std::collections::HashMap;
+
+    fn sfnt(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![0, 1, 0, 0];
+        out.extend((tables.len() as u16).to_be_bytes());
+        out.extend([0u8; 6]);
+        let mut offset = 12 + tables.len() * 16;
+        for (tag, body) in tables {
+            out.extend(*tag);
+            out.extend([0u8; 4]);
+            out.extend((offset as u32).to_be_bytes());
+            out.extend((body.len() as u32).to_be_bytes());
+            offset += body.len();
+        }
+        for (_, body) in tables {
+            out.extend(body);
+        }
+        out
+    }
+
+    fn words(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// A glyph of one contour through `(x, y, on_curve)` points, with every
+    /// coordinate written as a full word.
+    pub(crate) fn simple_glyph(points: &[(i16, i16, bool)]) -> Vec<u8> {
+        let mut glyph = words(&[1, 0, 0, 0, 0, points.len() as u16 - 1, 0]);
+        glyph.extend(points.iter().map(|p| u8::from(p.2)));
+        let mut last = (0, 0);
+        let mut ys = Vec::new();
+        for &(x, y, _) in points {
+            glyph.extend((x - last.0).to_be_bytes());
+            ys.extend((y - last.1).to_be_bytes());
+            last = (x, y);
+        }
+        glyph.extend(ys);
+        glyph
+    }
+
+    /// An sfnt holding just the `head`, `maxp`, `loca` and `glyf` tables for
+    /// `glyphs`.
+    pub(crate) fn glyph_font(units_per_em: u16, glyphs: &[Vec<u8>]) -> Vec<u8> {
+        sfnt(&glyph_tables(units_per_em, glyphs))
+    }
+
+    /// A font of empty glyphs whose format 4 `cmap` maps `chars[i]` to glyph
+    /// `i + 1`.
+    pub(crate) fn cmap_font(chars: &[char]) -> Vec<u8> {
+        let mut segments: Vec<(u16, u16)> = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (c as u16, i as u16 + 1))
+            .collect();
+        segments.sort();
+        segments.push((0xFFFF, 1));
+        let count = segments.len() as u16;
+        let mut cmap = words(&[0, 1, 3, 1]);
+        cmap.extend(12u32.to_be_bytes());
+        cmap.extend(words(&[4, 16 + 8 * count, 0, 2 * count, 0, 0, 0]));
+        cmap.extend(words(&segments.iter().map(|s| s.0).collect::<Vec<_>>()));
+        cmap.extend(words(&[0]));
+        cmap.extend(words(&segments.iter().map(|s| s.0).collect::<Vec<_>>()));
+        let deltas: Vec<u16> = segments.iter().map(|s| s.1.wrapping_sub(s.0)).collect();
+        cmap.extend(words(&deltas));
+        cmap.extend(words(&vec![0; segments.len()]));
+
+        let mut tables = vec![(b"cmap", cmap)];
+        tables.extend(glyph_tables(1000, &vec![Vec::new(); chars.len() + 1]));
+        sfnt(&tables)
+    }
+
+    fn glyph_tables(units_per_em: u16, glyphs: &[Vec<u8>]) -> Vec<(&'static [u8; 4], Vec<u8>)> {
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&units_per_em.to_be_bytes());
+        let maxp = words(&[0, 0x5000, glyphs.len() as u16]);
+        let mut glyf = Vec::new();
+        let mut loca = words(&[0]);
+        for glyph in glyphs {
+            glyf.extend(glyph);
+            glyf.resize(glyf.len().next_multiple_of(2), 0);
+            loca.extend(words(&[(glyf.len() / 2) as u16]));
+        }
+        vec![
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ]
+    }
+
+    #[test]
+    fn reverse_map_combines_cmap_and_post_names() {
+        // Format 4 subtable mapping the Kangxi radical U+2F08 and U+4EBA both
+        // to glyph 3, plus the final 0xFFFF segment.
+        let mut cmap = words(&[0, 1, 3, 1]);
+        cmap.extend(12u32.to_be_bytes());
+        cmap.extend(words(&[4, 40, 0, 6, 4, 1, 2]));
+        cmap.extend(words(&[0x2F08, 0x4EBA, 0xFFFF, 0, 0x2F08, 0x4EBA, 0xFFFF]));
+        cmap.extend(words(&[
+            (3u16).wrapping_sub(0x2F08),
+            (3u16).wrapping_sub(0x4EBA),
+            1,
+            0,
+            0,
+            0,
+        ]));
+
+        let maxp = [0, 0, 0x50, 0, 0, 4].to_vec();
+
+        let mut post = vec![0, 2, 0, 0];
+        post.extend([0u8; 28]);
+        post.extend(words(&[4, 0, 258, 3, 259]));
+        post.extend(b"\x07uni6C11\x02g3");
+
+        let data = sfnt(&[(b"cmap", cmap), (b"maxp", maxp), (b"post", post)]);
+        let font = parse_truetype(&data).expect("valid sfnt");
+        let reverse = font.gid_to_unicode();
+
+        assert_eq!(font.num_glyphs, 4);
+        assert_eq!(reverse.get(&1).map(String::as_str), Some("民"));
+        assert_eq!(reverse.get(&2).map(String::as_str), Some(" "));
+        assert_eq!(reverse.get(&3).map(String::as_str), Some("人"));
+
+        // A format 12 group whose glyph IDs run past u32::MAX.
+        let mut group = words(&[12, 0]);
+        group.extend(
+            [28u32, 0, 1, 0x41, 0x42, u32::MAX]
+                .iter()
+                .flat_map(|n| n.to_be_bytes()),
+        );
+        let reader = Reader { data: &group };
+        assert_eq!(parse_cmap_subtable(&reader, 0), Some(HashMap::new()));
+    }
+
+    #[test]
+    fn wide_cmap_subtables_stop_at_the_code_budget() {
+        // 20,000 format 12 groups, each spanning the largest allowed range.
+        let count = 20_000u32;
+        let mut table = words(&[12, 0]);
+        table.extend(
+            [16 + count * 12, 0, count]
+                .iter()
+                .flat_map(|n| n.to_be_bytes()),
+        );
+        for _ in 0..count {
+            table.extend([0u32, 0xFFFF, 1].iter().flat_map(|n| n.to_be_bytes()));
+        }
+        let reader = Reader { data: &table };
+        assert_eq!(
+            parse_cmap_subtable(&reader, 0).map(|m| m.len()),
+            Some(0xFFFF)
+        );
+
+        // Format 4 segments that all repeat the full code range.
+        let seg_count = 0x7FFF;
+        let mut table = words(&[4, 0, 0, seg_count * 2, 0, 0, 0]);
+        table.extend(words(&vec![0xFFFE; seg_count as usize]));
+        table.extend(words(&[0]));
+        table.extend(words(&vec![0; seg_count as usize]));
+        table.extend(words(&vec![1; seg_count as usize]));
+        table.extend(words(&vec![0; seg_count as usize]));
+        let reader = Reader { data: &table };
+        assert_eq!(
+            parse_cmap_subtable(&reader, 0).map(|m| m.len()),
+            Some(0xFFFF)
+        );
+    }
+
+    #[test]
+    fn outlines_follow_quadratic_contours_and_components() {
+        // On, off, off, on: the two control points imply an on-curve point
+        // between them.
+        let curve = simple_glyph(&[
+            (0, 0, true),
+            (0, 500, false),
+            (500, 500, false),
+            (500, 0, true),
+        ]);
+        // Glyph 1 moved by (100, 50) at half size.
+        let mut composite = words(&[0xFFFF, 0, 0, 0, 0]);
+        composite.extend(words(&[0x0001 | 0x0002 | 0x0008, 1, 100, 50, 0x2000]));
+        let data = glyph_font(1000, &[Vec::new(), curve, composite]);
+
+        assert_eq!(glyph_outline(&data, 0), Some(Vec::new()));
+        assert_eq!(
+            glyph_outline(&data, 1).unwrap(),
+            [
+                PathSegment::MoveTo((0.0, 0.0)),
+                PathSegment::QuadTo((0.0, 0.5), (0.25, 0.5)),
+                PathSegment::QuadTo((0.5, 0.5), (0.5, 0.0)),
+                PathSegment::LineTo((0.0, 0.0)),
+                PathSegment::Close,
+            ]
+        );
+        let placed = glyph_outline(&data, 2).unwrap();
+        assert_eq!(placed.len(), 5);
+        assert_eq!(placed[0], PathSegment::MoveTo((0.1, 0.05)));
+        let PathSegment::QuadTo(control, end) = placed[1] else {
+            panic!("{:?}", placed[1]);
+        };
+        assert_eq!(control, (0.1, 0.3));
+        assert!((end.0 - 0.225).abs() < 1e-9 && end.1 == 0.3);
+    }
+
+    #[test]
+    fn composites_fanning_out_stop_at_the_outline_budget() {
+        // Glyphs 1 to 7 each place the next glyph 100 times; glyph 8 is a
+        // triangle, so glyph 1 would draw 100^7 of them.
+        let fan = |child: u16| {
+            let mut glyph = words(&[0xFFFF, 0, 0, 0, 0]);
+            for i in 0..100 {
+                let more = if i < 99 { 0x0020 } else { 0 };
+                glyph.extend(words(&[more, child, 0]));
+            }
+            glyph
+        };
+        let mut glyphs = vec![Vec::new()];
+        glyphs.extend((2..=8).map(fan));
+        glyphs.push(simple_glyph(&[
+            (0, 0, true),
+            (500, 0, true),
+            (0, 500, true),
+        ]));
+        let data = glyph_font(1000, &glyphs);
+
+        assert_eq!(glyph_outline(&data, 7).map(|o| o.len()), Some(500));
+        assert_eq!(glyph_outline(&data, 1), None);
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::encoding::standard_encoding_glyph;
+use crate::parser_utils::hex_value;
+use crate::postscript::{PsToken, PsTokenizer};
+
+const EEXEC_KEY: u16 = 55665;
+
+/// Reads the built-in `/Encoding` of a Type1 font program (`FontFile`), as a
+/// map from character code to glyph name. `length1` is the stream's
+/// `Length1`, the size of the cleartext portion.
+pub fn parse_type1_encoding(
+    data: &[u8],
+    length1: Option<usize>,
+) -> Result<HashMap<u8, String>, &'static str> {
+    let (clear, encrypted) = split_program(data, length1)?;
+    if let Some(encoding) = read_encoding(clear) {
+        return Ok(encoding);
+    }
+    // A few fonts define the encoding inside the encrypted portion. Only the
+    // part before the binary subroutines and charstrings is tokenized.
+    let private = eexec_decrypt(encrypted);
+    let end = ["/Subrs", "/CharStrings"]
+        .iter()
+        .filter_map(|key| find(&private, key.as_bytes()))
+        .min()
+        .unwrap_or(private.len());
+    read_encoding(&private[..end]).ok_or("no /Encoding in font program")
+}
+
+// Splits the program into its cleartext and eexec-encrypted portions. Both
+// the PFB segment format and the plain PFA layout are accepted.
+fn split_program(data: &[u8], length1: Option<usize>) -> Result<(&[u8], &[u8]), &'static str> {
+    if data.first() == Some(&0x80) {
+        let mut segments = Vec::new();
+        let mut pos = 0;
+        while pos + 6 <= data.len() && data[pos] == 0x80 && data[pos + 1] != 3 {
+            let len =
+                u32::from_le_bytes([data[pos + 2], data[pos + 3], data[pos + 4], data[pos + 5]])
+                    as usize;
+            let body = data
+                .get(pos + 6..pos + 6 + len)
+                .ok_or("truncated PFB segment")?;
+            segments.push(body);
+            pos += 6 + len;
+        }
+        return match segments.as_slice() {
+            [clear, encrypted, ..] => Ok((clear, encrypted)),
+            [clear] => Ok((clear, &[])),
+            [] => Err("empty PFB program"),
+        };
+    }
+
+    let split = match length1 {
+        Some(len) if len > 0 && len <= data.len() => len,
+        _ => find(data, b"eexec")
+            .map(|at| at + 5)
+            .ok_or("missing eexec section")?,
+    };
+    let mut start = split;
+    while start < data.len() && data[start].is_ascii_whitespace() {
+        start += 1;
+    }
+    Ok((&data[..split], &data[start..]))
+}
+
+/// Decrypts an eexec-encrypted section, in binary or hexadecimal form, and
+/// drops the four random lead bytes.
+fn eexec_decrypt(data: &[u8]) -> Vec<u8> {
+    let is_hex = data.len() >= 4 && data[..4].iter().all(|&b| hex_value(b).is_some());
+    let cipher: Vec<u8> = if is_hex {
+        let digits: Vec<u8> = data.iter().filter_map(|&b| hex_value(b)).collect();
+        digits.chunks_exact(2).map(|p| (p[0] << 4) | p[1]).collect()
+    } else {
+        data.to_vec()
+    };
+
+    let mut r = EEXEC_KEY;
+    let plain: Vec<u8> = cipher
+        .iter()
+        .map(|&c| {
+            let p = c ^ (r >> 8) as u8;
+            r = (c as u16)
+                .wrapping_add(r)
+                .wrapping_mul(52845)
+                .wrapping_add(22719);
+            p
+        })
+        .collect();
+    plain.get(4..).unwrap_or(&[]).to_vec()
+}
+
+// Handles `/Encoding StandardEncoding def` and the array form built with
+// `dup <code> /<glyph> put` entries.
+fn read_encoding(program: &[u8]) -> Option<HashMap<u8, String>> {
+    let mut tokens =
+        PsTokenizer::new(program).skip_while(|t| *t != PsToken::Name("Encoding".into()));
+    tokens.next()?;
+    match tokens.next()? {
+        PsToken::Operator(name) if name == "StandardEncoding" => Some(
+            (0..=255u8)
+                .filter_map(|code| standard_encoding_glyph(code).map(|g| (code, g.to_string())))
+                .collect(),
+        ),
+        PsToken::Integer(_) => {
+            let mut encoding = HashMap::new();
+            let mut window: Vec<PsToken> = Vec::with_capacity(4);
+            for token in tokens {
+                if matches!(&token, PsToken::Operator(op) if op == "def" || op == "readonly") {
+                    break;
+                }
+                window.push(token);
+                if window.len() > 4 {
+                    window.remove(0);
+                }
+                if let [PsToken::Operator(dup), PsToken::Integer(code), PsToken::Name(glyph), PsToken::Operator(put)] =
+                    window.as_slice()
+                {
+                    if dup == "dup"
+                        && put == "put"
+                        && (0..=255).contains(code)
+                        && glyph != ".notdef"
+                    {
+                        encoding.insert(*code as u8, glyph.clone());
+                    }
+                }
+            }
+            Some(encoding)
+        }
+        _ => None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eexec_encrypt(plain: &[u8]) -> Vec<u8> {
+        let mut r = EEXEC_KEY;
+        [0u8; 4]
+            .iter()
+            .chain(plain)
+            .map(|&p| {
+                let c = p ^ (r >> 8) as u8;
+                r = (c as u16)
+                    .wrapping_add(r)
+                    .wrapping_mul(52845)
+                    .wrapping_add(22719);
+                c
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_custom_encoding_from_cleartext_and_encrypted_parts() {
+        let clear = b"%!FontType1-1.0: Symbolic\n/FontName /Symbolic def\n\
+            /Encoding 256 array 0 1 255 {1 index exch /.notdef put} for\n\
+            dup 65 /Euro put dup 66 /summation put readonly def\ncurrentfile eexec\n";
+        let mut program = clear.to_vec();
+        program.extend(eexec_encrypt(
+            b"dup /Private 8 dict dup begin /CharStrings 2",
+        ));
+        let encoding = parse_type1_encoding(&program, Some(clear.len())).unwrap();
+        assert_eq!(encoding.len(), 2);
+        assert_eq!(encoding[&65], "Euro");
+        assert_eq!(encoding[&66], "summation");
+
+        let mut hidden = b"/FontName /Hidden def currentfile eexec\n".to_vec();
+        hidden.extend(eexec_encrypt(
+            b"/Encoding StandardEncoding def /Subrs 0 array",
+        ));
+        let encoding = parse_type1_encoding(&hidden, None).unwrap();
+        assert_eq!(encoding[&0x27], "quoteright");
+    }
+}
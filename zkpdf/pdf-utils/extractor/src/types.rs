@@ -1,5 +1,5 @@
 use core::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub enum PdfError {
@@ -16,20 +16,640 @@ impl fmt::Display for PdfError {
     }
 }
 
+/// Options for [`crate::extract_text_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Apply [`crate::normalize_text`] to each page: CJK radicals and
+    /// compatibility ideographs to unified ideographs, full-width and
+    /// half-width forms to their ordinary forms, then NFC.
+    pub normalize_unicode: bool,
+    /// Leave out glyphs that would not be seen on the rendered page; see
+    /// [`HiddenReason`].
+    pub visible_only: bool,
+    /// Keep text in optional content (layers) that is off in the document's
+    /// default configuration. It is left out otherwise, and always marked
+    /// with [`HiddenReason::OptionalContentOff`].
+    pub include_hidden_layers: bool,
+    /// Leave out text outside the page's crop box, even when `visible_only`
+    /// is off.
+    pub crop_to_page: bool,
+    /// Outlines of known glyphs, used to read codes of embedded fonts that
+    /// map to no Unicode text; see [`crate::learn_glyph_fingerprints`].
+    pub glyph_fingerprints: Option<FingerprintDatabase>,
+}
+
+/// Why a shown glyph would not be seen on the rendered page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HiddenReason {
+    /// Text rendering mode 3 or 7, which neither fills nor strokes.
+    InvisibleRenderMode,
+    /// Painted in the colour of what lies beneath it: the last fill covering
+    /// the glyph, or white paper.
+    SameColorAsBackground,
+    /// Outside the page's crop box.
+    OutsideCropBox,
+    /// Outside the current clipping path.
+    Clipped,
+    /// Rendered too small to read, usually by a zero font size or matrix.
+    ZeroScale,
+    /// In optional content that is off in the default configuration.
+    OptionalContentOff,
+    /// Painted in a colour that can't be compared with what lies beneath it:
+    /// a pattern, or a separation, DeviceN or indexed colour.
+    UnknownPaint,
+    /// Painted with a constant alpha (`/ca`, `/CA`) of zero or nearly zero.
+    Transparent,
+    /// Painted through a soft mask (`/SMask`), which may hide it.
+    SoftMasked,
+    /// Over paint whose colour isn't known, such as an image or a shading,
+    /// so it may match what lies beneath it.
+    UnknownBackground,
+}
+
+/// A glyph shown by a page's content, as returned by
+/// [`crate::extract_glyphs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextGlyph {
+    pub text: String,
+    /// Glyph origin on the displayed page: the crop box turned by `/Rotate`,
+    /// with its lower-left corner at (0, 0).
+    pub x: f64,
+    pub y: f64,
+    /// Rendered font size in default user space units.
+    pub size: f64,
+    /// Empty when the glyph is visible.
+    pub hidden: Vec<HiddenReason>,
+    /// How `text` was obtained from the glyph's character code.
+    pub source: CharSource,
+}
+
+impl TextGlyph {
+    pub fn is_visible(&self) -> bool {
+        self.hidden.is_empty()
+    }
+}
+
+/// How a character of extracted text was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharSource {
+    /// A `bfchar` entry of the font's ToUnicode map.
+    ToUnicodeChar,
+    /// A `bfrange` entry of the font's ToUnicode map.
+    ToUnicodeRange,
+    /// The Adobe character collection named by a CID-keyed font's
+    /// Registry-Ordering.
+    PredefinedCMap,
+    /// A glyph name: from a simple font's `Differences`, its named base
+    /// encoding or the encoding built into its program, or from the charset
+    /// of an embedded CFF program.
+    GlyphName,
+    /// The `cmap` (or `post` glyph names) of an embedded TrueType program.
+    EmbeddedCmap,
+    /// A glyph outline found in [`ExtractOptions::glyph_fingerprints`].
+    GlyphFingerprint,
+    /// Nothing maps the code, so a code below 128 is read as ASCII.
+    AsciiFallback,
+    /// Nothing maps the code: U+FFFD.
+    Replacement,
+    /// A text string rather than glyphs: an annotation's `/Contents` or an
+    /// XFA form value.
+    TextString,
+    /// A space or line break placed by the extractor's layout.
+    Layout,
+}
+
+impl CharSource {
+    /// Whether the character is a guess that no mapping in the file backs:
+    /// an outline matched against a fingerprint database, or a fallback.
+    pub fn is_guessed(self) -> bool {
+        matches!(
+            self,
+            CharSource::GlyphFingerprint | CharSource::AsciiFallback | CharSource::Replacement
+        )
+    }
+}
+
+/// Notes on how a page's text was assembled, as returned by
+/// [`crate::extract_text_with_diagnostics`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageDiagnostics {
+    /// Glyphs left out because the same glyph, in the same font, had already
+    /// been drawn almost on top of them: fake bold and shadow overprints.
+    pub collapsed_overprints: Vec<TextGlyph>,
+    /// How each character of the page's text was obtained, one entry per
+    /// `char`.
+    pub char_sources: Vec<CharSource>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PdfFont {
     pub base_name: Option<String>,
     pub subtype: Option<String>,
     pub encoding: Option<String>,
     pub to_unicode_map: Option<HashMap<u32, String>>,
+    /// Codes of `to_unicode_map` mapped by a `bfrange` rather than a
+    /// `bfchar` entry.
+    pub to_unicode_ranges: HashSet<u32>,
+    /// Codes whose `to_unicode_map` entry looked wrong and was replaced,
+    /// with where the replacement came from.
+    pub to_unicode_repairs: HashMap<u32, CharSource>,
     pub differences: Option<HashMap<u32, String>>,
+    /// Character code to Unicode recovered from the embedded font program.
+    pub embedded_unicode: Option<HashMap<u32, String>>,
+    /// Where `embedded_unicode` was read from: [`CharSource::EmbeddedCmap`],
+    /// [`CharSource::GlyphName`] or [`CharSource::PredefinedCMap`].
+    pub embedded_source: Option<CharSource>,
+    /// Character code to glyph name from the encoding built into an embedded
+    /// Type1 program; the base encoding when the font names none.
+    pub builtin_encoding: Option<HashMap<u32, String>>,
+    /// Advance widths in glyph space, keyed by character code or CID, from
+    /// `/Widths` or a descendant font's `/W`.
+    pub widths: HashMap<u32, f64>,
+    /// How a Type0 font's strings split into codes, from its embedded
+    /// encoding CMap, or from its ToUnicode map when the encoding is a
+    /// predefined CMap. Empty when codes are two bytes (`Identity-H`) or one
+    /// (simple fonts).
+    pub codespace_ranges: Vec<CodespaceRange>,
+    /// Width of codes missing from `widths` (`MissingWidth` or `DW`).
+    pub default_width: Option<f64>,
+    /// Maps glyph space to text space: `/FontMatrix` for Type3 fonts and a
+    /// 1/1000 scale for every other font type.
+    pub font_matrix: [f64; 6],
+    /// Glyph names that have a procedure in a Type3 font's `/CharProcs`.
+    pub char_procs: Option<HashSet<String>>,
+    /// The embedded TrueType or CFF program, for drawing glyph outlines.
+    pub program: Option<FontProgram>,
+    pub warnings: Vec<String>,
+}
+
+impl PdfFont {
+    /// Horizontal advance of a character code in text space, per unit of font
+    /// size, when the font declares a width for it.
+    pub fn advance(&self, code: u32) -> Option<f64> {
+        let width = self.widths.get(&code).copied().or(self.default_width)?;
+        Some(width * self.font_matrix[0])
+    }
+}
+
+/// A font program embedded through `FontFile2` or `FontFile3`.
+#[derive(Debug, Clone)]
+pub struct FontProgram {
+    /// The decoded program; for OpenType CFF fonts, just the `CFF ` table.
+    pub data: Vec<u8>,
+    /// Whether `data` is a bare CFF program rather than an sfnt with `glyf`.
+    pub cff: bool,
+    /// Glyph ID of each character code the font shows.
+    pub glyph_ids: HashMap<u32, u16>,
+}
+
+/// Unicode text of glyphs keyed by a fingerprint of their outline, built by
+/// [`crate::learn_glyph_fingerprints`] from fonts whose mapping is known.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FingerprintDatabase {
+    entries: HashMap<u64, String>,
+}
+
+impl FingerprintDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, fingerprint: u64, text: String) {
+        self.entries.insert(fingerprint, text);
+    }
+
+    pub fn lookup(&self, fingerprint: u64) -> Option<&str> {
+        self.entries.get(&fingerprint).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The database as text, one glyph per line: the fingerprint in hex
+    /// followed by the code points of its text in hex.
+    pub fn to_text(&self) -> String {
+        let mut entries: Vec<(&u64, &String)> = self.entries.iter().collect();
+        entries.sort();
+        let mut out = String::new();
+        for (fingerprint, text) in entries {
+            out.push_str(&format!("{:016x}", fingerprint));
+            for c in text.chars() {
+                out.push_str(&format!(" {:04X}", c as u32));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Reads a database written by [`FingerprintDatabase::to_text`],
+    /// skipping lines that don't parse.
+    pub fn from_text(text: &str) -> Self {
+        let mut database = Self::default();
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(fingerprint) = fields.next().and_then(|f| u64::from_str_radix(f, 16).ok())
+            else {
+                continue;
+            };
+            let chars: Option<String> = fields
+                .map(|f| u32::from_str_radix(f, 16).ok().and_then(char::from_u32))
+                .collect();
+            match chars {
+                Some(chars) if !chars.is_empty() => database.insert(fingerprint, chars),
+                _ => continue,
+            }
+        }
+        database
+    }
+}
+
+/// A parsed CMap program, either a ToUnicode map or a CID encoding.
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    pub name: Option<String>,
+    /// 0 for horizontal writing, 1 for vertical.
+    pub wmode: u8,
+    pub cid_system_info: Option<CidSystemInfo>,
+    /// Name of the parent CMap given to `usecmap`, which is not loaded.
+    pub use_cmap: Option<String>,
+    pub codespace_ranges: Vec<CodespaceRange>,
+    /// `bfchar`/`bfrange` mappings from character code to Unicode text.
+    pub to_unicode: HashMap<u32, String>,
+    /// Codes of `to_unicode` last mapped by a `bfrange` entry.
+    pub range_codes: HashSet<u32>,
+    /// `cidchar`/`cidrange` mappings from character code to CID.
+    pub cid_ranges: Vec<CidRange>,
+    pub notdef_ranges: Vec<CidRange>,
+    /// Malformed or suspicious entries that were skipped or repaired.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CidSystemInfo {
+    pub registry: String,
+    pub ordering: String,
+    pub supplement: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodespaceRange {
+    pub low: u32,
+    pub high: u32,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidRange {
+    pub low: u32,
+    pub high: u32,
+    pub cid: u32,
 }
 
 #[derive(Debug, Clone)]
 pub struct PageContent {
+    /// Object id of the page dictionary; `None` for a page written directly
+    /// in its parent's `/Kids`.
+    pub id: Option<(u32, u16)>,
     pub content_streams: Vec<Vec<u8>>,
     pub fonts: HashMap<String, PdfFont>,
     pub resources: HashMap<String, PdfObj>,
+    /// `/MediaBox`, inherited from the page tree when the page has none.
+    pub media_box: Option<[f64; 4]>,
+    /// `/CropBox` clipped to the media box, or the media box when there is
+    /// none; inherited like it.
+    pub crop_box: Option<[f64; 4]>,
+    /// Clockwise rotation of the displayed page: 0, 90, 180 or 270.
+    pub rotate: u16,
+    /// Optional content groups that are off in the document's default
+    /// configuration.
+    pub hidden_layers: HashSet<(u32, u16)>,
+    /// Entries of the page's `/Annots` array, usually references.
+    pub annotations: Vec<PdfObj>,
+}
+
+/// A parsed document: its pages, every object by id, and the trailer.
+#[derive(Debug, Clone)]
+pub struct PdfDocument {
+    pub pages: Vec<PageContent>,
+    pub objects: HashMap<(u32, u16), PdfObj>,
+    pub trailer: HashMap<String, PdfObj>,
+}
+
+impl PdfDocument {
+    /// The document catalog, `/Root` in the trailer.
+    pub fn catalog(&self) -> Option<&HashMap<String, PdfObj>> {
+        match crate::resolve(self.trailer.get("Root")?, &self.objects) {
+            PdfObj::Dictionary(catalog) => Some(catalog),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of an interactive form field, from its `/FT` and `/Ff` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Checkbox,
+    RadioButton,
+    PushButton,
+    Choice,
+    Signature,
+    /// A field without a recognised `/FT`.
+    Unknown,
+}
+
+/// Decoded `/V` of a form field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// The field has no value.
+    Empty,
+    Text(String),
+    /// Appearance state of a checkbox or radio button, such as `Yes` or `Off`.
+    State(String),
+    /// Selected options of a choice field.
+    Choices(Vec<String>),
+    /// A signed signature field, with the signer's `/Name` when given.
+    Signature {
+        signer: Option<String>,
+    },
+}
+
+/// A terminal field of a document's interactive form (`/AcroForm`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    /// Fully qualified name: the partial names (`/T`) of the field and its
+    /// ancestors joined with `.`.
+    pub name: String,
+    /// `/TU`, the name shown to users.
+    pub alternate_name: Option<String>,
+    pub kind: FieldKind,
+    pub value: FieldValue,
+}
+
+/// A date from the `/Info` dictionary (`D:YYYYMMDDHHmmSSOHH'mm'`) or from XMP
+/// (ISO 8601). Fields left out of the string take their earliest value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Offset from UTC in minutes, or `None` when the date gives no zone.
+    pub utc_offset_minutes: Option<i16>,
+}
+
+impl PdfDate {
+    /// Seconds since the Unix epoch, taking a date without a zone as UTC.
+    pub fn unix_timestamp(&self) -> i64 {
+        // Days from 1970-01-01 to the civil date (Howard Hinnant's algorithm).
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        let seconds =
+            i64::from(self.hour) * 3600 + i64::from(self.minute) * 60 + i64::from(self.second);
+        days * 86_400 + seconds - i64::from(self.utc_offset_minutes.unwrap_or(0)) * 60
+    }
+}
+
+/// The document information dictionary (`/Info` in the trailer).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<PdfDate>,
+    pub mod_date: Option<PdfDate>,
+    /// The date strings as written, kept when they do not parse.
+    pub raw_creation_date: Option<String>,
+    pub raw_mod_date: Option<String>,
+    /// Other text entries, sorted by key.
+    pub custom: Vec<(String, String)>,
+}
+
+/// A Dublin Core, `xmp` or `pdf` property of the catalog's XMP packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmpProperty {
+    /// The property with its namespace's usual prefix, such as `dc:title`
+    /// or `xmp:CreateDate`, whatever prefix the packet itself declares.
+    pub name: String,
+    /// The value, or the items of an `rdf:Alt`, `rdf:Seq` or `rdf:Bag`.
+    pub values: Vec<String>,
+}
+
+/// Document-level metadata from `/Info` and the catalog's `/Metadata` stream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentMetadata {
+    pub info: DocumentInfo,
+    pub xmp: Vec<XmpProperty>,
+}
+
+impl DocumentMetadata {
+    /// The first value of an XMP property, such as `xmp:CreatorTool`.
+    pub fn xmp_value(&self, name: &str) -> Option<&str> {
+        self.xmp
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| property.values.first())
+            .map(String::as_str)
+    }
+}
+
+/// An entry of the document outline (bookmarks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub title: String,
+    /// Index of the target page, as in `extract_text`, when the item's
+    /// destination or `GoTo` action points into this document.
+    pub page: Option<usize>,
+    pub children: Vec<OutlineItem>,
+}
+
+/// A destination from the catalog's `/Dests` or the `/Names /Dests` tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedDestination {
+    pub name: String,
+    pub page: Option<usize>,
+}
+
+/// How a document is navigated: its outline, named destinations and page
+/// labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentNavigation {
+    pub outline: Vec<OutlineItem>,
+    pub destinations: Vec<NamedDestination>,
+    /// The label of each page, by page index: from `/PageLabels`, or the
+    /// 1-based page number when there are none.
+    pub page_labels: Vec<String>,
+}
+
+impl DocumentNavigation {
+    /// Index of the first page with the given label, such as `iii`.
+    pub fn page_for_label(&self, label: &str) -> Option<usize> {
+        self.page_labels.iter().position(|l| l == label)
+    }
+}
+
+/// What activating a link does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkAction {
+    Uri(String),
+    /// A destination in this document, with the page index it resolves to.
+    GoTo {
+        page: Option<usize>,
+    },
+    /// A destination in another PDF file.
+    GoToRemote {
+        file: Option<String>,
+    },
+    /// Opens or runs a file.
+    Launch {
+        file: Option<String>,
+    },
+    JavaScript(String),
+    /// Any other action, by its `/S` type.
+    Other(String),
+}
+
+impl LinkAction {
+    /// Launch and JavaScript actions run code rather than navigate.
+    pub fn is_suspicious(&self) -> bool {
+        matches!(self, LinkAction::Launch { .. } | LinkAction::JavaScript(_))
+    }
+}
+
+/// A `/Link` annotation on a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// `/Rect` in default user space, normalised so that x0 <= x1, y0 <= y1.
+    pub rect: [f64; 4],
+    /// The link's action followed by those chained through `/Next`, or a
+    /// `GoTo` for a link with a `/Dest`.
+    pub actions: Vec<LinkAction>,
+    /// Page text laid out within the rectangle.
+    pub anchor_text: String,
+}
+
+impl Link {
+    pub fn is_suspicious(&self) -> bool {
+        self.actions.iter().any(LinkAction::is_suspicious)
+    }
+}
+
+/// A file embedded in the document, such as the XML of an e-invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedFile {
+    /// `/UF` or `/F` of the file specification.
+    pub name: String,
+    pub description: Option<String>,
+    /// The stream's `/Subtype`, such as `text/xml`.
+    pub mime_type: Option<String>,
+    /// `/AFRelationship`, such as `Alternative` or `Data`.
+    pub relationship: Option<String>,
+    /// Size in bytes and MD5 checksum as recorded in `/Params`; they are not
+    /// checked against `data`.
+    pub size: Option<usize>,
+    pub checksum: Option<Vec<u8>>,
+    pub creation_date: Option<PdfDate>,
+    pub mod_date: Option<PdfDate>,
+    /// Index of the page whose FileAttachment annotation holds the file, or
+    /// `None` for files in the `/EmbeddedFiles` name tree.
+    pub page: Option<usize>,
+    /// The decoded contents, or `None` when the stream's filters are not
+    /// supported.
+    pub data: Option<Vec<u8>>,
+}
+
+/// An image drawn on a page, either an image XObject or an inline image.
+#[derive(Debug, Clone)]
+pub struct PageImage {
+    /// Resource name of an image XObject (`Im1`); `None` for inline images.
+    pub name: Option<String>,
+    /// Bounds of the unit square under the CTM, on the displayed page like
+    /// [`TextGlyph`] positions.
+    pub rect: [f64; 4],
+    /// The CTM followed by the page's display transform: maps the unit
+    /// square, whose top edge holds the first row of samples, onto the
+    /// displayed page.
+    pub matrix: [f64; 6],
+    pub width: u32,
+    pub height: u32,
+    /// Colour space family, such as `DeviceRGB`, `ICCBased` or `Indexed`.
+    pub color_space: Option<String>,
+    /// Colour components per sample: 1 for gray, indexed and mask images,
+    /// 3 for RGB, 4 for CMYK.
+    pub components: u8,
+    pub bits_per_component: u8,
+    pub image_mask: bool,
+    /// Filters in the order they are undone, with inline image abbreviations
+    /// expanded.
+    pub filters: Vec<String>,
+    /// Parameters of each filter, empty when it has none.
+    pub decode_parms: Vec<HashMap<String, PdfObj>>,
+    /// The lookup table of an Indexed colour space, as RGB triples.
+    pub palette: Option<Vec<u8>>,
+    /// The image data as stored, still encoded.
+    pub data: Vec<u8>,
+}
+
+/// A page drawn by [`crate::render_page`].
+#[derive(Debug, Clone)]
+pub struct RenderedPage {
+    pub width: usize,
+    pub height: usize,
+    /// RGBA pixels, row by row from the top left.
+    pub pixels: Vec<u8>,
+}
+
+impl RenderedPage {
+    /// The page as a PNG file.
+    pub fn to_png(&self) -> Vec<u8> {
+        crate::images::encode_rgba_png(self.width, self.height, &self.pixels)
+    }
+}
+
+/// An image in a standalone file format, from [`crate::export_image`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageExport {
+    Jpeg(Vec<u8>),
+    Jpeg2000(Vec<u8>),
+    Png(Vec<u8>),
+}
+
+/// A QR code read from an image on a page, from [`crate::extract_qr_codes`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrCode {
+    pub page: usize,
+    /// Bounds of the symbol on the displayed page, like [`PageImage::rect`].
+    pub rect: [f64; 4],
+    pub version: u8,
+    /// The bytes of all segments in order; kanji segments are Shift JIS.
+    pub payload: Vec<u8>,
+    /// The payload as UTF-8, or as Latin-1 when it is not valid UTF-8.
+    pub text: String,
+}
+
+/// A value from the `datasets` packet of an XFA form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XfaField {
+    /// Path of the data element below `xfa:data`, in XFA SOM form such as
+    /// `form1.applicant.name` or `form1.item[1].amount`.
+    pub path: String,
+    pub value: String,
 }
 
 #[derive(Debug, Clone)]
@@ -60,4 +680,10 @@ pub enum Token {
     Array(Vec<Token>),
     ArrayStart,
     ArrayEnd,
+    /// An inline image: the key-value tokens between `BI` and `ID`, and the
+    /// data between `ID` and `EI`.
+    InlineImage {
+        params: Vec<Token>,
+        data: Vec<u8>,
+    },
 }
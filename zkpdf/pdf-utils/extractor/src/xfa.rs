@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::types::{PdfDocument, PdfObj, XfaField};
+use crate::xml::{self, Element};
+use crate::{decode_stream, resolve};
+
+/// The leaves of the XFA `datasets` packet's `data` tree, in document order.
+pub fn xfa_fields(document: &PdfDocument) -> Vec<XfaField> {
+    let Some(packet) = xfa_packet(document) else {
+        return Vec::new();
+    };
+    let root = xml::parse(&packet);
+    let Some(data) = root
+        .descendant("datasets")
+        .and_then(|datasets| datasets.child("data"))
+    else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    collect_leaves(data, "", &mut fields);
+    fields
+}
+
+/// The XFA data of a dynamic form, whose pages hold only a placeholder, as
+/// `path: value` lines. Static forms also fill in their AcroForm fields, and
+/// their values are already drawn on the pages.
+pub fn dynamic_form_text(document: &PdfDocument) -> Option<String> {
+    let catalog = document.catalog()?;
+    let objects = &document.objects;
+    let needs_rendering = matches!(
+        catalog.get("NeedsRendering").map(|n| resolve(n, objects)),
+        Some(PdfObj::Boolean(true))
+    );
+    let has_fields = matches!(
+        acro_form(document)?.get("Fields").map(|f| resolve(f, objects)),
+        Some(PdfObj::Array(fields)) if !fields.is_empty()
+    );
+    if !needs_rendering && has_fields {
+        return None;
+    }
+    let lines: Vec<String> = xfa_fields(document)
+        .into_iter()
+        .filter(|field| !field.value.is_empty())
+        .map(|field| format!("{}: {}", field.path, field.value))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn acro_form(document: &PdfDocument) -> Option<&HashMap<String, PdfObj>> {
+    match resolve(document.catalog()?.get("AcroForm")?, &document.objects) {
+        PdfObj::Dictionary(form) => Some(form),
+        _ => None,
+    }
+}
+
+// `/XFA` is either one stream holding the whole XDP document or an array of
+// packet names and streams, whose contents joined in order make up the same
+// document. Packets may be split across streams, so the XML is only parsed
+// once reassembled.
+fn xfa_packet(document: &PdfDocument) -> Option<String> {
+    let objects = &document.objects;
+    let mut bytes = Vec::new();
+    match resolve(acro_form(document)?.get("XFA")?, objects) {
+        PdfObj::Stream(stream) => bytes = decode_stream(stream),
+        PdfObj::Array(items) => {
+            for item in items.iter().skip(1).step_by(2) {
+                if let PdfObj::Stream(stream) = resolve(item, objects) {
+                    bytes.extend(decode_stream(stream));
+                }
+            }
+        }
+        _ => return None,
+    }
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// Data values are elements without child elements, unless marked as an
+// (empty) group with `xfa:dataNode="dataGroup"`. Paths use XFA SOM
+// syntax: local names joined with `.`, with `[n]` on names that repeat
+// among their siblings. `xml::parse` caps the nesting, which bounds the
+// recursion.
+fn collect_leaves(element: &Element, path: &str, fields: &mut Vec<XfaField>) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for child in element.elements() {
+        *counts.entry(child.local_name()).or_default() += 1;
+    }
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for child in element.elements() {
+        let name = child.local_name();
+        let index = seen.entry(name).or_default();
+        let segment = if counts[name] > 1 {
+            format!("{name}[{index}]")
+        } else {
+            name.to_string()
+        };
+        *index += 1;
+        let child_path = if path.is_empty() {
+            segment
+        } else {
+            format!("{path}.{segment}")
+        };
+        let group = child.attribute("dataNode") == Some("dataGroup");
+        if child.elements().next().is_none() && !group {
+            fields.push(XfaField {
+                path: child_path,
+                value: child.text().trim().to_string(),
+            });
+        } else {
+            collect_leaves(child, &child_path, fields);
+        }
+    }
+}
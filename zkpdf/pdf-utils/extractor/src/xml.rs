@@ -0,0 +1,284 @@
+//! A small, lenient XML reader for the packets embedded in PDFs (XFA, XMP).
+
+/// Elements nest at most this deep; deeper start tags are dropped and their
+/// content goes to the deepest open element.
+pub const MAX_DEPTH: usize = 256;
+
+/// An element with its namespace prefix kept in `name`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// Name without its namespace prefix.
+    pub fn local_name(&self) -> &str {
+        local(&self.name)
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// First child element with the given local name.
+    pub fn child(&self, local_name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.local_name() == local_name)
+    }
+
+    /// First descendant element, depth first, with the given local name.
+    pub fn descendant(&self, local_name: &str) -> Option<&Element> {
+        self.elements().find_map(|e| {
+            if e.local_name() == local_name {
+                Some(e)
+            } else {
+                e.descendant(local_name)
+            }
+        })
+    }
+
+    /// Attribute value by local name.
+    pub fn attribute(&self, local_name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(name, _)| local(name) == local_name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Concatenated text of the element and its descendants.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        let mut pending = vec![self.children.iter()];
+        while let Some(children) = pending.last_mut() {
+            match children.next() {
+                Some(Node::Text(t)) => text.push_str(t),
+                Some(Node::Element(e)) => pending.push(e.children.iter()),
+                None => {
+                    pending.pop();
+                }
+            }
+        }
+        text
+    }
+}
+
+fn local(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Parses `data` into a document whose root holds the top-level nodes.
+/// Malformed markup is skipped rather than rejected: unclosed elements are
+/// closed at the end and stray end tags are ignored.
+pub fn parse(data: &str) -> Element {
+    let mut stack = vec![Element::default()];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            push_text(&mut stack, rest);
+            break;
+        };
+        push_text(&mut stack, &rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").unwrap_or(body.len());
+            append_text(&mut stack, &body[..end]);
+            rest = body.get(end + 3..).unwrap_or("");
+        } else if let Some(body) = rest.strip_prefix("<!--") {
+            rest = body.find("-->").map_or("", |end| &body[end + 3..]);
+        } else if let Some(body) = rest.strip_prefix("<?") {
+            rest = body.find("?>").map_or("", |end| &body[end + 2..]);
+        } else if rest.starts_with("<!") {
+            rest = skip_declaration(rest);
+        } else if let Some(body) = rest.strip_prefix("</") {
+            let end = body.find('>').unwrap_or(body.len());
+            close(&mut stack, body[..end].trim());
+            rest = body.get(end + 1..).unwrap_or("");
+        } else {
+            let (element, self_closing, remaining) = start_tag(&rest[1..]);
+            rest = remaining;
+            if self_closing {
+                add_child(&mut stack, element);
+            } else if stack.len() <= MAX_DEPTH {
+                // The document root sits below the top-level elements.
+                stack.push(element);
+            }
+        }
+    }
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap_or_default();
+        add_child(&mut stack, element);
+    }
+    stack.pop().unwrap_or_default()
+}
+
+// Skips `<!DOCTYPE ...>` and similar, including an internal subset in `[ ]`.
+fn skip_declaration(rest: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '>' if depth <= 0 => return &rest[i + 1..],
+            _ => {}
+        }
+    }
+    ""
+}
+
+// Parses a start tag after its `<`, returning the element, whether it closed
+// itself with `/>`, and the input after the tag.
+fn start_tag(tag: &str) -> (Element, bool, &str) {
+    let name_end = tag
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(tag.len());
+    let mut element = Element {
+        name: tag[..name_end].to_string(),
+        ..Element::default()
+    };
+    let mut rest = &tag[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return (element, true, after);
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return (element, false, after);
+        }
+        let Some(eq) = rest.find('=') else {
+            return (element, true, "");
+        };
+        let name = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|q| *q == '"' || *q == '\'') else {
+            // Not an attribute; skip to the end of the tag.
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            return (element, rest[..end].ends_with("/>"), &rest[end..]);
+        };
+        let value = &value[1..];
+        let end = value.find(quote).unwrap_or(value.len());
+        element.attributes.push((name, unescape(&value[..end])));
+        rest = value.get(end + 1..).unwrap_or("");
+    }
+}
+
+fn close(stack: &mut Vec<Element>, name: &str) {
+    // Ignore end tags that match no open element.
+    let Some(depth) = stack.iter().rposition(|e| e.name == name) else {
+        return;
+    };
+    if depth == 0 {
+        return;
+    }
+    while stack.len() > depth {
+        let element = stack.pop().unwrap_or_default();
+        add_child(stack, element);
+    }
+}
+
+fn add_child(stack: &mut [Element], element: Element) {
+    if let Some(parent) = stack.last_mut() {
+        parent.children.push(Node::Element(element));
+    }
+}
+
+fn push_text(stack: &mut [Element], raw: &str) {
+    if !raw.is_empty() {
+        append_text(stack, &unescape(raw));
+    }
+}
+
+fn append_text(stack: &mut [Element], text: &str) {
+    let Some(parent) = stack.last_mut() else {
+        return;
+    };
+    match parent.children.last_mut() {
+        Some(Node::Text(existing)) => existing.push_str(text),
+        _ => parent.children.push(Node::Text(text.to_string())),
+    }
+}
+
+/// Replaces the predefined entities and character references.
+pub fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|semi| *semi <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_elements_text_and_entities() {
+        let doc = parse(
+            "<?xml version=\"1.0\"?><!-- c --><a:root x='1 &amp; 2'><b>T &lt;1&gt; &#x4E2D;</b>\
+             <c/><d><![CDATA[<raw>]]></d></a:root>",
+        );
+        let root = doc.child("root").unwrap();
+        assert_eq!(root.attribute("x"), Some("1 & 2"));
+        assert_eq!(root.child("b").unwrap().text(), "T <1> 中");
+        assert!(root.child("c").unwrap().children.is_empty());
+        assert_eq!(root.descendant("d").unwrap().text(), "<raw>");
+    }
+
+    #[test]
+    fn nesting_past_the_depth_cap_is_dropped() {
+        let depth = 100_000;
+        let doc = parse(&format!("{}x{}", "<a>".repeat(depth), "</a>".repeat(depth)));
+        assert_eq!(doc.text(), "x");
+
+        let mut levels = 0;
+        let mut element = &doc;
+        while let Some(child) = element.child("a") {
+            element = child;
+            levels += 1;
+        }
+        assert_eq!(levels, MAX_DEPTH);
+        assert_eq!(element.children, [Node::Text("x".to_string())]);
+    }
+}
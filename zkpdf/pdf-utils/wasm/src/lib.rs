@@ -1,4 +1,4 @@
-use core::verify_text;
+use core::{MatchMode, verify_text, verify_text_with_mode};
 use extractor::extract_text;
 use wasm_bindgen::prelude::*;
 
@@ -8,6 +8,20 @@ pub fn wasm_verify_text(pdf_bytes: &[u8], page_number: u8, sub_string: &str) ->
     verify_text(pdf_bytes.to_vec(), page_number, sub_string).unwrap_or(false)
 }
 
+/// WebAssembly export: verify text under a match mode given as
+/// `MatchMode::to_bits` flags; unknown flags fail the check
+#[wasm_bindgen]
+pub fn wasm_verify_text_with_mode(
+    pdf_bytes: &[u8],
+    page_number: u8,
+    sub_string: &str,
+    match_mode: u8,
+) -> bool {
+    MatchMode::from_bits(match_mode)
+        .and_then(|mode| verify_text_with_mode(pdf_bytes.to_vec(), page_number, sub_string, mode))
+        .unwrap_or(false)
+}
+
 /// WebAssembly export: extract raw text content per page
 #[wasm_bindgen]
 pub fn wasm_extract_text(pdf_bytes: &[u8]) -> Vec<JsValue> {